@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use lending_client::lending::constants::BASIS_POINTS;
+use lending_client::lending::state::User;
+use lending_client::math::{self, LiquidationQuote, HEALTHY_HEALTH_FACTOR};
+use lending_client::rpc::{RpcClient, RpcClientExt};
+use lending_client::{instructions, PROGRAM_ID};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::market::Market;
+
+pub struct Config {
+    /// Minimum profit of a liquidation, the value of the seized collateral minus the repaid debt,
+    /// in USD scaled by 10^VALUE_DECIMALS
+    pub min_profit: u128,
+    /// Minimum profit relative to the repaid debt, in basis points
+    pub min_profit_bps: u64,
+    /// Only log the liquidations that would be sent
+    pub dry_run: bool,
+    /// Price update accounts to use for a mint instead of its push oracle feed
+    pub price_updates: HashMap<Pubkey, Pubkey>,
+}
+
+/// The most profitable liquidation of an unhealthy user
+pub struct Opportunity {
+    pub user_account: Pubkey,
+    pub collateral_bank: Pubkey,
+    pub borrowed_bank: Pubkey,
+    pub health_factor: u64,
+    pub quote: LiquidationQuote,
+    pub profit: u128,
+}
+
+/// Finds the pair of the user's collateral and debt whose liquidation is most profitable
+/// and meets the configured thresholds
+pub fn find_opportunity(market: &Market, config: &Config, user_account: &Pubkey, user: &User) -> Option<Opportunity> {
+    if !user.has_borrows() {
+        return None;
+    }
+    let emode = market.emode(user).ok()?;
+    let priced_banks = market.priced_banks(user)?;
+    let health_factor = math::health_factor(user, &priced_banks, emode.as_ref());
+    if health_factor >= HEALTHY_HEALTH_FACTOR {
+        return None;
+    }
+
+    let mut best: Option<Opportunity> = None;
+    for borrowed in user.active_positions().filter(|position| position.borrow_shares > 0) {
+        for collateral in user.active_positions().filter(|position| position.deposit_shares > 0) {
+            // The program takes a single token program for both mints, and distinct banks
+            if borrowed.bank == collateral.bank || market.token_programs.get(&borrowed.bank) != market.token_programs.get(&collateral.bank) {
+                continue;
+            }
+            let Some(quote) = math::liquidation_quote(user, &priced_banks, emode.as_ref(), &collateral.bank, &borrowed.bank) else {
+                continue;
+            };
+            let profit = quote.seized_value.saturating_sub(quote.repaid_value);
+            if best.as_ref().is_none_or(|best| profit > best.profit) {
+                best = Some(Opportunity {
+                    user_account: *user_account,
+                    collateral_bank: collateral.bank,
+                    borrowed_bank: borrowed.bank,
+                    health_factor,
+                    quote,
+                    profit,
+                });
+            }
+        }
+    }
+    best.filter(|best| {
+        best.quote.repaid_amount > 0
+            && best.profit >= config.min_profit
+            && best.profit * BASIS_POINTS as u128 >= best.quote.repaid_value * config.min_profit_bps as u128
+    })
+}
+
+pub struct Keeper {
+    pub rpc: RpcClient,
+    pub liquidator: Keypair,
+    pub config: Config,
+}
+
+impl Keeper {
+    /// Scans every user once and liquidates the unhealthy ones that are worth it.
+    /// Returns the number of liquidations sent.
+    pub fn scan(&self) -> Result<usize> {
+        let market = Market::load(&self.rpc, &self.config.price_updates)?;
+        let users = self.rpc.get_program_anchor_accounts::<User>(&PROGRAM_ID)?;
+
+        let mut liquidations = 0;
+        for (user_account, user) in &users {
+            let Some(opportunity) = find_opportunity(&market, &self.config, user_account, user) else {
+                continue;
+            };
+            println!(
+                "user {} health {}%: repay {} of bank {} for {} of bank {}, profit {}",
+                opportunity.user_account,
+                opportunity.health_factor,
+                opportunity.quote.repaid_amount,
+                opportunity.borrowed_bank,
+                opportunity.quote.seized_amount,
+                opportunity.collateral_bank,
+                opportunity.profit,
+            );
+            if self.config.dry_run {
+                continue;
+            }
+            match self.liquidate(&market, user, &opportunity) {
+                Ok(signature) => {
+                    println!("liquidated user {user_account}: {signature}");
+                    liquidations += 1;
+                }
+                Err(err) => eprintln!("failed to liquidate user {user_account}: {err:#}"),
+            }
+        }
+        Ok(liquidations)
+    }
+
+    fn liquidate(&self, market: &Market, user: &User, opportunity: &Opportunity) -> Result<Signature> {
+        let remaining_accounts = instructions::health_accounts(user, |bank| market.price_updates[bank]);
+        let ix = instructions::liquidate(
+            &self.liquidator.pubkey(),
+            &opportunity.user_account,
+            &market.banks[&opportunity.collateral_bank].mint_address,
+            &market.banks[&opportunity.borrowed_bank].mint_address,
+            &market.token_programs[&opportunity.borrowed_bank],
+            &remaining_accounts,
+        );
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(&[ix], Some(&self.liquidator.pubkey()), &[&self.liquidator], blockhash);
+        Ok(self.rpc.send_and_confirm_transaction(&transaction)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{AnchorDeserialize, Space};
+    use lending_client::accounts;
+    use lending_client::lending::constants::INDEX_SCALE;
+    use lending_client::lending::state::Bank;
+    use lending_client::mock_oracle::MockPriceUpdate;
+    use pyth_solana_receiver_sdk::price_update::Price;
+
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+    const PRICE_EXPONENT: i32 = -8;
+
+    /// Market of a SOL bank with a 5% liquidation bonus, an ETH bank with 10% and a USDC bank, at $100, $1,000 and $1
+    struct Fixture {
+        market: Market,
+        sol: Pubkey,
+        eth: Pubkey,
+        usdc: Pubkey,
+    }
+
+    fn bank(feed_id: u8, mint_decimals: u8, liquidation_bonus: u64) -> Bank {
+        let mut bank = Bank::deserialize(&mut vec![0; Bank::INIT_SPACE].as_slice()).unwrap();
+        bank.mint_address = Pubkey::new_unique();
+        bank.mint_decimals = mint_decimals;
+        bank.oracle_feed_id = [feed_id; 32];
+        bank.max_price_age = 60;
+        (bank.max_ltv, bank.liquidation_threshold, bank.liquidation_bonus, bank.liquidation_close_factor) = (70, 80, liquidation_bonus, 50);
+        (bank.borrow_index, bank.deposit_index) = (INDEX_SCALE, INDEX_SCALE);
+        (bank.collateral_enabled, bank.borrow_enabled, bank.listed) = (true, true, true);
+        bank.last_updated = NOW;
+        bank
+    }
+
+    /// Checked price of `bank` from a mock price update account, like `Market::load` reads it
+    fn price(bank: &Bank, dollars: i64) -> Price {
+        let update = MockPriceUpdate::new(bank.oracle_feed_id, dollars * 10i64.pow(PRICE_EXPONENT.unsigned_abs()), PRICE_EXPONENT)
+            .publish_time(NOW);
+        let price_update = accounts::deserialize_price_update(&update.data()).unwrap();
+        math::checked_price(bank, &price_update, NOW).unwrap()
+    }
+
+    fn setup() -> Fixture {
+        let (sol, eth, usdc) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut banks = HashMap::from([(sol, bank(1, 9, 5)), (eth, bank(2, 8, 10)), (usdc, bank(3, 6, 5))]);
+        for bank in banks.values_mut() {
+            (bank.total_deposits, bank.total_deposit_shares) = (u64::MAX / 2, u64::MAX / 2);
+            (bank.total_borrowed, bank.total_borrowed_shares) = (1_000_000_000, 1_000_000_000);
+        }
+        let prices = [(sol, 100), (eth, 1_000), (usdc, 1)]
+            .into_iter()
+            .map(|(key, dollars)| (key, price(&banks[&key], dollars)))
+            .collect();
+        let token_program = Pubkey::new_unique();
+        let market = Market {
+            now: NOW,
+            token_programs: banks.keys().map(|key| (*key, token_program)).collect(),
+            price_updates: banks.keys().map(|key| (*key, Pubkey::new_unique())).collect(),
+            banks,
+            emode_categories: HashMap::new(),
+            prices,
+        };
+        Fixture { market, sol, eth, usdc }
+    }
+
+    fn user(deposits: &[(Pubkey, u64)], borrows: &[(Pubkey, u64)]) -> User {
+        let mut user = User::deserialize(&mut vec![0; User::INIT_SPACE].as_slice()).unwrap();
+        user.owner = Pubkey::new_unique();
+        for (bank, shares) in deposits {
+            user.get_or_create_position(bank).unwrap().deposit_shares = *shares;
+        }
+        for (bank, shares) in borrows {
+            user.get_or_create_position(bank).unwrap().borrow_shares = *shares;
+        }
+        user
+    }
+
+    fn config(min_profit: u128, min_profit_bps: u64) -> Config {
+        Config { min_profit, min_profit_bps, dry_run: true, price_updates: HashMap::new() }
+    }
+
+    #[test]
+    fn healthy_user_is_skipped() {
+        let fixture = setup();
+        // $80 liquidation value against $50 of debt
+        let user = user(&[(fixture.sol, 1_000_000_000)], &[(fixture.usdc, 50_000_000)]);
+        assert!(find_opportunity(&fixture.market, &config(0, 0), &Pubkey::new_unique(), &user).is_none());
+        // Users without borrows are never liquidatable
+        let user = self::user(&[(fixture.sol, 1_000_000_000)], &[]);
+        assert!(find_opportunity(&fixture.market, &config(0, 0), &Pubkey::new_unique(), &user).is_none());
+    }
+
+    #[test]
+    fn unprofitable_liquidation_is_skipped() {
+        let fixture = setup();
+        // $80 liquidation value against $90 of debt
+        let user = user(&[(fixture.sol, 1_000_000_000)], &[(fixture.usdc, 90_000_000)]);
+        let user_account = Pubkey::new_unique();
+
+        // Half the debt, $45, repaid for $47.25 of collateral
+        let opportunity = find_opportunity(&fixture.market, &config(0, 0), &user_account, &user).unwrap();
+        assert_eq!(opportunity.health_factor, 88);
+        assert_eq!((opportunity.quote.repaid_amount, opportunity.quote.seized_amount), (45_000_000, 472_500_000));
+        assert_eq!(opportunity.profit, 2_250_000);
+        assert!(find_opportunity(&fixture.market, &config(2_250_000, 500), &user_account, &user).is_some());
+
+        // Below the minimum profit in USD or relative to the repaid debt
+        assert!(find_opportunity(&fixture.market, &config(2_250_001, 0), &user_account, &user).is_none());
+        assert!(find_opportunity(&fixture.market, &config(0, 501), &user_account, &user).is_none());
+    }
+
+    #[test]
+    fn most_profitable_pair_is_chosen() {
+        let fixture = setup();
+        // $50 of SOL with a 5% bonus and $50 of ETH with a 10% bonus against $90 of debt
+        let user = user(&[(fixture.sol, 500_000_000), (fixture.eth, 5_000_000)], &[(fixture.usdc, 90_000_000)]);
+
+        let opportunity = find_opportunity(&fixture.market, &config(0, 0), &Pubkey::new_unique(), &user).unwrap();
+        assert_eq!((opportunity.collateral_bank, opportunity.borrowed_bank), (fixture.eth, fixture.usdc));
+        // $45 repaid for $49.50 of ETH
+        assert_eq!((opportunity.quote.repaid_amount, opportunity.quote.seized_amount), (45_000_000, 4_950_000));
+        assert_eq!(opportunity.profit, 4_500_000);
+
+        // A pair the program cannot liquidate in one instruction is never chosen
+        let mut market = fixture.market;
+        market.token_programs.insert(fixture.eth, Pubkey::new_unique());
+        let opportunity = find_opportunity(&market, &config(0, 0), &Pubkey::new_unique(), &user).unwrap();
+        assert_eq!(opportunity.collateral_bank, fixture.sol);
+    }
+}
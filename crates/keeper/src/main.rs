@@ -0,0 +1,104 @@
+//! Liquidation keeper for the lending program.
+//!
+//! Every scan loads all banks, their prices and all users from the RPC endpoint, values each user
+//! with the program's own health math, and liquidates unhealthy users whose most profitable
+//! liquidation meets the configured thresholds.
+
+mod keeper;
+mod market;
+
+use std::collections::HashMap;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use lending_client::lending::constants::VALUE_DECIMALS;
+use lending_client::rpc;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Signer};
+
+use crate::keeper::{Config, Keeper};
+
+const USAGE: &str = "Usage: keeper [OPTIONS]
+
+Options:
+  --url <URL>                  RPC endpoint, default http://127.0.0.1:8899
+  --keypair <PATH>             Liquidator keypair file, default ~/.config/solana/id.json
+  --min-profit <USD>           Minimum value of the seized collateral over the repaid debt, default 0
+  --min-profit-bps <BPS>       Minimum profit relative to the repaid debt, default 0
+  --interval <SECONDS>         Time between scans, default 10
+  --price-update <MINT>=<ACCOUNT>  Price update account to use for a mint instead of its
+                                   Pyth push oracle feed, can be repeated
+  --once                       Scan once and exit
+  --dry-run                    Log liquidations without sending them";
+
+/// Parses a USD amount such as `1.25` into a value scaled by 10^VALUE_DECIMALS
+fn parse_usd(value: &str) -> Result<u128> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if fraction.len() > VALUE_DECIMALS as usize {
+        bail!("too many decimals in {value}");
+    }
+    let scale = 10u128.pow(VALUE_DECIMALS as u32);
+    let fraction = format!("{fraction:0<width$}", width = VALUE_DECIMALS as usize);
+    Ok(whole.parse::<u128>()? * scale + fraction.parse::<u128>()?)
+}
+
+fn run() -> Result<()> {
+    let mut url = "http://127.0.0.1:8899".to_string();
+    let mut keypair_path = format!("{}/.config/solana/id.json", std::env::var("HOME").unwrap_or_default());
+    let mut interval = 10;
+    let mut once = false;
+    let mut config = Config { min_profit: 0, min_profit_bps: 0, dry_run: false, price_updates: HashMap::new() };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("missing value for {arg}"));
+        match arg.as_str() {
+            "--url" => url = value()?,
+            "--keypair" => keypair_path = value()?,
+            "--min-profit" => config.min_profit = parse_usd(&value()?).context("invalid --min-profit")?,
+            "--min-profit-bps" => config.min_profit_bps = value()?.parse().context("invalid --min-profit-bps")?,
+            "--interval" => interval = value()?.parse().context("invalid --interval")?,
+            "--price-update" => {
+                let value = value()?;
+                let (mint, account) = value
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("invalid --price-update {value}, expected <MINT>=<ACCOUNT>"))?;
+                config.price_updates.insert(Pubkey::from_str(mint)?, Pubkey::from_str(account)?);
+            }
+            "--once" => once = true,
+            "--dry-run" => config.dry_run = true,
+            "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => bail!("unknown argument {arg}\n\n{USAGE}"),
+        }
+    }
+
+    let liquidator = read_keypair_file(&keypair_path).map_err(|err| anyhow!("failed to read keypair {keypair_path}: {err}"))?;
+    println!("liquidator {}", liquidator.pubkey());
+    let keeper = Keeper { rpc: rpc::client(&url), liquidator, config };
+    loop {
+        match keeper.scan() {
+            Ok(liquidations) => println!("scan done, {liquidations} liquidations sent"),
+            Err(err) => eprintln!("scan failed: {err:#}"),
+        }
+        if once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use lending_client::lending::state::{Bank, EModeCategory, User};
+use lending_client::math::{self, PricedBank};
+use lending_client::rpc::{RpcClient, RpcClientExt};
+use lending_client::{accounts, pda, PROGRAM_ID};
+use pyth_solana_receiver_sdk::price_update::Price;
+use solana_sdk::pubkey::Pubkey;
+
+/// Snapshot of every bank, e-mode category and bank price, taken once per scan
+/// so that all users are valued against the same cached prices
+pub struct Market {
+    pub now: i64,
+    pub banks: HashMap<Pubkey, Bank>,
+    pub emode_categories: HashMap<u8, EModeCategory>,
+    /// Checked price of each bank's asset, banks with stale or missing prices are left out
+    pub prices: HashMap<Pubkey, Price>,
+    /// Token program of each bank's mint
+    pub token_programs: HashMap<Pubkey, Pubkey>,
+    /// Price update account each bank is priced from
+    pub price_updates: HashMap<Pubkey, Pubkey>,
+}
+
+impl Market {
+    /// `price_updates` overrides the price update account of a mint, for example with a mock oracle account
+    pub fn load(rpc: &RpcClient, price_updates: &HashMap<Pubkey, Pubkey>) -> Result<Self> {
+        let now = rpc.get_clock()?.unix_timestamp;
+        let banks: HashMap<Pubkey, Bank> = rpc.get_program_anchor_accounts::<Bank>(&PROGRAM_ID)?.into_iter().collect();
+        let emode_categories = rpc
+            .get_program_anchor_accounts::<EModeCategory>(&PROGRAM_ID)?
+            .into_iter()
+            .map(|(_, category)| (category.id, category))
+            .collect();
+
+        let bank_keys: Vec<Pubkey> = banks.keys().copied().collect();
+        let price_updates: HashMap<Pubkey, Pubkey> = bank_keys
+            .iter()
+            .map(|key| {
+                let bank = &banks[key];
+                let price_update = price_updates
+                    .get(&bank.mint_address)
+                    .copied()
+                    .unwrap_or_else(|| pda::price_feed(&bank.oracle_feed_id, 0));
+                (*key, price_update)
+            })
+            .collect();
+
+        let price_accounts = rpc.get_accounts(&bank_keys.iter().map(|key| price_updates[key]).collect::<Vec<_>>())?;
+        let mut prices = HashMap::new();
+        for (key, account) in bank_keys.iter().zip(price_accounts) {
+            let price = account
+                .ok_or_else(|| anyhow!("price update {} not found", price_updates[key]))
+                .and_then(|account| Ok(accounts::deserialize_price_update(&account.data)?))
+                .and_then(|price_update| Ok(math::checked_price(&banks[key], &price_update, now)?));
+            match price {
+                Ok(price) => {
+                    prices.insert(*key, price);
+                }
+                Err(err) => eprintln!("skipping bank {key}: {err}"),
+            }
+        }
+
+        let mint_keys: Vec<Pubkey> = bank_keys.iter().map(|key| banks[key].mint_address).collect();
+        let token_programs = bank_keys
+            .iter()
+            .zip(rpc.get_accounts(&mint_keys)?)
+            .filter_map(|(key, mint)| Some((*key, mint?.owner)))
+            .collect();
+
+        Ok(Self { now, banks, emode_categories, prices, token_programs, price_updates })
+    }
+
+    pub fn emode(&self, user: &User) -> Result<Option<EModeCategory>> {
+        match user.emode_category {
+            0 => Ok(None),
+            id => match self.emode_categories.get(&id) {
+                Some(category) => Ok(Some(category.clone())),
+                None => Err(anyhow!("e-mode category {id} not found")),
+            },
+        }
+    }
+
+    /// The user's banks accrued and priced as the program would, `None` if a bank or its price is missing
+    /// or its interest overflows
+    pub fn priced_banks(&self, user: &User) -> Option<Vec<PricedBank>> {
+        user.active_positions()
+            .map(|position| {
+                let bank = self.banks.get(&position.bank)?;
+                let price = self.prices.get(&position.bank)?;
+                math::priced_bank(position.bank, bank, *price, self.now).ok()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,246 @@
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, FeedId};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::cli::DEFAULT_URL;
+
+/// Operator command-line tool for the lending program.
+///
+/// Amounts are in the token's base units.
+#[derive(Debug, Parser)]
+#[command(name = "lending-cli", version)]
+pub struct Args {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Connection, wallet and output options shared by all commands
+#[derive(Debug, ClapArgs)]
+pub struct GlobalArgs {
+    /// RPC endpoint
+    #[arg(long, global = true, default_value = DEFAULT_URL)]
+    pub url: String,
+    /// Signer keypair file, default ~/.config/solana/id.json
+    #[arg(long, global = true)]
+    pub keypair: Option<String>,
+    #[arg(long, global = true, value_enum, default_value_t = Output::Display)]
+    pub output: Output,
+    /// Price update account to use for a mint instead of its Pyth push oracle feed, can be repeated
+    #[arg(long = "price-update", global = true, value_name = "MINT=ACCOUNT", value_parser = parse_price_update)]
+    pub price_updates: Vec<(Pubkey, Pubkey)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    Display,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create a bank for a mint
+    InitBank {
+        mint: Pubkey,
+        /// Percent
+        #[arg(long)]
+        liquidation_threshold: u64,
+        /// Percent
+        #[arg(long)]
+        max_ltv: u64,
+        /// Pyth feed id in hex
+        #[arg(long, value_parser = parse_feed_id)]
+        feed_id: FeedId,
+    },
+    /// Change settings the bank authority can change directly. Settings changed together
+    /// on-chain keep their current value when only one of them is given.
+    UpdateBank {
+        mint: Pubkey,
+        /// Basis points
+        #[arg(long)]
+        flash_loan_fee: Option<u64>,
+        #[arg(long)]
+        deposit_limit: Option<u64>,
+        #[arg(long)]
+        borrow_limit: Option<u64>,
+        #[arg(long)]
+        borrow_enabled: Option<bool>,
+        #[arg(long)]
+        pause_flags: Option<u8>,
+    },
+    /// Queue a change of risk parameters behind the timelock, unspecified ones keep their current value
+    QueueConfigChange {
+        mint: Pubkey,
+        #[command(flatten)]
+        params: RiskParamArgs,
+    },
+    /// Apply a queued config change once its timelock has passed
+    ExecuteConfigChange { mint: Pubkey },
+    /// Show a bank's totals, utilization and rates
+    Bank { mint: Pubkey },
+    /// Create a user account
+    InitUser {
+        #[arg(long, default_value_t = 0)]
+        sub_account: u8,
+    },
+    /// Show a user's positions and health
+    User {
+        /// Wallet owning the user account, default the signer
+        #[arg(long)]
+        owner: Option<Pubkey>,
+        #[arg(long, default_value_t = 0)]
+        sub_account: u8,
+    },
+    /// Deposit tokens for receipt tokens
+    Deposit { mint: Pubkey, amount: u64 },
+    /// Burn receipt tokens for the underlying tokens
+    Withdraw { mint: Pubkey, amount: u64 },
+    /// Lock receipt tokens into the user account as collateral
+    DepositCollateral {
+        mint: Pubkey,
+        shares: u64,
+        #[arg(long, default_value_t = 0)]
+        sub_account: u8,
+    },
+    /// Release collateral back to receipt tokens
+    WithdrawCollateral {
+        mint: Pubkey,
+        shares: u64,
+        #[arg(long, default_value_t = 0)]
+        sub_account: u8,
+    },
+    /// Borrow against the user's collateral
+    Borrow {
+        mint: Pubkey,
+        amount: u64,
+        #[arg(long, default_value_t = 0)]
+        sub_account: u8,
+    },
+    /// Repay a borrow
+    Repay {
+        mint: Pubkey,
+        amount: u64,
+        #[arg(long, default_value_t = 0)]
+        sub_account: u8,
+    },
+    /// Repay an unhealthy user's debt for their collateral plus the liquidation bonus
+    Liquidate {
+        user_account: Pubkey,
+        collateral_mint: Pubkey,
+        borrowed_mint: Pubkey,
+    },
+}
+
+/// Risk parameters of `queue-config-change`
+#[derive(Debug, ClapArgs)]
+pub struct RiskParamArgs {
+    /// Percent
+    #[arg(long)]
+    pub max_ltv: Option<u64>,
+    /// Percent
+    #[arg(long)]
+    pub liquidation_threshold: Option<u64>,
+    /// Percent
+    #[arg(long)]
+    pub liquidation_bonus: Option<u64>,
+    /// Percent
+    #[arg(long)]
+    pub liquidation_close_factor: Option<u64>,
+    /// Basis points per year
+    #[arg(long)]
+    pub base_rate: Option<u64>,
+    /// Basis points per year
+    #[arg(long)]
+    pub interest_rate: Option<u64>,
+    /// Pyth feed id in hex
+    #[arg(long, value_parser = parse_feed_id)]
+    pub feed_id: Option<FeedId>,
+    /// Seconds
+    #[arg(long)]
+    pub max_price_age: Option<u64>,
+    #[arg(long)]
+    pub emode_category: Option<u8>,
+    #[arg(long)]
+    pub collateral_enabled: Option<bool>,
+    #[arg(long)]
+    pub isolated: Option<bool>,
+    /// USD
+    #[arg(long)]
+    pub isolation_debt_ceiling: Option<u64>,
+    #[arg(long)]
+    pub borrowable_in_isolation: Option<bool>,
+}
+
+fn parse_feed_id(value: &str) -> Result<FeedId, String> {
+    get_feed_id_from_hex(value).map_err(|err| format!("{err:?}"))
+}
+
+fn parse_price_update(value: &str) -> Result<(Pubkey, Pubkey), String> {
+    let (mint, account) = value.split_once('=').ok_or("expected <MINT>=<ACCOUNT>")?;
+    let mint = mint.parse().map_err(|err| format!("invalid mint: {err}"))?;
+    let account = account.parse().map_err(|err| format!("invalid account: {err}"))?;
+    Ok((mint, account))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::error::ErrorKind;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(["lending-cli"].iter().chain(args))
+    }
+
+    #[test]
+    fn global_options_after_the_command() {
+        let mint = Pubkey::new_unique();
+        let price_update = Pubkey::new_unique();
+        let args = parse(&[
+            "bank",
+            &mint.to_string(),
+            "--output",
+            "json",
+            "--price-update",
+            &format!("{mint}={price_update}"),
+        ])
+        .unwrap();
+        assert!(matches!(args.command, Command::Bank { mint: bank_mint } if bank_mint == mint));
+        assert_eq!(args.global.url, DEFAULT_URL);
+        assert_eq!(args.global.output, Output::Json);
+        assert_eq!(args.global.price_updates, vec![(mint, price_update)]);
+    }
+
+    #[test]
+    fn user_defaults_to_the_signer_and_first_sub_account() {
+        let args = parse(&["user"]).unwrap();
+        assert!(matches!(args.command, Command::User { owner: None, sub_account: 0 }));
+        assert_eq!(args.global.output, Output::Display);
+
+        let owner = Pubkey::new_unique();
+        let args = parse(&["user", "--owner", &owner.to_string(), "--sub-account", "2"]).unwrap();
+        assert!(matches!(args.command, Command::User { owner: Some(user_owner), sub_account: 2 } if user_owner == owner));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        let mint = Pubkey::new_unique().to_string();
+        assert_eq!(parse(&["bank"]).unwrap_err().kind(), ErrorKind::MissingRequiredArgument);
+        assert_eq!(parse(&["bank", "not-a-pubkey"]).unwrap_err().kind(), ErrorKind::ValueValidation);
+        assert_eq!(parse(&["user", "--output", "yaml"]).unwrap_err().kind(), ErrorKind::InvalidValue);
+        assert_eq!(parse(&["user", "--price-update", &mint]).unwrap_err().kind(), ErrorKind::ValueValidation);
+        assert_eq!(parse(&["deposit", &mint, "1", "--sub-acount", "1"]).unwrap_err().kind(), ErrorKind::UnknownArgument);
+        let init_bank = ["init-bank", &mint, "--max-ltv", "70", "--liquidation-threshold", "80", "--feed-id", "zz"];
+        assert_eq!(parse(&init_bank).unwrap_err().kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn queue_config_change_keeps_unspecified_params() {
+        let mint = Pubkey::new_unique().to_string();
+        let args = parse(&["queue-config-change", &mint, "--max-ltv", "60", "--collateral-enabled", "false"]).unwrap();
+        let Command::QueueConfigChange { params, .. } = args.command else { panic!("wrong command") };
+        assert_eq!((params.max_ltv, params.collateral_enabled), (Some(60), Some(false)));
+        assert!(params.liquidation_threshold.is_none() && params.feed_id.is_none());
+    }
+}
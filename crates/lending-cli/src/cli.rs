@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use anchor_lang::prelude::AccountMeta;
+use anyhow::{anyhow, Context, Result};
+use lending_client::lending::state::{Bank, EModeCategory, User};
+use lending_client::math::{self, PricedBank};
+use lending_client::rpc::{self, RpcClient, RpcClientExt};
+use lending_client::{accounts, instructions, pda};
+use serde_json::Value;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::args::{GlobalArgs, Output};
+
+pub const DEFAULT_URL: &str = "http://127.0.0.1:8899";
+
+/// Connection, wallet and output settings shared by all commands
+pub struct Cli {
+    pub rpc: RpcClient,
+    keypair_path: String,
+    output: Output,
+    /// Price update accounts to use for a mint instead of its push oracle feed
+    price_updates: HashMap<Pubkey, Pubkey>,
+}
+
+impl Cli {
+    pub fn new(args: GlobalArgs) -> Self {
+        let keypair_path = args
+            .keypair
+            .unwrap_or_else(|| format!("{}/.config/solana/id.json", std::env::var("HOME").unwrap_or_default()));
+        Self {
+            rpc: rpc::client(&args.url),
+            keypair_path,
+            output: args.output,
+            price_updates: args.price_updates.into_iter().collect(),
+        }
+    }
+
+    pub fn payer(&self) -> Result<Keypair> {
+        read_keypair_file(&self.keypair_path).map_err(|err| anyhow!("failed to read keypair {}: {err}", self.keypair_path))
+    }
+
+    /// Prints `json` with `--output json`, otherwise the display text
+    pub fn print(&self, display: String, json: Value) {
+        match self.output {
+            Output::Display => println!("{display}"),
+            Output::Json => println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default()),
+        }
+    }
+
+    pub fn send(&self, payer: &Keypair, instructions: &[Instruction]) -> Result<Signature> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &[payer], blockhash);
+        Ok(self.rpc.send_and_confirm_transaction(&transaction)?)
+    }
+
+    /// Token program owning `mint`
+    pub fn token_program(&self, mint: &Pubkey) -> Result<Pubkey> {
+        let account = self.rpc.get_optional_account(mint)?.ok_or_else(|| anyhow!("mint {mint} not found"))?;
+        Ok(account.owner)
+    }
+
+    pub fn bank(&self, mint: &Pubkey) -> Result<Bank> {
+        self.rpc.get_anchor_account(&pda::bank(mint)).with_context(|| format!("no bank for mint {mint}"))
+    }
+
+    pub fn user(&self, user_account: &Pubkey) -> Result<User> {
+        self.rpc.get_anchor_account(user_account)
+    }
+
+    pub fn price_update(&self, bank: &Bank) -> Pubkey {
+        self.price_updates
+            .get(&bank.mint_address)
+            .copied()
+            .unwrap_or_else(|| pda::price_feed(&bank.oracle_feed_id, 0))
+    }
+
+    /// Banks of the user's positions, keyed by bank address
+    pub fn position_banks(&self, user: &User) -> Result<HashMap<Pubkey, Bank>> {
+        user.active_positions()
+            .map(|position| Ok((position.bank, self.rpc.get_anchor_account(&position.bank)?)))
+            .collect()
+    }
+
+    /// Remaining accounts for instructions that check the user's health
+    pub fn health_accounts(&self, user: &User) -> Result<Vec<AccountMeta>> {
+        let banks = self.position_banks(user)?;
+        Ok(instructions::health_accounts(user, |bank| self.price_update(&banks[bank])))
+    }
+
+    pub fn emode(&self, user: &User) -> Result<Option<EModeCategory>> {
+        if user.emode_category == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.rpc.get_anchor_account(&pda::emode_category(user.emode_category))?))
+    }
+
+    /// The user's banks with interest accrued and priced as the program would at the cluster's current time
+    pub fn priced_banks(&self, user: &User) -> Result<Vec<PricedBank>> {
+        let now = self.rpc.get_clock()?.unix_timestamp;
+        let banks = self.position_banks(user)?;
+        user.active_positions()
+            .map(|position| {
+                let bank = &banks[&position.bank];
+                let price_update_key = self.price_update(bank);
+                let account = self
+                    .rpc
+                    .get_optional_account(&price_update_key)?
+                    .ok_or_else(|| anyhow!("price update {price_update_key} not found"))?;
+                let price_update = accounts::deserialize_price_update(&account.data)?;
+                let price = math::checked_price(bank, &price_update, now)
+                    .map_err(|err| anyhow!("price of bank {}: {err}", position.bank))?;
+                math::priced_bank(position.bank, bank, price, now)
+                    .map_err(|err| anyhow!("interest of bank {}: {err}", position.bank))
+            })
+            .collect()
+    }
+}
+
+/// `amount` in whole tokens
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+    let scale = 10u64.pow(decimals as u32);
+    format!("{}.{:0width$}", amount / scale, amount % scale, width = decimals as usize)
+}
+
+/// USD value scaled by 10^VALUE_DECIMALS
+pub fn format_value(value: u128) -> String {
+    let scale = 10u128.pow(lending_client::lending::constants::VALUE_DECIMALS as u32);
+    format!("${}.{:06}", value / scale, value % scale)
+}
+
+/// Basis points as a percentage
+pub fn format_bps(bps: u64) -> String {
+    format!("{}.{:02}%", bps / 100, bps % 100)
+}
@@ -0,0 +1,449 @@
+use anchor_lang::prelude::AccountMeta;
+use anyhow::{anyhow, bail, Result};
+use lending_client::lending::state::{Bank, ConfigChange, EModeCategory, RiskParams, User};
+use lending_client::math::{self, PricedBank, HEALTHY_HEALTH_FACTOR};
+use lending_client::rpc::RpcClientExt;
+use lending_client::{instructions, pda};
+use pyth_solana_receiver_sdk::price_update::FeedId;
+use serde_json::{json, Value};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+use crate::args::{Command, RiskParamArgs};
+use crate::cli::{format_amount, format_bps, format_value, Cli};
+
+pub fn run(cli: &Cli, command: Command) -> Result<()> {
+    match command {
+        Command::InitBank { mint, liquidation_threshold, max_ltv, feed_id } => {
+            init_bank(cli, &mint, liquidation_threshold, max_ltv, feed_id)
+        }
+        Command::UpdateBank {
+            mint,
+            flash_loan_fee,
+            deposit_limit,
+            borrow_limit,
+            borrow_enabled,
+            pause_flags,
+        } => {
+            let bank = cli.bank(&mint)?;
+            let signer = cli.payer()?.pubkey();
+            let mut ixs = Vec::new();
+            if let Some(flash_loan_fee) = flash_loan_fee {
+                ixs.push(instructions::set_flash_loan_fee(&signer, &mint, flash_loan_fee));
+            }
+            if deposit_limit.is_some() || borrow_limit.is_some() {
+                ixs.push(instructions::set_bank_limits(
+                    &signer,
+                    &mint,
+                    deposit_limit.unwrap_or(bank.deposit_limit),
+                    borrow_limit.unwrap_or(bank.borrow_limit),
+                ));
+            }
+            if let Some(borrow_enabled) = borrow_enabled {
+                ixs.push(instructions::set_borrow_enabled(&signer, &mint, borrow_enabled));
+            }
+            if let Some(pause_flags) = pause_flags {
+                ixs.push(instructions::set_bank_pause(&signer, &mint, pause_flags));
+            }
+            if ixs.is_empty() {
+                bail!("nothing to update");
+            }
+            send(cli, &ixs)
+        }
+        Command::QueueConfigChange { mint, params } => queue_config_change(cli, &mint, params),
+        Command::ExecuteConfigChange { mint } => execute_config_change(cli, &mint),
+        Command::Bank { mint } => show_bank(cli, &mint),
+        Command::InitUser { sub_account } => {
+            let signer = cli.payer()?.pubkey();
+            send(cli, &[instructions::init_user(&signer, sub_account)])
+        }
+        Command::User { owner, sub_account } => {
+            let owner = match owner {
+                Some(owner) => owner,
+                None => cli.payer()?.pubkey(),
+            };
+            show_user(cli, &owner, sub_account)
+        }
+        Command::Deposit { mint, amount } => user_action(cli, UserAction::Deposit, &mint, amount, 0),
+        Command::Withdraw { mint, amount } => user_action(cli, UserAction::Withdraw, &mint, amount, 0),
+        Command::DepositCollateral { mint, shares, sub_account } => {
+            user_action(cli, UserAction::DepositCollateral, &mint, shares, sub_account)
+        }
+        Command::WithdrawCollateral { mint, shares, sub_account } => {
+            user_action(cli, UserAction::WithdrawCollateral, &mint, shares, sub_account)
+        }
+        Command::Borrow { mint, amount, sub_account } => user_action(cli, UserAction::Borrow, &mint, amount, sub_account),
+        Command::Repay { mint, amount, sub_account } => user_action(cli, UserAction::Repay, &mint, amount, sub_account),
+        Command::Liquidate { user_account, collateral_mint, borrowed_mint } => {
+            liquidate(cli, &user_account, &collateral_mint, &borrowed_mint)
+        }
+    }
+}
+
+fn send(cli: &Cli, instructions: &[Instruction]) -> Result<()> {
+    let payer = cli.payer()?;
+    let signature = cli.send(&payer, instructions)?;
+    cli.print(format!("Signature: {signature}"), json!({ "signature": signature.to_string() }));
+    Ok(())
+}
+
+fn init_bank(cli: &Cli, mint: &Pubkey, liquidation_threshold: u64, max_ltv: u64, feed_id: FeedId) -> Result<()> {
+    let payer = cli.payer()?;
+    let token_program = cli.token_program(mint)?;
+    send(cli, &[instructions::init_bank(&payer.pubkey(), mint, &token_program, liquidation_threshold, max_ltv, feed_id)])
+}
+
+/// Risk parameters go through the timelock, unspecified ones keep their current value
+fn queue_config_change(cli: &Cli, mint: &Pubkey, args: RiskParamArgs) -> Result<()> {
+    let bank = cli.bank(mint)?;
+    let params = RiskParams {
+        liquidation_threshold: args.liquidation_threshold.unwrap_or(bank.liquidation_threshold),
+        liquidation_bonus: args.liquidation_bonus.unwrap_or(bank.liquidation_bonus),
+        liquidation_close_factor: args.liquidation_close_factor.unwrap_or(bank.liquidation_close_factor),
+        max_ltv: args.max_ltv.unwrap_or(bank.max_ltv),
+        interest_rate: args.interest_rate.unwrap_or(bank.interest_rate),
+        base_rate: args.base_rate.unwrap_or(bank.base_rate),
+        oracle_feed_id: args.feed_id.unwrap_or(bank.oracle_feed_id),
+        max_price_age: args.max_price_age.unwrap_or(bank.max_price_age),
+        emode_category: args.emode_category.unwrap_or(bank.emode_category),
+        collateral_enabled: args.collateral_enabled.unwrap_or(bank.collateral_enabled),
+        isolated: args.isolated.unwrap_or(bank.isolated),
+        isolation_debt_ceiling: args.isolation_debt_ceiling.unwrap_or(bank.isolation_debt_ceiling),
+        borrowable_in_isolation: args.borrowable_in_isolation.unwrap_or(bank.borrowable_in_isolation),
+    };
+
+    let signer = cli.payer()?.pubkey();
+    send(cli, &[instructions::queue_config_change(&signer, mint, params)])
+}
+
+fn execute_config_change(cli: &Cli, mint: &Pubkey) -> Result<()> {
+    let config_change = cli
+        .rpc
+        .get_anchor_account::<ConfigChange>(&pda::config_change(&pda::bank(mint)))?;
+    send(cli, &[instructions::execute_config_change(mint, &config_change.proposer)])
+}
+
+fn show_bank(cli: &Cli, mint: &Pubkey) -> Result<()> {
+    let now = cli.rpc.get_clock()?.unix_timestamp;
+    let bank = math::accrued_bank(&cli.bank(mint)?, now).map_err(|err| anyhow!("interest of bank {mint}: {err}"))?;
+    let (display, json) = bank_output(mint, &bank)?;
+    cli.print(display, json);
+    Ok(())
+}
+
+/// Display text and JSON of `bank`, accrued to the time it is shown at
+fn bank_output(mint: &Pubkey, bank: &Bank) -> Result<(String, Value)> {
+    let decimals = bank.mint_decimals;
+    let borrow_rate = math::borrow_rate(bank).map_err(|err| anyhow!("borrow rate of bank {mint}: {err}"))?;
+    let supply_rate = math::supply_rate(bank).map_err(|err| anyhow!("supply rate of bank {mint}: {err}"))?;
+
+    Ok((
+        [
+            format!("Bank: {}", pda::bank(mint)),
+            format!("Mint: {mint}"),
+            format!("Authority: {}", bank.authority),
+            format!("Listed: {}", bank.listed),
+            format!("Total deposits: {} ({} shares)", format_amount(bank.total_deposits, decimals), bank.total_deposit_shares),
+            format!("Total borrowed: {} ({} shares)", format_amount(bank.total_borrowed, decimals), bank.total_borrowed_shares),
+            format!("Utilization: {}", format_bps(bank.utilization)),
+            format!("Borrow rate: {} APR", format_bps(borrow_rate)),
+            format!("Supply rate: {} APR", format_bps(supply_rate)),
+            format!("Rate curve: {} to {}", format_bps(bank.base_rate), format_bps(bank.interest_rate)),
+            format!("Max LTV: {}%", bank.max_ltv),
+            format!("Liquidation threshold: {}%", bank.liquidation_threshold),
+            format!("Liquidation bonus: {}%", bank.liquidation_bonus),
+            format!("Liquidation close factor: {}%", bank.liquidation_close_factor),
+            format!("Deposit limit: {}", format_amount(bank.deposit_limit, decimals)),
+            format!("Borrow limit: {}", format_amount(bank.borrow_limit, decimals)),
+            format!("Collateral enabled: {}", bank.collateral_enabled),
+            format!("Borrow enabled: {}", bank.borrow_enabled),
+            format!("Isolated: {} (debt {} of {})", bank.isolated, bank.isolation_debt, bank.isolation_debt_ceiling),
+            format!("Borrowable in isolation: {}", bank.borrowable_in_isolation),
+            format!("E-mode category: {}", bank.emode_category),
+            format!("Pause flags: {:#07b}", bank.pause_flags),
+            format!("Last updated: {}", bank.last_updated),
+        ]
+        .join("\n"),
+        json!({
+            "bank": pda::bank(mint).to_string(),
+            "mint": mint.to_string(),
+            "authority": bank.authority.to_string(),
+            "listed": bank.listed,
+            "mint_decimals": decimals,
+            "total_deposits": bank.total_deposits,
+            "total_deposit_shares": bank.total_deposit_shares,
+            "total_borrowed": bank.total_borrowed,
+            "total_borrowed_shares": bank.total_borrowed_shares,
+            "utilization_bps": bank.utilization,
+            "borrow_rate_bps": borrow_rate,
+            "supply_rate_bps": supply_rate,
+            "base_rate_bps": bank.base_rate,
+            "interest_rate_bps": bank.interest_rate,
+            "borrow_index": bank.borrow_index.to_string(),
+            "deposit_index": bank.deposit_index.to_string(),
+            "max_ltv": bank.max_ltv,
+            "liquidation_threshold": bank.liquidation_threshold,
+            "liquidation_bonus": bank.liquidation_bonus,
+            "liquidation_close_factor": bank.liquidation_close_factor,
+            "deposit_limit": bank.deposit_limit,
+            "borrow_limit": bank.borrow_limit,
+            "collateral_enabled": bank.collateral_enabled,
+            "borrow_enabled": bank.borrow_enabled,
+            "isolated": bank.isolated,
+            "isolation_debt": bank.isolation_debt,
+            "isolation_debt_ceiling": bank.isolation_debt_ceiling,
+            "borrowable_in_isolation": bank.borrowable_in_isolation,
+            "emode_category": bank.emode_category,
+            "pause_flags": bank.pause_flags,
+            "last_updated": bank.last_updated,
+        }),
+    ))
+}
+
+fn show_user(cli: &Cli, owner: &Pubkey, sub_account: u8) -> Result<()> {
+    let user_account = pda::user(owner, sub_account);
+    let user = cli.user(&user_account)?;
+    let emode = cli.emode(&user)?;
+    let priced_banks = cli.priced_banks(&user)?;
+    let (display, json) = user_output(&user_account, &user, &priced_banks, emode.as_ref());
+    cli.print(display, json);
+    Ok(())
+}
+
+/// Display text and JSON of a user's positions and health, `priced_banks` holding the bank of each position
+fn user_output(user_account: &Pubkey, user: &User, priced_banks: &[PricedBank], emode: Option<&EModeCategory>) -> (String, Value) {
+    let values = math::calculate_user_values(user, priced_banks, emode);
+    let health_factor = values.health_factor();
+    let format_health = |health_factor: u64| match health_factor {
+        u64::MAX => "none, no borrows".to_string(),
+        health_factor => format!("{health_factor}%"),
+    };
+
+    let positions: Vec<_> = user
+        .active_positions()
+        .zip(priced_banks)
+        .map(|(position, priced_bank)| {
+            let bank = &priced_bank.bank;
+            (
+                bank.mint_address,
+                bank.mint_decimals,
+                position.deposit_shares,
+                bank.deposit_amount_for(position.deposit_shares),
+                position.borrow_shares,
+                bank.borrow_amount_for(position.borrow_shares),
+                position.is_collateral(bank),
+            )
+        })
+        .collect();
+
+    (
+        {
+            let mut lines = vec![
+                format!("User: {user_account}"),
+                format!("Owner: {} (sub-account {})", user.owner, user.sub_account),
+                format!("E-mode category: {}", user.emode_category),
+                format!("Health factor: {} (liquidatable below {HEALTHY_HEALTH_FACTOR}%)", format_health(health_factor)),
+                format!("Stored health factor: {} at {}", format_health(user.health_factor), user.last_updated),
+                format!("Collateral value: {}", format_value(values.collateral_value)),
+                format!("Borrowable value: {}", format_value(values.borrowable_value)),
+                format!("Liquidation value: {}", format_value(values.liquidation_value)),
+                format!("Borrowed value: {}", format_value(values.borrowed_value)),
+            ];
+            if user.is_isolated() {
+                lines.push(format!("Isolated on bank: {}", user.isolated_bank));
+            }
+            for (mint, decimals, _, deposited, _, borrowed, is_collateral) in &positions {
+                lines.push(format!(
+                    "Position {mint}: deposited {}{}, borrowed {}",
+                    format_amount(*deposited, *decimals),
+                    if *is_collateral { "" } else { " (not collateral)" },
+                    format_amount(*borrowed, *decimals),
+                ));
+            }
+            lines.join("\n")
+        },
+        json!({
+            "user": user_account.to_string(),
+            "owner": user.owner.to_string(),
+            "sub_account": user.sub_account,
+            "emode_category": user.emode_category,
+            "isolated_bank": user.is_isolated().then(|| user.isolated_bank.to_string()),
+            "health_factor": health_factor,
+            "stored_health_factor": user.health_factor,
+            "last_updated": user.last_updated,
+            "collateral_value": values.collateral_value.to_string(),
+            "borrowable_value": values.borrowable_value.to_string(),
+            "liquidation_value": values.liquidation_value.to_string(),
+            "borrowed_value": values.borrowed_value.to_string(),
+            "positions": positions.iter().map(|(mint, _, deposit_shares, deposited, borrow_shares, borrowed, is_collateral)| json!({
+                "mint": mint.to_string(),
+                "deposit_shares": deposit_shares,
+                "deposited": deposited,
+                "borrow_shares": borrow_shares,
+                "borrowed": borrowed,
+                "is_collateral": is_collateral,
+            })).collect::<Vec<_>>(),
+        }),
+    )
+}
+
+/// Remaining accounts for a health-checked action on `bank`, including the position the action may open
+fn health_accounts_with(cli: &Cli, user: &User, bank: &Pubkey) -> Result<Vec<AccountMeta>> {
+    let mut user = user.clone();
+    user.get_or_create_position(bank).map_err(|err| anyhow!("{err}"))?;
+    cli.health_accounts(&user)
+}
+
+enum UserAction {
+    Deposit,
+    Withdraw,
+    DepositCollateral,
+    WithdrawCollateral,
+    Borrow,
+    Repay,
+}
+
+fn user_action(cli: &Cli, action: UserAction, mint: &Pubkey, amount: u64, sub_account: u8) -> Result<()> {
+    let signer = cli.payer()?.pubkey();
+    let token_program = cli.token_program(mint)?;
+    let price_update = cli.price_update(&cli.bank(mint)?);
+    let ix = match action {
+        UserAction::Deposit => instructions::deposit(&signer, mint, &price_update, &token_program, amount),
+        UserAction::Withdraw => instructions::withdraw(&signer, mint, &price_update, &token_program, amount),
+        UserAction::DepositCollateral => instructions::deposit_collateral(&signer, mint, &token_program, sub_account, amount),
+        UserAction::WithdrawCollateral | UserAction::Borrow | UserAction::Repay => {
+            let user = cli.user(&pda::user(&signer, sub_account))?;
+            let isolated_bank = user.is_isolated().then_some(user.isolated_bank);
+            match action {
+                UserAction::WithdrawCollateral => {
+                    let remaining_accounts = cli.health_accounts(&user)?;
+                    instructions::withdraw_collateral(
+                        &signer,
+                        mint,
+                        &price_update,
+                        &token_program,
+                        sub_account,
+                        amount,
+                        &remaining_accounts,
+                    )
+                }
+                UserAction::Borrow => {
+                    let remaining_accounts = health_accounts_with(cli, &user, &pda::bank(mint))?;
+                    instructions::borrow(
+                        &signer,
+                        mint,
+                        &price_update,
+                        &token_program,
+                        sub_account,
+                        amount,
+                        isolated_bank,
+                        &remaining_accounts,
+                    )
+                }
+                _ => {
+                    let remaining_accounts = cli.health_accounts(&user)?;
+                    instructions::repay(
+                        &signer,
+                        mint,
+                        &price_update,
+                        &token_program,
+                        sub_account,
+                        amount,
+                        isolated_bank,
+                        &remaining_accounts,
+                    )
+                }
+            }
+        }
+    };
+    send(cli, &[ix])
+}
+
+fn liquidate(cli: &Cli, user_account: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey) -> Result<()> {
+    let liquidator = cli.payer()?.pubkey();
+    let user = cli.user(user_account)?;
+    let token_program = cli.token_program(borrowed_mint)?;
+    let remaining_accounts = cli.health_accounts(&user)?;
+    send(
+        cli,
+        &[instructions::liquidate(&liquidator, user_account, collateral_mint, borrowed_mint, &token_program, &remaining_accounts)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{AnchorDeserialize, Space};
+    use lending_client::lending::constants::INDEX_SCALE;
+    use pyth_solana_receiver_sdk::price_update::Price;
+
+    use super::*;
+
+    fn bank(mint: Pubkey, mint_decimals: u8, total_deposits: u64, total_borrowed: u64) -> Bank {
+        let mut bank = Bank::deserialize(&mut vec![0; Bank::INIT_SPACE].as_slice()).unwrap();
+        bank.mint_address = mint;
+        bank.mint_decimals = mint_decimals;
+        (bank.total_deposits, bank.total_deposit_shares) = (total_deposits, total_deposits);
+        (bank.total_borrowed, bank.total_borrowed_shares) = (total_borrowed, total_borrowed);
+        bank.utilization = bank.utilization_rate();
+        (bank.base_rate, bank.interest_rate) = (200, 1_000);
+        (bank.max_ltv, bank.liquidation_threshold, bank.liquidation_bonus, bank.liquidation_close_factor) = (70, 80, 5, 50);
+        (bank.borrow_index, bank.deposit_index) = (INDEX_SCALE, INDEX_SCALE);
+        (bank.collateral_enabled, bank.borrow_enabled, bank.listed) = (true, true, true);
+        bank
+    }
+
+    fn usd(dollars: i64) -> Price {
+        Price { price: dollars * 100_000_000, conf: 0, exponent: -8, publish_time: 0 }
+    }
+
+    #[test]
+    fn bank_output_shows_totals_utilization_and_rates() {
+        let mint = Pubkey::new_unique();
+        let (display, json) = bank_output(&mint, &bank(mint, 6, 1_000_000_000, 500_000_000)).unwrap();
+
+        assert!(display.contains(&format!("Bank: {}", pda::bank(&mint))));
+        assert!(display.contains("Total deposits: 1000.000000 (1000000000 shares)"));
+        assert!(display.contains("Total borrowed: 500.000000 (500000000 shares)"));
+        assert!(display.contains("Utilization: 50.00%"));
+        // Halfway between the 2% base rate and the 10% rate at full utilization
+        assert!(display.contains("Borrow rate: 6.00% APR"));
+        assert!(display.contains("Supply rate: 3.00% APR"));
+        assert_eq!(json["mint"], mint.to_string());
+        assert_eq!(json["total_deposits"], 1_000_000_000);
+        assert_eq!((json["utilization_bps"].as_u64(), json["borrow_rate_bps"].as_u64()), (Some(5_000), Some(600)));
+        assert_eq!(json["borrow_index"], INDEX_SCALE.to_string());
+    }
+
+    #[test]
+    fn user_output_shows_positions_and_health() {
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (sol_bank, usdc_bank) = (pda::bank(&sol), pda::bank(&usdc));
+        let owner = Pubkey::new_unique();
+        let user_account = pda::user(&owner, 0);
+        let mut user = User::deserialize(&mut vec![0; User::INIT_SPACE].as_slice()).unwrap();
+        user.owner = owner;
+        user.get_or_create_position(&sol_bank).unwrap().deposit_shares = 1_000_000_000;
+        user.get_or_create_position(&usdc_bank).unwrap().borrow_shares = 50_000_000;
+        let priced_banks = [
+            PricedBank { key: sol_bank, bank: bank(sol, 9, 10_000_000_000, 0), price: usd(100) },
+            PricedBank { key: usdc_bank, bank: bank(usdc, 6, 1_000_000_000, 50_000_000), price: usd(1) },
+        ];
+
+        let (display, json) = user_output(&user_account, &user, &priced_banks, None);
+        // $80 liquidation value against $50 of debt
+        assert!(display.contains("Health factor: 160% (liquidatable below 100%)"));
+        assert!(display.contains("Collateral value: $100.000000"));
+        assert!(display.contains(&format!("Position {sol}: deposited 1.000000000, borrowed 0.000000000")));
+        assert!(display.contains(&format!("Position {usdc}: deposited 0.000000, borrowed 50.000000")));
+        assert_eq!(json["user"], user_account.to_string());
+        assert_eq!(json["owner"], owner.to_string());
+        assert_eq!(json["health_factor"], 160);
+        assert_eq!(json["borrowed_value"], "50000000");
+        assert!(json["isolated_bank"].is_null());
+        let positions = json["positions"].as_array().unwrap();
+        assert_eq!(positions.len(), 2);
+        assert_eq!((positions[0]["deposited"].as_u64(), positions[0]["is_collateral"].as_bool()), (Some(1_000_000_000), Some(true)));
+        assert_eq!(positions[1]["borrowed"], 50_000_000);
+    }
+}
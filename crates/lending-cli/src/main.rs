@@ -0,0 +1,29 @@
+//! Operator command-line tool for the lending program
+
+mod args;
+mod cli;
+mod commands;
+
+use std::process::ExitCode;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::args::Args;
+use crate::cli::Cli;
+
+fn run() -> Result<()> {
+    let args = Args::parse();
+    let cli = Cli::new(args.global);
+    commands::run(&cli, args.command)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
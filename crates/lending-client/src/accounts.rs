@@ -0,0 +1,36 @@
+use anchor_lang::{AccountDeserialize, Result};
+use lending::state::{Bank, ConfigChange, EModeCategory, EModeCategoryChange, ProtocolConfig, User};
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+
+/// Deserializes account data, checking the account discriminator
+pub fn deserialize<T: AccountDeserialize>(mut data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut data)
+}
+
+pub fn deserialize_bank(data: &[u8]) -> Result<Bank> {
+    deserialize(data)
+}
+
+pub fn deserialize_user(data: &[u8]) -> Result<User> {
+    deserialize(data)
+}
+
+pub fn deserialize_protocol_config(data: &[u8]) -> Result<ProtocolConfig> {
+    deserialize(data)
+}
+
+pub fn deserialize_emode_category(data: &[u8]) -> Result<EModeCategory> {
+    deserialize(data)
+}
+
+pub fn deserialize_config_change(data: &[u8]) -> Result<ConfigChange> {
+    deserialize(data)
+}
+
+pub fn deserialize_emode_category_change(data: &[u8]) -> Result<EModeCategoryChange> {
+    deserialize(data)
+}
+
+pub fn deserialize_price_update(data: &[u8]) -> Result<PriceUpdateV2> {
+    deserialize(data)
+}
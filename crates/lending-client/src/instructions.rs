@@ -0,0 +1,738 @@
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token;
+use lending::state::{RiskParams, User};
+use lending::{accounts, instruction};
+
+use crate::{pda, PROGRAM_ID};
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData, remaining_accounts: &[AccountMeta]) -> Instruction {
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend_from_slice(remaining_accounts);
+    Instruction::new_with_bytes(PROGRAM_ID, &data.data(), metas)
+}
+
+/// Remaining accounts for instructions that price a user's positions: the user's e-mode category
+/// if one is selected, followed by a `(bank, price update)` pair for each position in position order.
+/// `price_update` returns the price update account to use for a bank.
+pub fn health_accounts(user: &User, price_update: impl Fn(&Pubkey) -> Pubkey) -> Vec<AccountMeta> {
+    let mut metas = Vec::new();
+    if user.emode_category != 0 {
+        metas.push(AccountMeta::new_readonly(pda::emode_category(user.emode_category), false));
+    }
+    for position in user.active_positions() {
+        metas.push(AccountMeta::new_readonly(position.bank, false));
+        metas.push(AccountMeta::new_readonly(price_update(&position.bank), false));
+    }
+    metas
+}
+
+// Protocol config
+
+pub fn init_protocol_config(signer: &Pubkey, guardian: Pubkey) -> Instruction {
+    build(
+        accounts::InitProtocolConfig {
+            signer: *signer,
+            config: pda::config(),
+            program: PROGRAM_ID,
+            program_data: pda::program_data(),
+            system_program: system_program::ID,
+        },
+        instruction::InitProtocolConfig { guardian },
+        &[],
+    )
+}
+
+fn update_protocol_config(signer: &Pubkey) -> accounts::UpdateProtocolConfig {
+    accounts::UpdateProtocolConfig { signer: *signer, config: pda::config() }
+}
+
+pub fn set_guardian(signer: &Pubkey, guardian: Pubkey) -> Instruction {
+    build(update_protocol_config(signer), instruction::SetGuardian { guardian }, &[])
+}
+
+pub fn propose_admin(signer: &Pubkey, admin: Pubkey) -> Instruction {
+    build(update_protocol_config(signer), instruction::ProposeAdmin { admin }, &[])
+}
+
+pub fn accept_admin(signer: &Pubkey) -> Instruction {
+    build(update_protocol_config(signer), instruction::AcceptAdmin {}, &[])
+}
+
+pub fn set_protocol_pause(signer: &Pubkey, pause_flags: u8) -> Instruction {
+    build(update_protocol_config(signer), instruction::SetProtocolPause { pause_flags }, &[])
+}
+
+// Banks
+
+pub fn init_bank(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    liquidation_threshold: u64,
+    max_ltv: u64,
+    oracle_feed_id: [u8; 32],
+) -> Instruction {
+    build(
+        accounts::InitBank {
+            signer: *signer,
+            config: pda::config(),
+            mint: *mint,
+            bank: pda::bank(mint),
+            bank_token_account: pda::treasury(mint),
+            receipt_mint: pda::receipt_mint(mint),
+            token_program: *token_program,
+            system_program: system_program::ID,
+        },
+        instruction::InitBank { liquidation_threshold, max_ltv, oracle_feed_id },
+        &[],
+    )
+}
+
+fn update_bank(signer: &Pubkey, mint: &Pubkey) -> accounts::UpdateBank {
+    accounts::UpdateBank { signer: *signer, mint: *mint, bank: pda::bank(mint) }
+}
+
+fn admin_update_bank(signer: &Pubkey, mint: &Pubkey) -> accounts::AdminUpdateBank {
+    accounts::AdminUpdateBank { signer: *signer, config: pda::config(), mint: *mint, bank: pda::bank(mint) }
+}
+
+pub fn list_bank(signer: &Pubkey, mint: &Pubkey) -> Instruction {
+    build(admin_update_bank(signer, mint), instruction::ListBank {}, &[])
+}
+
+pub fn delist_bank(signer: &Pubkey, mint: &Pubkey) -> Instruction {
+    build(admin_update_bank(signer, mint), instruction::DelistBank {}, &[])
+}
+
+pub fn set_bank_authority(signer: &Pubkey, mint: &Pubkey, authority: Pubkey) -> Instruction {
+    build(admin_update_bank(signer, mint), instruction::SetBankAuthority { authority }, &[])
+}
+
+pub fn propose_bank_authority(signer: &Pubkey, mint: &Pubkey, authority: Pubkey) -> Instruction {
+    build(update_bank(signer, mint), instruction::ProposeBankAuthority { authority }, &[])
+}
+
+pub fn accept_bank_authority(signer: &Pubkey, mint: &Pubkey) -> Instruction {
+    build(
+        accounts::AcceptBankAuthority { signer: *signer, mint: *mint, bank: pda::bank(mint) },
+        instruction::AcceptBankAuthority {},
+        &[],
+    )
+}
+
+pub fn set_flash_loan_fee(signer: &Pubkey, mint: &Pubkey, flash_loan_fee: u64) -> Instruction {
+    build(update_bank(signer, mint), instruction::SetFlashLoanFee { flash_loan_fee }, &[])
+}
+
+pub fn set_bank_limits(signer: &Pubkey, mint: &Pubkey, deposit_limit: u64, borrow_limit: u64) -> Instruction {
+    build(update_bank(signer, mint), instruction::SetBankLimits { deposit_limit, borrow_limit }, &[])
+}
+
+pub fn set_borrow_enabled(signer: &Pubkey, mint: &Pubkey, borrow_enabled: bool) -> Instruction {
+    build(update_bank(signer, mint), instruction::SetBorrowEnabled { borrow_enabled }, &[])
+}
+
+pub fn set_bank_pause(signer: &Pubkey, mint: &Pubkey, pause_flags: u8) -> Instruction {
+    build(
+        accounts::SetBankPause { signer: *signer, config: pda::config(), mint: *mint, bank: pda::bank(mint) },
+        instruction::SetBankPause { pause_flags },
+        &[],
+    )
+}
+
+pub fn accrue_interest(mint: &Pubkey) -> Instruction {
+    build(
+        accounts::AccrueInterest { mint: *mint, bank: pda::bank(mint) },
+        instruction::AccrueInterest {},
+        &[],
+    )
+}
+
+// Timelocked config changes
+
+pub fn queue_config_change(signer: &Pubkey, mint: &Pubkey, params: RiskParams) -> Instruction {
+    let bank = pda::bank(mint);
+    build(
+        accounts::QueueConfigChange {
+            signer: *signer,
+            mint: *mint,
+            bank,
+            config_change: pda::config_change(&bank),
+            system_program: system_program::ID,
+        },
+        instruction::QueueConfigChange { params },
+        &[],
+    )
+}
+
+/// `proposer` is the signer of the `queue_config_change` and receives the rent of the change account
+pub fn execute_config_change(mint: &Pubkey, proposer: &Pubkey) -> Instruction {
+    let bank = pda::bank(mint);
+    build(
+        accounts::ExecuteConfigChange {
+            mint: *mint,
+            bank,
+            config_change: pda::config_change(&bank),
+            proposer: *proposer,
+        },
+        instruction::ExecuteConfigChange {},
+        &[],
+    )
+}
+
+pub fn cancel_config_change(signer: &Pubkey, mint: &Pubkey, proposer: &Pubkey) -> Instruction {
+    let bank = pda::bank(mint);
+    build(
+        accounts::CancelConfigChange {
+            signer: *signer,
+            mint: *mint,
+            bank,
+            config_change: pda::config_change(&bank),
+            proposer: *proposer,
+        },
+        instruction::CancelConfigChange {},
+        &[],
+    )
+}
+
+// E-mode
+
+pub fn init_emode_category(
+    signer: &Pubkey,
+    id: u8,
+    max_ltv: u64,
+    liquidation_threshold: u64,
+    liquidation_bonus: u64,
+) -> Instruction {
+    build(
+        accounts::InitEModeCategory {
+            signer: *signer,
+            config: pda::config(),
+            emode_category: pda::emode_category(id),
+            system_program: system_program::ID,
+        },
+        instruction::InitEmodeCategory { id, max_ltv, liquidation_threshold, liquidation_bonus },
+        &[],
+    )
+}
+
+pub fn queue_emode_category_change(
+    signer: &Pubkey,
+    id: u8,
+    max_ltv: u64,
+    liquidation_threshold: u64,
+    liquidation_bonus: u64,
+) -> Instruction {
+    let emode_category = pda::emode_category(id);
+    build(
+        accounts::QueueEModeCategoryChange {
+            signer: *signer,
+            config: pda::config(),
+            emode_category,
+            emode_category_change: pda::config_change(&emode_category),
+            system_program: system_program::ID,
+        },
+        instruction::QueueEmodeCategoryChange { max_ltv, liquidation_threshold, liquidation_bonus },
+        &[],
+    )
+}
+
+/// `proposer` is the signer of the `queue_emode_category_change` and receives the rent of the change account
+pub fn execute_emode_category_change(id: u8, proposer: &Pubkey) -> Instruction {
+    let emode_category = pda::emode_category(id);
+    build(
+        accounts::ExecuteEModeCategoryChange {
+            emode_category,
+            emode_category_change: pda::config_change(&emode_category),
+            proposer: *proposer,
+        },
+        instruction::ExecuteEmodeCategoryChange {},
+        &[],
+    )
+}
+
+pub fn cancel_emode_category_change(signer: &Pubkey, id: u8, proposer: &Pubkey) -> Instruction {
+    let emode_category = pda::emode_category(id);
+    build(
+        accounts::CancelEModeCategoryChange {
+            signer: *signer,
+            config: pda::config(),
+            emode_category,
+            emode_category_change: pda::config_change(&emode_category),
+            proposer: *proposer,
+        },
+        instruction::CancelEmodeCategoryChange {},
+        &[],
+    )
+}
+
+/// Remaining accounts are the e-mode category being selected (unless it is 0), followed by
+/// the `(bank, price update)` pairs of the user's positions
+pub fn set_user_emode(signer: &Pubkey, sub_account: u8, emode_category: u8, remaining_accounts: &[AccountMeta]) -> Instruction {
+    build(
+        accounts::SetUserEMode { signer: *signer, user_account: pda::user(signer, sub_account) },
+        instruction::SetUserEmode { sub_account, emode_category },
+        remaining_accounts,
+    )
+}
+
+// Users
+
+pub fn init_user(signer: &Pubkey, sub_account: u8) -> Instruction {
+    build(
+        accounts::InitUser {
+            signer: *signer,
+            user_account: pda::user(signer, sub_account),
+            system_program: system_program::ID,
+        },
+        instruction::InitUser { sub_account },
+        &[],
+    )
+}
+
+fn deposit_accounts(signer: &Pubkey, mint: &Pubkey, price_update: &Pubkey, token_program: &Pubkey) -> accounts::Deposit {
+    let receipt_mint = pda::receipt_mint(mint);
+    accounts::Deposit {
+        signer: *signer,
+        config: pda::config(),
+        mint: *mint,
+        bank: pda::bank(mint),
+        price_update: *price_update,
+        bank_token_account: pda::treasury(mint),
+        receipt_mint,
+        user_token_account: pda::associated_token_account(signer, mint, token_program),
+        user_receipt_token_account: pda::associated_token_account(signer, &receipt_mint, token_program),
+        token_program: *token_program,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    }
+}
+
+fn withdraw_accounts(signer: &Pubkey, mint: &Pubkey, price_update: &Pubkey, token_program: &Pubkey) -> accounts::Withdraw {
+    let receipt_mint = pda::receipt_mint(mint);
+    accounts::Withdraw {
+        signer: *signer,
+        config: pda::config(),
+        mint: *mint,
+        bank: pda::bank(mint),
+        price_update: *price_update,
+        bank_token_account: pda::treasury(mint),
+        receipt_mint,
+        user_token_account: pda::associated_token_account(signer, mint, token_program),
+        user_receipt_token_account: pda::associated_token_account(signer, &receipt_mint, token_program),
+        token_program: *token_program,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    }
+}
+
+/// `price_update` is the bank's price update account, its price is reported in the event
+pub fn deposit(signer: &Pubkey, mint: &Pubkey, price_update: &Pubkey, token_program: &Pubkey, amount: u64) -> Instruction {
+    build(deposit_accounts(signer, mint, price_update, token_program), instruction::Deposit { amount }, &[])
+}
+
+pub fn withdraw(signer: &Pubkey, mint: &Pubkey, price_update: &Pubkey, token_program: &Pubkey, amount: u64) -> Instruction {
+    build(withdraw_accounts(signer, mint, price_update, token_program), instruction::Withdraw { amount }, &[])
+}
+
+/// `mint` must be the native mint
+pub fn deposit_sol(signer: &Pubkey, mint: &Pubkey, price_update: &Pubkey, token_program: &Pubkey, amount: u64) -> Instruction {
+    let receipt_mint = pda::receipt_mint(mint);
+    build(
+        accounts::DepositSol {
+            signer: *signer,
+            config: pda::config(),
+            mint: *mint,
+            bank: pda::bank(mint),
+            price_update: *price_update,
+            bank_token_account: pda::treasury(mint),
+            receipt_mint,
+            wsol_account: pda::wsol_account(signer),
+            user_receipt_token_account: pda::associated_token_account(signer, &receipt_mint, token_program),
+            token_program: *token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::DepositSol { amount },
+        &[],
+    )
+}
+
+/// `mint` must be the native mint
+pub fn withdraw_sol(signer: &Pubkey, mint: &Pubkey, price_update: &Pubkey, token_program: &Pubkey, amount: u64) -> Instruction {
+    let receipt_mint = pda::receipt_mint(mint);
+    build(
+        accounts::WithdrawSol {
+            signer: *signer,
+            config: pda::config(),
+            mint: *mint,
+            bank: pda::bank(mint),
+            price_update: *price_update,
+            bank_token_account: pda::treasury(mint),
+            receipt_mint,
+            wsol_account: pda::wsol_account(signer),
+            user_receipt_token_account: pda::associated_token_account(signer, &receipt_mint, token_program),
+            token_program: *token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::WithdrawSol { amount },
+        &[],
+    )
+}
+
+pub fn deposit_collateral(signer: &Pubkey, mint: &Pubkey, token_program: &Pubkey, sub_account: u8, shares: u64) -> Instruction {
+    let receipt_mint = pda::receipt_mint(mint);
+    build(
+        accounts::DepositCollateral {
+            signer: *signer,
+            mint: *mint,
+            bank: pda::bank(mint),
+            receipt_mint,
+            user_account: pda::user(signer, sub_account),
+            user_receipt_token_account: pda::associated_token_account(signer, &receipt_mint, token_program),
+            token_program: *token_program,
+        },
+        instruction::DepositCollateral { sub_account, shares },
+        &[],
+    )
+}
+
+/// Remaining accounts are the user's [`health_accounts`]
+pub fn withdraw_collateral(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+    token_program: &Pubkey,
+    sub_account: u8,
+    shares: u64,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    let receipt_mint = pda::receipt_mint(mint);
+    build(
+        accounts::WithdrawCollateral {
+            signer: *signer,
+            config: pda::config(),
+            mint: *mint,
+            bank: pda::bank(mint),
+            price_update: *price_update,
+            receipt_mint,
+            user_account: pda::user(signer, sub_account),
+            user_receipt_token_account: pda::associated_token_account(signer, &receipt_mint, token_program),
+            token_program: *token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::WithdrawCollateral { sub_account, shares },
+        remaining_accounts,
+    )
+}
+
+/// Remaining accounts are the user's [`health_accounts`], needed when disabling collateral
+pub fn set_collateral_enabled(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    sub_account: u8,
+    enabled: bool,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts::SetCollateralEnabled {
+            signer: *signer,
+            mint: *mint,
+            bank: pda::bank(mint),
+            user_account: pda::user(signer, sub_account),
+        },
+        instruction::SetCollateralEnabled { sub_account, enabled },
+        remaining_accounts,
+    )
+}
+
+/// Remaining accounts are the user's [`health_accounts`]
+pub fn transfer_collateral(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+    from_sub_account: u8,
+    to_sub_account: u8,
+    amount: u64,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts::TransferCollateral {
+            signer: *signer,
+            config: pda::config(),
+            mint: *mint,
+            bank: pda::bank(mint),
+            price_update: *price_update,
+            from_user_account: pda::user(signer, from_sub_account),
+            to_user_account: pda::user(signer, to_sub_account),
+        },
+        instruction::TransferCollateral { from_sub_account, to_sub_account, amount },
+        remaining_accounts,
+    )
+}
+
+fn borrow_accounts(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+    token_program: &Pubkey,
+    sub_account: u8,
+    isolated_bank: Option<Pubkey>,
+) -> accounts::Borrow {
+    accounts::Borrow {
+        signer: *signer,
+        config: pda::config(),
+        mint: *mint,
+        bank: pda::bank(mint),
+        price_update: *price_update,
+        bank_token_account: pda::treasury(mint),
+        user_account: pda::user(signer, sub_account),
+        user_token_account: pda::associated_token_account(signer, mint, token_program),
+        isolated_bank,
+        token_program: *token_program,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    }
+}
+
+fn repay_accounts(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+    token_program: &Pubkey,
+    sub_account: u8,
+    isolated_bank: Option<Pubkey>,
+) -> accounts::Repay {
+    accounts::Repay {
+        signer: *signer,
+        config: pda::config(),
+        mint: *mint,
+        bank: pda::bank(mint),
+        price_update: *price_update,
+        bank_token_account: pda::treasury(mint),
+        user_account: pda::user(signer, sub_account),
+        user_token_account: pda::associated_token_account(signer, mint, token_program),
+        isolated_bank,
+        token_program: *token_program,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    }
+}
+
+/// `isolated_bank` is the user's `isolated_bank`, required when the user is in isolation mode.
+/// Remaining accounts are the user's [`health_accounts`], including the borrowed bank.
+#[allow(clippy::too_many_arguments)]
+pub fn borrow(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+    token_program: &Pubkey,
+    sub_account: u8,
+    amount: u64,
+    isolated_bank: Option<Pubkey>,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        borrow_accounts(signer, mint, price_update, token_program, sub_account, isolated_bank),
+        instruction::Borrow { sub_account, amount },
+        remaining_accounts,
+    )
+}
+
+/// Remaining accounts are the user's [`health_accounts`] when the user keeps borrows after the repayment
+#[allow(clippy::too_many_arguments)]
+pub fn repay(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+    token_program: &Pubkey,
+    sub_account: u8,
+    amount: u64,
+    isolated_bank: Option<Pubkey>,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        repay_accounts(signer, mint, price_update, token_program, sub_account, isolated_bank),
+        instruction::Repay { sub_account, amount },
+        remaining_accounts,
+    )
+}
+
+/// Like [`borrow`], `mint` must be the native mint
+#[allow(clippy::too_many_arguments)]
+pub fn borrow_sol(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+    token_program: &Pubkey,
+    sub_account: u8,
+    amount: u64,
+    isolated_bank: Option<Pubkey>,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts::BorrowSol {
+            signer: *signer,
+            config: pda::config(),
+            mint: *mint,
+            bank: pda::bank(mint),
+            price_update: *price_update,
+            bank_token_account: pda::treasury(mint),
+            user_account: pda::user(signer, sub_account),
+            wsol_account: pda::wsol_account(signer),
+            isolated_bank,
+            token_program: *token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::BorrowSol { sub_account, amount },
+        remaining_accounts,
+    )
+}
+
+/// Like [`repay`], `mint` must be the native mint
+#[allow(clippy::too_many_arguments)]
+pub fn repay_sol(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+    token_program: &Pubkey,
+    sub_account: u8,
+    amount: u64,
+    isolated_bank: Option<Pubkey>,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts::RepaySol {
+            signer: *signer,
+            config: pda::config(),
+            mint: *mint,
+            bank: pda::bank(mint),
+            price_update: *price_update,
+            bank_token_account: pda::treasury(mint),
+            user_account: pda::user(signer, sub_account),
+            wsol_account: pda::wsol_account(signer),
+            isolated_bank,
+            token_program: *token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RepaySol { sub_account, amount },
+        remaining_accounts,
+    )
+}
+
+/// Remaining accounts are the liquidated user's [`health_accounts`]
+pub fn liquidate(
+    liquidator: &Pubkey,
+    user_account: &Pubkey,
+    collateral_mint: &Pubkey,
+    borrowed_mint: &Pubkey,
+    token_program: &Pubkey,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts::Liquidate {
+            liquidator: *liquidator,
+            config: pda::config(),
+            collateral_mint: *collateral_mint,
+            borrowed_mint: *borrowed_mint,
+            collateral_bank: pda::bank(collateral_mint),
+            collateral_bank_token_account: pda::treasury(collateral_mint),
+            borrowed_bank: pda::bank(borrowed_mint),
+            borrowed_bank_token_account: pda::treasury(borrowed_mint),
+            user_account: *user_account,
+            liquidator_collateral_token_account: pda::associated_token_account(liquidator, collateral_mint, token_program),
+            liquidator_borrowed_token_account: pda::associated_token_account(liquidator, borrowed_mint, token_program),
+            token_program: *token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Liquidate {},
+        remaining_accounts,
+    )
+}
+
+/// Remaining accounts are the user's [`health_accounts`]. Banks passed as writable
+/// also get their accrued interest stored.
+pub fn refresh_user(user_account: &Pubkey, remaining_accounts: &[AccountMeta]) -> Instruction {
+    build(
+        accounts::RefreshUser { user_account: *user_account },
+        instruction::RefreshUser {},
+        remaining_accounts,
+    )
+}
+
+// Views, simulate these and read the return data
+
+/// Remaining accounts are the user's [`health_accounts`]
+pub fn get_user_position(user_account: &Pubkey, remaining_accounts: &[AccountMeta]) -> Instruction {
+    build(
+        accounts::ViewUser { user_account: *user_account },
+        instruction::GetUserPosition {},
+        remaining_accounts,
+    )
+}
+
+fn view_user_bank(user_account: &Pubkey, mint: &Pubkey, price_update: &Pubkey) -> accounts::ViewUserBank {
+    accounts::ViewUserBank {
+        mint: *mint,
+        bank: pda::bank(mint),
+        price_update: *price_update,
+        user_account: *user_account,
+    }
+}
+
+pub fn get_max_borrowable(user_account: &Pubkey, mint: &Pubkey, price_update: &Pubkey, remaining_accounts: &[AccountMeta]) -> Instruction {
+    build(view_user_bank(user_account, mint, price_update), instruction::GetMaxBorrowable {}, remaining_accounts)
+}
+
+pub fn get_max_withdrawable(user_account: &Pubkey, mint: &Pubkey, price_update: &Pubkey, remaining_accounts: &[AccountMeta]) -> Instruction {
+    build(view_user_bank(user_account, mint, price_update), instruction::GetMaxWithdrawable {}, remaining_accounts)
+}
+
+pub fn get_liquidation_price(user_account: &Pubkey, mint: &Pubkey, price_update: &Pubkey, remaining_accounts: &[AccountMeta]) -> Instruction {
+    build(view_user_bank(user_account, mint, price_update), instruction::GetLiquidationPrice {}, remaining_accounts)
+}
+
+// Flash loans
+
+/// Must be followed by a [`flash_repay`] for the same mint later in the transaction
+pub fn flash_borrow(signer: &Pubkey, mint: &Pubkey, token_program: &Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::FlashBorrow {
+            signer: *signer,
+            config: pda::config(),
+            mint: *mint,
+            bank: pda::bank(mint),
+            bank_token_account: pda::treasury(mint),
+            user_token_account: pda::associated_token_account(signer, mint, token_program),
+            instruction_sysvar: sysvar::instructions::ID,
+            token_program: *token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::FlashBorrow { amount },
+        &[],
+    )
+}
+
+pub fn flash_repay(signer: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Instruction {
+    build(
+        accounts::FlashRepay {
+            signer: *signer,
+            mint: *mint,
+            bank: pda::bank(mint),
+            bank_token_account: pda::treasury(mint),
+            user_token_account: pda::associated_token_account(signer, mint, token_program),
+            token_program: *token_program,
+        },
+        instruction::FlashRepay {},
+        &[],
+    )
+}
@@ -0,0 +1,20 @@
+//! Rust client for the lending program.
+//!
+//! - [`pda`] derives the program's account addresses
+//! - [`instructions`] builds an `Instruction` for every instruction of `lending_protocol`
+//! - [`accounts`] deserializes the program's accounts
+//! - [`math`] runs the program's interest, health and liquidation math off-chain
+//! - `mock_oracle`, behind the `mock-oracle` feature, builds synthetic Pyth price update accounts for tests
+//! - `rpc`, behind the `rpc` feature, re-exports `solana-client`'s blocking `RpcClient` with helpers to fetch and deserialize the program's accounts
+
+pub mod accounts;
+pub mod instructions;
+pub mod math;
+#[cfg(feature = "mock-oracle")]
+pub mod mock_oracle;
+pub mod pda;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+
+pub use lending;
+pub use lending::ID as PROGRAM_ID;
@@ -0,0 +1,96 @@
+//! Off-chain mirrors of the program's interest, health and liquidation math.
+//! Everything is computed by the program's own code, so results match what
+//! the program would compute given the same accounts, prices and time.
+
+use anchor_lang::prelude::{Clock, Pubkey, Result};
+use lending::constants::BASIS_POINTS;
+use lending::state::{Bank, EModeCategory, User};
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+
+pub use lending::risk::{
+    calculate_amount, calculate_liquidation, calculate_liquidation_price, calculate_max_borrowable,
+    calculate_max_withdrawable, calculate_user_values, calculate_value, collateral_weights, liquidation_bonus, PricedBank,
+    UserValues, HEALTHY_HEALTH_FACTOR,
+};
+
+/// The bank as it will be once interest is accrued up to `now`
+pub fn accrued_bank(bank: &Bank, now: i64) -> Result<Bank> {
+    let mut bank = bank.clone();
+    bank.accrue_interest(now)?;
+    Ok(bank)
+}
+
+/// Borrow rate in basis points per year at the bank's current utilization
+pub fn borrow_rate(bank: &Bank) -> Result<u64> {
+    bank.borrow_rate()
+}
+
+/// Rate depositors earn in basis points per year. All borrow interest goes to depositors,
+/// so this is the borrow rate scaled by utilization.
+pub fn supply_rate(bank: &Bank) -> Result<u64> {
+    Ok((bank.borrow_rate()? as u128 * bank.utilization_rate() as u128 / BASIS_POINTS as u128) as u64)
+}
+
+/// The bank's price from a price update, with the checks the program applies at `now`
+pub fn checked_price(bank: &Bank, price_update: &PriceUpdateV2, now: i64) -> Result<Price> {
+    let clock = Clock { unix_timestamp: now, ..Clock::default() };
+    lending::risk::get_price(bank, price_update, &clock)
+}
+
+/// A bank of one of the user's positions, accrued up to `now` like the program does before pricing it
+pub fn priced_bank(key: Pubkey, bank: &Bank, price: Price, now: i64) -> Result<PricedBank> {
+    Ok(PricedBank { key, bank: accrued_bank(bank, now)?, price })
+}
+
+/// Health factor in percent, below [`HEALTHY_HEALTH_FACTOR`] the user can be liquidated
+pub fn health_factor(user: &User, priced_banks: &[PricedBank], emode: Option<&EModeCategory>) -> u64 {
+    calculate_user_values(user, priced_banks, emode).health_factor()
+}
+
+/// Amounts of a liquidation of `user` repaying debt in `borrowed_bank` and seizing collateral in `collateral_bank`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiquidationQuote {
+    pub repaid_amount: u64,
+    pub seized_amount: u64,
+    /// USD value of the repaid debt, scaled by 10^VALUE_DECIMALS
+    pub repaid_value: u128,
+    /// USD value of the seized collateral, scaled by 10^VALUE_DECIMALS
+    pub seized_value: u128,
+}
+
+/// What `liquidate` would repay and seize, or `None` if the user is healthy or has no such debt and collateral
+pub fn liquidation_quote(
+    user: &User,
+    priced_banks: &[PricedBank],
+    emode: Option<&EModeCategory>,
+    collateral_bank: &Pubkey,
+    borrowed_bank: &Pubkey,
+) -> Option<LiquidationQuote> {
+    if health_factor(user, priced_banks, emode) >= HEALTHY_HEALTH_FACTOR {
+        return None;
+    }
+    let find = |key: &Pubkey| priced_banks.iter().find(|priced_bank| priced_bank.key == *key);
+    let collateral = find(collateral_bank)?;
+    let borrowed = find(borrowed_bank)?;
+
+    let borrow_shares = user.position(borrowed_bank)?.borrow_shares;
+    let collateral_position = user.position(collateral_bank)?;
+    if borrow_shares == 0 || !collateral_position.is_seizable() {
+        return None;
+    }
+    let (repaid_amount, seized_amount) = calculate_liquidation(
+        borrowed.bank.borrow_amount_for(borrow_shares),
+        collateral.bank.deposit_amount_for(collateral_position.deposit_shares),
+        &borrowed.bank,
+        &borrowed.price,
+        &collateral.bank,
+        &collateral.price,
+        emode,
+    );
+    Some(LiquidationQuote {
+        repaid_amount,
+        seized_amount,
+        repaid_value: calculate_value(repaid_amount, &borrowed.price, borrowed.bank.mint_decimals),
+        seized_value: calculate_value(seized_amount, &collateral.price, collateral.bank.mint_decimals),
+    })
+}
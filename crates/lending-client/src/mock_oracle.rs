@@ -0,0 +1,119 @@
+//! Synthetic Pyth `PriceUpdateV2` accounts for tests and local validators, where the Pyth receiver
+//! program and its verified price updates are not available. The program only checks the account's
+//! owner, feed id, verification level and publish time, so any price can be set up this way.
+
+use anchor_lang::prelude::{Pubkey, Rent};
+use anchor_lang::AccountSerialize;
+use pyth_solana_receiver_sdk::price_update::{FeedId, PriceFeedMessage, PriceUpdateV2, VerificationLevel};
+
+/// Owner a price update account must have for the program to accept it
+pub const PRICE_UPDATE_OWNER: Pubkey = pyth_solana_receiver_sdk::ID;
+
+/// Builder of a `PriceUpdateV2` for a single feed.
+/// The EMA follows the price and the previous publish time follows the publish time unless set explicitly.
+#[derive(Clone, Debug)]
+pub struct MockPriceUpdate {
+    pub feed_id: FeedId,
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub ema: Option<(i64, u64)>,
+    pub publish_time: i64,
+    pub prev_publish_time: Option<i64>,
+    pub verification_level: VerificationLevel,
+    pub posted_slot: u64,
+    pub write_authority: Pubkey,
+}
+
+impl MockPriceUpdate {
+    /// A fully verified update of `feed_id` with the given price, no confidence interval, published at time 0
+    pub fn new(feed_id: FeedId, price: i64, exponent: i32) -> Self {
+        Self {
+            feed_id,
+            price,
+            conf: 0,
+            exponent,
+            ema: None,
+            publish_time: 0,
+            prev_publish_time: None,
+            verification_level: VerificationLevel::Full,
+            posted_slot: 0,
+            write_authority: Pubkey::default(),
+        }
+    }
+
+    pub fn price(mut self, price: i64, exponent: i32) -> Self {
+        self.price = price;
+        self.exponent = exponent;
+        self
+    }
+
+    pub fn conf(mut self, conf: u64) -> Self {
+        self.conf = conf;
+        self
+    }
+
+    pub fn ema(mut self, ema_price: i64, ema_conf: u64) -> Self {
+        self.ema = Some((ema_price, ema_conf));
+        self
+    }
+
+    pub fn publish_time(mut self, publish_time: i64) -> Self {
+        self.publish_time = publish_time;
+        self
+    }
+
+    pub fn prev_publish_time(mut self, prev_publish_time: i64) -> Self {
+        self.prev_publish_time = Some(prev_publish_time);
+        self
+    }
+
+    /// Updates with less than `Full` verification are rejected by the program
+    pub fn verification_level(mut self, verification_level: VerificationLevel) -> Self {
+        self.verification_level = verification_level;
+        self
+    }
+
+    pub fn posted_slot(mut self, posted_slot: u64) -> Self {
+        self.posted_slot = posted_slot;
+        self
+    }
+
+    pub fn write_authority(mut self, write_authority: Pubkey) -> Self {
+        self.write_authority = write_authority;
+        self
+    }
+
+    pub fn build(&self) -> PriceUpdateV2 {
+        let (ema_price, ema_conf) = self.ema.unwrap_or((self.price, self.conf));
+        PriceUpdateV2 {
+            write_authority: self.write_authority,
+            verification_level: self.verification_level,
+            price_message: PriceFeedMessage {
+                feed_id: self.feed_id,
+                price: self.price,
+                conf: self.conf,
+                exponent: self.exponent,
+                publish_time: self.publish_time,
+                prev_publish_time: self.prev_publish_time.unwrap_or(self.publish_time),
+                ema_price,
+                ema_conf,
+            },
+            posted_slot: self.posted_slot,
+        }
+    }
+
+    /// Account data, including the Anchor discriminator, to be owned by [`PRICE_UPDATE_OWNER`]
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(PriceUpdateV2::LEN);
+        self.build().try_serialize(&mut data).expect("price update serializes into a Vec");
+        // Accounts are sized for the largest verification level
+        data.resize(PriceUpdateV2::LEN, 0);
+        data
+    }
+
+    /// Rent-exempt balance of the account
+    pub fn lamports(&self) -> u64 {
+        Rent::default().minimum_balance(PriceUpdateV2::LEN)
+    }
+}
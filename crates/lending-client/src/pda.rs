@@ -0,0 +1,61 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use pyth_solana_receiver_sdk::PYTH_PUSH_ORACLE_ID;
+
+use crate::PROGRAM_ID;
+
+/// Protocol config, seeds `["config"]`
+pub fn config() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &PROGRAM_ID).0
+}
+
+/// Bank of a mint, seeds `[mint]`
+pub fn bank(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[mint.as_ref()], &PROGRAM_ID).0
+}
+
+/// Token account holding a bank's deposits, seeds `["treasury", mint]`
+pub fn treasury(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury", mint.as_ref()], &PROGRAM_ID).0
+}
+
+/// Mint of a bank's receipt tokens, seeds `["receipt", mint]`
+pub fn receipt_mint(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"receipt", mint.as_ref()], &PROGRAM_ID).0
+}
+
+/// User account of a wallet, seeds `[owner, sub_account]`
+pub fn user(owner: &Pubkey, sub_account: u8) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), &[sub_account]], &PROGRAM_ID).0
+}
+
+/// Ephemeral wSOL account of a wallet used by the SOL instructions, seeds `["wsol", owner]`
+pub fn wsol_account(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"wsol", owner.as_ref()], &PROGRAM_ID).0
+}
+
+/// E-mode category, seeds `["emode", id]`
+pub fn emode_category(id: u8) -> Pubkey {
+    Pubkey::find_program_address(&[b"emode", &[id]], &PROGRAM_ID).0
+}
+
+/// Queued change of a bank's risk parameters or of an e-mode category, seeds `["config_change", target]`
+pub fn config_change(target: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"config_change", target.as_ref()], &PROGRAM_ID).0
+}
+
+/// Program data account of the upgradeable lending program
+pub fn program_data() -> Pubkey {
+    Pubkey::find_program_address(&[PROGRAM_ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+/// Associated token account of a wallet, as used for the user side of transfers
+pub fn associated_token_account(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+/// Price feed account the Pyth push oracle keeps updated for a feed id, in shard `shard_id`
+pub fn price_feed(feed_id: &[u8; 32], shard_id: u16) -> Pubkey {
+    Pubkey::find_program_address(&[&shard_id.to_le_bytes(), feed_id], &PYTH_PUSH_ORACLE_ID).0
+}
@@ -0,0 +1,83 @@
+//! Helpers on top of `solana-client`'s blocking [`RpcClient`] for what the lending tools need:
+//! reading and deserializing the program's accounts, scanning them by type and reading the clock.
+
+use anchor_lang::{AccountDeserialize, Discriminator};
+use anyhow::{anyhow, Context, Result};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::clock::Clock;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar;
+
+pub use solana_client::rpc_client::RpcClient;
+
+/// Most accounts `getMultipleAccounts` returns in one request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Client for `url` using `confirmed` commitment
+pub fn client(url: &str) -> RpcClient {
+    RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed())
+}
+
+pub trait RpcClientExt {
+    /// The account, `None` if it doesn't exist
+    fn get_optional_account(&self, pubkey: &Pubkey) -> Result<Option<Account>>;
+
+    /// Accounts in the same order as `pubkeys`, `None` for accounts that don't exist.
+    /// Unlike `get_multiple_accounts`, any number of accounts can be fetched.
+    fn get_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>>;
+
+    /// Fetches and deserializes an Anchor account, failing if it doesn't exist
+    fn get_anchor_account<T: AccountDeserialize>(&self, pubkey: &Pubkey) -> Result<T>;
+
+    /// Every account of type `T` owned by `program_id`, found by its Anchor discriminator.
+    /// Accounts that fail to deserialize are skipped.
+    fn get_program_anchor_accounts<T: AccountDeserialize + Discriminator>(&self, program_id: &Pubkey) -> Result<Vec<(Pubkey, T)>>;
+
+    /// The cluster's `Clock` sysvar, the time the program sees
+    fn get_clock(&self) -> Result<Clock>;
+}
+
+impl RpcClientExt for RpcClient {
+    fn get_optional_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        Ok(self.get_account_with_commitment(pubkey, self.commitment())?.value)
+    }
+
+    fn get_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            accounts.extend(self.get_multiple_accounts_with_commitment(chunk, self.commitment())?.value);
+        }
+        Ok(accounts)
+    }
+
+    fn get_anchor_account<T: AccountDeserialize>(&self, pubkey: &Pubkey) -> Result<T> {
+        let account = self.get_optional_account(pubkey)?.ok_or_else(|| anyhow!("account {pubkey} not found"))?;
+        T::try_deserialize(&mut account.data.as_slice()).with_context(|| format!("failed to deserialize account {pubkey}"))
+    }
+
+    fn get_program_anchor_accounts<T: AccountDeserialize + Discriminator>(&self, program_id: &Pubkey) -> Result<Vec<(Pubkey, T)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &T::DISCRIMINATOR))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(self.commitment()),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        Ok(self
+            .get_program_accounts_with_config(program_id, config)?
+            .into_iter()
+            .filter_map(|(pubkey, account)| Some((pubkey, T::try_deserialize(&mut account.data.as_slice()).ok()?)))
+            .collect())
+    }
+
+    fn get_clock(&self) -> Result<Clock> {
+        let account = self.get_optional_account(&sysvar::clock::ID)?.ok_or_else(|| anyhow!("clock sysvar not found"))?;
+        Ok(bincode::deserialize(&account.data)?)
+    }
+}
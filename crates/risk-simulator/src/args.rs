@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use lending_client::lending::constants::{DEFAULT_LIQUIDATION_BONUS, DEFAULT_LIQUIDATION_CLOSE_FACTOR};
+use risk_simulator::simulation::Scenario;
+
+/// Replays <PRICES>, a CSV of `timestamp,price` rows of the collateral asset in USD (e.g. SOL/USD),
+/// against synthetic users who deposit it and borrow a $1 stablecoin, and prints the liquidations,
+/// bad debt and utilization for every combination of the risk parameters.
+#[derive(Debug, Parser)]
+#[command(name = "risk-simulator", version)]
+pub struct Args {
+    /// CSV file of `timestamp,price` rows
+    pub prices: PathBuf,
+    /// Max LTVs to try, in percent
+    #[arg(long = "max-ltv", value_name = "PERCENTS", value_delimiter = ',', default_value = "70")]
+    pub max_ltvs: Vec<u64>,
+    /// Liquidation thresholds to try, in percent
+    #[arg(long = "liquidation-threshold", value_name = "PERCENTS", value_delimiter = ',', default_value = "80")]
+    pub liquidation_thresholds: Vec<u64>,
+    /// Liquidation bonuses to try, in percent
+    #[arg(long = "liquidation-bonus", value_name = "PERCENTS", value_delimiter = ',', default_values_t = [DEFAULT_LIQUIDATION_BONUS])]
+    pub liquidation_bonuses: Vec<u64>,
+    /// Share of the debt a liquidation repays, in percent
+    #[arg(long, value_name = "PERCENT", default_value_t = DEFAULT_LIQUIDATION_CLOSE_FACTOR)]
+    pub close_factor: u64,
+    /// Number of borrowers
+    #[arg(long, default_value_t = 1000)]
+    pub users: usize,
+    /// Collateral of each user in whole tokens
+    #[arg(long, value_name = "MIN-MAX", value_parser = parse_range, default_value = "1-100")]
+    pub collateral: (u64, u64),
+    /// Share of their borrowing power users borrow, in percent
+    #[arg(long, value_name = "MIN-MAX", value_parser = parse_range, default_value = "50-100")]
+    pub usage: (u64, u64),
+    /// Stablecoins supplied to the borrowed bank, default enough for 80% utilization after the initial borrows
+    #[arg(long, value_name = "TOKENS")]
+    pub supply: Option<u64>,
+    /// Borrow rate at full utilization, in basis points
+    #[arg(long, default_value_t = 1000)]
+    pub interest_rate: u64,
+    /// Borrow rate at no utilization, in basis points
+    #[arg(long, default_value_t = 0)]
+    pub base_rate: u64,
+    /// Seed of the synthetic users
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+impl Args {
+    pub fn scenario(&self) -> Scenario {
+        Scenario {
+            users: self.users,
+            seed: self.seed,
+            collateral: self.collateral,
+            usage: self.usage,
+            supply: self.supply,
+            interest_rate: self.interest_rate,
+            base_rate: self.base_rate,
+        }
+    }
+}
+
+fn parse_range(value: &str) -> Result<(u64, u64), String> {
+    let (min, max) = value.split_once('-').ok_or("expected <MIN>-<MAX>")?;
+    let min = min.parse().map_err(|err| format!("invalid minimum: {err}"))?;
+    let max = max.parse().map_err(|err| format!("invalid maximum: {err}"))?;
+    Ok((min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::error::ErrorKind;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(["risk-simulator"].iter().chain(args))
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse(&["prices.csv"]).unwrap();
+        assert_eq!(args.prices, PathBuf::from("prices.csv"));
+        assert_eq!((args.max_ltvs, args.liquidation_thresholds), (vec![70], vec![80]));
+        assert_eq!((args.liquidation_bonuses, args.close_factor), (vec![DEFAULT_LIQUIDATION_BONUS], DEFAULT_LIQUIDATION_CLOSE_FACTOR));
+        assert_eq!((args.collateral, args.usage, args.supply), ((1, 100), (50, 100), None));
+    }
+
+    #[test]
+    fn lists_and_ranges() {
+        let args = parse(&["prices.csv", "--max-ltv", "60,70,75", "--usage", "10-20", "--supply", "5000"]).unwrap();
+        assert_eq!(args.max_ltvs, vec![60, 70, 75]);
+        let scenario = args.scenario();
+        assert_eq!((scenario.usage, scenario.supply), ((10, 20), Some(5000)));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert_eq!(parse(&[]).unwrap_err().kind(), ErrorKind::MissingRequiredArgument);
+        assert_eq!(parse(&["prices.csv", "--max-ltv", "70,x"]).unwrap_err().kind(), ErrorKind::ValueValidation);
+        assert_eq!(parse(&["prices.csv", "--collateral", "100"]).unwrap_err().kind(), ErrorKind::ValueValidation);
+        assert_eq!(parse(&["prices.csv", "--users", "10", "--user", "1"]).unwrap_err().kind(), ErrorKind::UnknownArgument);
+    }
+}
@@ -0,0 +1,8 @@
+//! Off-chain market simulator for tuning the lending program's risk parameters.
+//!
+//! - [`price_path`] reads price paths of the collateral asset from CSV
+//! - [`simulation`] replays a path against synthetic borrowers with the program's own interest, health
+//!   and liquidation math, and reports liquidations, bad debt and utilization
+
+pub mod price_path;
+pub mod simulation;
@@ -0,0 +1,71 @@
+//! Replays a price path against synthetic borrowers for every combination of the given risk parameters,
+//! printing one CSV row of results per combination.
+
+mod args;
+
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use lending_client::lending::constants::VALUE_DECIMALS;
+use risk_simulator::price_path;
+use risk_simulator::simulation::{self, CollateralParams};
+
+use crate::args::Args;
+
+/// USD value scaled by 10^VALUE_DECIMALS, with cents
+fn format_usd(value: u128) -> String {
+    let cents = value / 10u128.pow(VALUE_DECIMALS as u32 - 2);
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+fn run() -> Result<()> {
+    let args = Args::parse();
+    let scenario = args.scenario();
+    let prices = args.prices.display();
+    let csv = std::fs::read_to_string(&args.prices).with_context(|| format!("failed to read {prices}"))?;
+    let path = price_path::parse_csv(&csv).with_context(|| format!("failed to parse {prices}"))?;
+
+    println!(
+        "max_ltv,liquidation_threshold,liquidation_bonus,initial_borrowed_usd,liquidations,liquidated_usd,seized_usd,\
+         bad_debt_usd,peak_bad_debt_usd,insolvent_users,average_utilization_bps,peak_utilization_bps"
+    );
+    for &max_ltv in &args.max_ltvs {
+        for &liquidation_threshold in &args.liquidation_thresholds {
+            for &liquidation_bonus in &args.liquidation_bonuses {
+                let liquidation_close_factor = args.close_factor;
+                let params = CollateralParams { max_ltv, liquidation_threshold, liquidation_bonus, liquidation_close_factor };
+                let report = match simulation::simulate(&path, &scenario, &params) {
+                    Ok(report) => report,
+                    Err(err) => {
+                        eprintln!("skipping max LTV {max_ltv}%, threshold {liquidation_threshold}%, bonus {liquidation_bonus}%: {err:#}");
+                        continue;
+                    }
+                };
+                println!(
+                    "{max_ltv},{liquidation_threshold},{liquidation_bonus},{},{},{},{},{},{},{},{},{}",
+                    format_usd(report.initial_borrowed_value),
+                    report.liquidations,
+                    format_usd(report.liquidated_value),
+                    format_usd(report.seized_value),
+                    format_usd(report.bad_debt),
+                    format_usd(report.peak_bad_debt),
+                    report.insolvent_users,
+                    report.average_utilization,
+                    report.peak_utilization,
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
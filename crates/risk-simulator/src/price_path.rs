@@ -0,0 +1,104 @@
+//! Price paths of the collateral asset, read from `timestamp,price` CSV rows.
+
+use anyhow::{anyhow, bail, Context, Result};
+use pyth_solana_receiver_sdk::price_update::Price;
+
+/// Exponent of the parsed prices, the one Pyth uses for USD feeds
+pub const PRICE_EXPONENT: i32 = -8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PricePoint {
+    /// Unix timestamp in seconds
+    pub timestamp: i64,
+    pub price: Price,
+}
+
+/// Parses a price in USD such as `142.5` into a Pyth price with exponent [`PRICE_EXPONENT`]
+pub fn parse_price(value: &str) -> Result<i64> {
+    let decimals = PRICE_EXPONENT.unsigned_abs() as usize;
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        bail!("invalid price {value}");
+    }
+    if fraction.len() > decimals {
+        bail!("too many decimals in {value}");
+    }
+    let price: i64 = format!("{whole}{fraction:0<decimals$}").parse().with_context(|| format!("price {value} is too large"))?;
+    if price == 0 {
+        bail!("price {value} is not positive");
+    }
+    Ok(price)
+}
+
+/// Parses CSV rows of a Unix timestamp and a USD price, in chronological order.
+/// Blank lines, `#` comments and a header row are skipped.
+pub fn parse_csv(csv: &str) -> Result<Vec<PricePoint>> {
+    let mut points: Vec<PricePoint> = Vec::new();
+    let mut rows = csv
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+    if rows.peek().is_some_and(|(_, line)| line.split(',').next().is_some_and(|first| first.trim().parse::<i64>().is_err())) {
+        rows.next();
+    }
+    for (line_number, line) in rows {
+        let (timestamp, price) = line.split_once(',').ok_or_else(|| anyhow!("line {line_number}: expected timestamp,price"))?;
+        let timestamp: i64 = timestamp.trim().parse().with_context(|| format!("line {line_number}: invalid timestamp"))?;
+        let price = parse_price(price.trim()).with_context(|| format!("line {line_number}: invalid price"))?;
+        if points.last().is_some_and(|last| timestamp < last.timestamp) {
+            bail!("line {line_number}: timestamp {timestamp} is before the previous row");
+        }
+        points.push(PricePoint { timestamp, price: Price { price, conf: 0, exponent: PRICE_EXPONENT, publish_time: timestamp } });
+    }
+    if points.is_empty() {
+        bail!("no prices found");
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_are_scaled_to_the_pyth_exponent() {
+        assert_eq!(parse_price("142").unwrap(), 14_200_000_000);
+        assert_eq!(parse_price("142.5").unwrap(), 14_250_000_000);
+        assert_eq!(parse_price("0.00000001").unwrap(), 1);
+        assert_eq!(parse_price("1.").unwrap(), 100_000_000);
+        assert_eq!(parse_price(".5").unwrap(), 50_000_000);
+    }
+
+    #[test]
+    fn invalid_prices_are_rejected() {
+        for value in ["0", "0.0", "", ".", "-1", "-0.5", "+1", "1.-5", "1.000000001", "abc", "1.5e3", "1,5", "99999999999999"] {
+            assert!(parse_price(value).is_err(), "{value} should be rejected");
+        }
+    }
+
+    #[test]
+    fn csv_skips_header_comments_and_blank_lines() {
+        let csv = "# SOL/USD\ntimestamp,price\n\n1700000000, 100\n  1700000060,99.5  \n# gap\n1700000060,98\n";
+        let points = parse_csv(csv).unwrap();
+        let rows: Vec<_> = points.iter().map(|point| (point.timestamp, point.price.price, point.price.publish_time)).collect();
+        assert_eq!(rows, vec![
+            (1_700_000_000, 10_000_000_000, 1_700_000_000),
+            (1_700_000_060, 9_950_000_000, 1_700_000_060),
+            (1_700_000_060, 9_800_000_000, 1_700_000_060),
+        ]);
+        assert!(points.iter().all(|point| point.price.exponent == PRICE_EXPONENT && point.price.conf == 0));
+    }
+
+    #[test]
+    fn invalid_csv_is_rejected_with_its_line() {
+        let error = |csv: &str| format!("{:#}", parse_csv(csv).unwrap_err());
+        assert!(error("").contains("no prices found"));
+        assert!(error("timestamp,price\n# nothing yet\n").contains("no prices found"));
+        assert!(error("1700000000,100\n1700000060").contains("line 2: expected timestamp,price"));
+        assert!(error("1700000000,100\nsoon,100").contains("line 2: invalid timestamp"));
+        assert!(error("1700000000,100\n\n1700000060,0").contains("line 3: invalid price"));
+        assert!(error("1700000060,100\n1700000000,100").contains("line 2: timestamp 1700000000 is before the previous row"));
+    }
+}
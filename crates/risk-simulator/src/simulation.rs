@@ -0,0 +1,332 @@
+//! A two-bank market replayed along a price path: borrowers deposit the collateral asset and borrow
+//! a $1 stablecoin, interest accrues between price points, and unhealthy borrowers are liquidated
+//! as soon as a price makes them liquidatable. Banks, users and every computation are the program's
+//! own, so the outcome is what the program would produce for the same prices and liquidators.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{AnchorDeserialize, Space};
+use anyhow::{bail, Result};
+use lending_client::lending::constants::{INDEX_SCALE, MAXIMUM_AGE};
+use lending_client::lending::state::{Bank, RiskParams, User};
+use lending_client::math::{self, PricedBank};
+use pyth_solana_receiver_sdk::price_update::Price;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::price_path::{PricePoint, PRICE_EXPONENT};
+
+pub const COLLATERAL_BANK: Pubkey = Pubkey::new_from_array([1; 32]);
+pub const BORROWED_BANK: Pubkey = Pubkey::new_from_array([2; 32]);
+pub const COLLATERAL_DECIMALS: u8 = 9;
+pub const BORROWED_DECIMALS: u8 = 6;
+
+/// Liquidations of one user at a single price point stop after this many, each repays at most the close factor
+const MAX_LIQUIDATIONS_PER_POINT: usize = 64;
+
+/// Risk parameters of the collateral bank under evaluation, in percent
+#[derive(Clone, Copy, Debug)]
+pub struct CollateralParams {
+    pub max_ltv: u64,
+    pub liquidation_threshold: u64,
+    pub liquidation_bonus: u64,
+    pub liquidation_close_factor: u64,
+}
+
+/// The borrowers and the borrowed bank
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub users: usize,
+    /// Seed of the random population, the same seed gives the same users for every set of parameters
+    pub seed: u64,
+    /// Range of each user's collateral, in whole tokens
+    pub collateral: (u64, u64),
+    /// Range of the share of their borrowing power each user borrows at the first price, in percent
+    pub usage: (u64, u64),
+    /// Tokens supplied to the borrowed bank, by default enough for 80% utilization after the initial borrows
+    pub supply: Option<u64>,
+    /// Borrow rate of the borrowed bank at full utilization and at none, in basis points per year
+    pub interest_rate: u64,
+    pub base_rate: u64,
+}
+
+/// Outcome of a price path. USD values are scaled by 10^VALUE_DECIMALS, utilization is in basis points.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub initial_borrowed_value: u128,
+    pub liquidations: u64,
+    /// Value of the debt repaid by liquidators
+    pub liquidated_value: u128,
+    /// Value of the collateral paid to liquidators for it, including the bonus
+    pub seized_value: u128,
+    /// Debt left uncovered by collateral at the last price
+    pub bad_debt: u128,
+    /// Largest uncovered debt at any price
+    pub peak_bad_debt: u128,
+    /// Users whose debt exceeds their collateral at the last price
+    pub insolvent_users: usize,
+    /// Utilization of the borrowed bank averaged over the price points
+    pub average_utilization: u64,
+    pub peak_utilization: u64,
+}
+
+impl CollateralParams {
+    fn risk_params(&self) -> Result<RiskParams> {
+        let params = RiskParams {
+            liquidation_threshold: self.liquidation_threshold,
+            liquidation_bonus: self.liquidation_bonus,
+            liquidation_close_factor: self.liquidation_close_factor,
+            max_ltv: self.max_ltv,
+            interest_rate: 0,
+            base_rate: 0,
+            oracle_feed_id: [0; 32],
+            max_price_age: MAXIMUM_AGE,
+            emode_category: 0,
+            collateral_enabled: true,
+            isolated: false,
+            isolation_debt_ceiling: 0,
+            borrowable_in_isolation: false,
+        };
+        // Rejected like `queue_config_change` would reject them
+        if params.validate().is_err() {
+            bail!("invalid risk parameters {self:?}");
+        }
+        Ok(params)
+    }
+}
+
+/// A bank as `init_bank` leaves it, from a zeroed account, with the given risk parameters
+fn new_bank(mint_decimals: u8, params: &RiskParams, now: i64) -> Bank {
+    let mut bank = Bank::deserialize(&mut vec![0; Bank::INIT_SPACE].as_slice()).expect("zeroed bank deserializes");
+    bank.mint_decimals = mint_decimals;
+    bank.apply_risk_params(params);
+    bank.borrow_index = INDEX_SCALE;
+    bank.deposit_index = INDEX_SCALE;
+    bank.last_updated = now;
+    bank.borrow_enabled = true;
+    bank.listed = true;
+    bank
+}
+
+fn new_user() -> User {
+    User::deserialize(&mut vec![0; User::INIT_SPACE].as_slice()).expect("zeroed user deserializes")
+}
+
+struct Market {
+    collateral_bank: Bank,
+    borrowed_bank: Bank,
+    users: Vec<User>,
+    collateral_price: Price,
+    borrowed_price: Price,
+}
+
+impl Market {
+    fn priced_banks(&self) -> [PricedBank; 2] {
+        [
+            PricedBank { key: COLLATERAL_BANK, bank: self.collateral_bank.clone(), price: self.collateral_price },
+            PricedBank { key: BORROWED_BANK, bank: self.borrowed_bank.clone(), price: self.borrowed_price },
+        ]
+    }
+
+    /// `deposit` followed by `deposit_collateral` of all the shares
+    fn deposit_collateral(&mut self, user: usize, amount: u64) -> Result<()> {
+        let shares = self.collateral_bank.add_deposit(amount)?;
+        self.users[user].get_or_create_position(&COLLATERAL_BANK)?.deposit_shares += shares;
+        Ok(())
+    }
+
+    fn supply(&mut self, amount: u64) -> Result<()> {
+        self.borrowed_bank.add_deposit(amount)?;
+        Ok(())
+    }
+
+    fn borrow(&mut self, user: usize, amount: u64) -> Result<()> {
+        self.users[user].add_borrow(&BORROWED_BANK, &mut self.borrowed_bank, amount, None)?;
+        Ok(())
+    }
+
+    fn max_borrowable(&self, user: usize) -> u64 {
+        let values = math::calculate_user_values(&self.users[user], &self.priced_banks(), None);
+        math::calculate_max_borrowable(&values, &self.borrowed_bank, &self.borrowed_price)
+    }
+
+    /// Applies the liquidation `liquidate` would make, returning false when there is nothing to liquidate
+    fn liquidate(&mut self, user: usize, report: &mut Report) -> Result<bool> {
+        let Some(quote) = math::liquidation_quote(&self.users[user], &self.priced_banks(), None, &COLLATERAL_BANK, &BORROWED_BANK) else {
+            return Ok(false);
+        };
+        if quote.repaid_amount == 0 || quote.seized_amount == 0 {
+            return Ok(false);
+        }
+
+        // The same updates as `process_liquidate`
+        self.users[user].apply_liquidation(
+            &BORROWED_BANK,
+            &mut self.borrowed_bank,
+            quote.repaid_amount,
+            &COLLATERAL_BANK,
+            &mut self.collateral_bank,
+            quote.seized_amount,
+        )?;
+
+        report.liquidations += 1;
+        report.liquidated_value += quote.repaid_value;
+        report.seized_value += quote.seized_value;
+        Ok(true)
+    }
+
+    /// Debt not covered by the full value of the collateral, and the number of users with such debt
+    fn bad_debt(&self) -> (u128, usize) {
+        let priced_banks = self.priced_banks();
+        self.users
+            .iter()
+            .filter(|user| user.has_borrows())
+            .map(|user| {
+                let values = math::calculate_user_values(user, &priced_banks, None);
+                values.borrowed_value.saturating_sub(values.collateral_value)
+            })
+            .filter(|shortfall| *shortfall > 0)
+            .fold((0, 0), |(total, users), shortfall| (total + shortfall, users + 1))
+    }
+}
+
+/// Runs `scenario` along `path` with the collateral bank configured with `params`
+pub fn simulate(path: &[PricePoint], scenario: &Scenario, params: &CollateralParams) -> Result<Report> {
+    let Some(start) = path.first() else {
+        bail!("empty price path");
+    };
+    let borrowed_params = RiskParams {
+        liquidation_threshold: 0,
+        liquidation_bonus: 0,
+        liquidation_close_factor: 0,
+        max_ltv: 0,
+        interest_rate: scenario.interest_rate,
+        base_rate: scenario.base_rate,
+        oracle_feed_id: [0; 32],
+        max_price_age: MAXIMUM_AGE,
+        emode_category: 0,
+        collateral_enabled: true,
+        isolated: false,
+        isolation_debt_ceiling: 0,
+        borrowable_in_isolation: false,
+    };
+    if borrowed_params.validate().is_err() {
+        bail!("base rate {} exceeds interest rate {}", scenario.base_rate, scenario.interest_rate);
+    }
+    let (min_collateral, max_collateral) = scenario.collateral;
+    let (min_usage, max_usage) = scenario.usage;
+    if min_collateral > max_collateral || min_usage > max_usage || max_usage > 100 {
+        bail!("invalid collateral or usage range");
+    }
+
+    let mut market = Market {
+        collateral_bank: new_bank(COLLATERAL_DECIMALS, &params.risk_params()?, start.timestamp),
+        borrowed_bank: new_bank(BORROWED_DECIMALS, &borrowed_params, start.timestamp),
+        users: (0..scenario.users).map(|_| new_user()).collect(),
+        collateral_price: start.price,
+        borrowed_price: Price { price: 10i64.pow(PRICE_EXPONENT.unsigned_abs()), conf: 0, exponent: PRICE_EXPONENT, publish_time: start.timestamp },
+    };
+
+    // Every user deposits collateral, then borrows a share of what it allows once the bank is supplied
+    let mut rng = StdRng::seed_from_u64(scenario.seed);
+    let mut borrows = Vec::with_capacity(scenario.users);
+    for user in 0..scenario.users {
+        let collateral = rng.gen_range(min_collateral..=max_collateral) * 10u64.pow(COLLATERAL_DECIMALS as u32);
+        market.deposit_collateral(user, collateral)?;
+        let usage = rng.gen_range(min_usage..=max_usage);
+        borrows.push((market.max_borrowable(user) as u128 * usage as u128 / 100) as u64);
+    }
+    let total_borrows: u64 = borrows.iter().sum();
+    let supply = match scenario.supply {
+        Some(supply) => supply * 10u64.pow(BORROWED_DECIMALS as u32),
+        None => total_borrows / 4 * 5,
+    };
+    market.supply(supply)?;
+    for (user, amount) in borrows.into_iter().enumerate() {
+        let available = market.borrowed_bank.total_deposits - market.borrowed_bank.total_borrowed;
+        let amount = amount.min(available);
+        if amount > 0 {
+            market.borrow(user, amount)?;
+        }
+    }
+
+    let mut report = Report {
+        initial_borrowed_value: math::calculate_value(market.borrowed_bank.total_borrowed, &market.borrowed_price, BORROWED_DECIMALS),
+        ..Report::default()
+    };
+    let mut utilization_sum = 0u128;
+    for point in path {
+        market.collateral_bank.accrue_interest(point.timestamp)?;
+        market.borrowed_bank.accrue_interest(point.timestamp)?;
+        market.collateral_price = point.price;
+        market.borrowed_price.publish_time = point.timestamp;
+
+        for user in 0..market.users.len() {
+            for _ in 0..MAX_LIQUIDATIONS_PER_POINT {
+                if !market.liquidate(user, &mut report)? {
+                    break;
+                }
+            }
+        }
+
+        let (bad_debt, insolvent_users) = market.bad_debt();
+        report.bad_debt = bad_debt;
+        report.insolvent_users = insolvent_users;
+        report.peak_bad_debt = report.peak_bad_debt.max(bad_debt);
+        let utilization = market.borrowed_bank.utilization_rate();
+        report.peak_utilization = report.peak_utilization.max(utilization);
+        utilization_sum += utilization as u128;
+    }
+    report.average_utilization = (utilization_sum / path.len() as u128) as u64;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_path::parse_csv;
+
+    const DAY: i64 = 24 * 60 * 60;
+    const PARAMS: CollateralParams = CollateralParams { max_ltv: 70, liquidation_threshold: 80, liquidation_bonus: 5, liquidation_close_factor: 50 };
+
+    fn scenario() -> Scenario {
+        Scenario { users: 50, seed: 7, collateral: (1, 100), usage: (50, 100), supply: None, interest_rate: 1000, base_rate: 0 }
+    }
+
+    /// A path through `prices` with one point a day
+    fn daily_path(prices: &[&str]) -> Vec<PricePoint> {
+        let csv: Vec<String> = prices.iter().enumerate().map(|(day, price)| format!("{},{price}", 1_700_000_000 + day as i64 * DAY)).collect();
+        parse_csv(&csv.join("\n")).unwrap()
+    }
+
+    #[test]
+    fn flat_prices_liquidate_nobody() {
+        let report = simulate(&daily_path(&["100"; 8]), &scenario(), &PARAMS).unwrap();
+        assert!(report.initial_borrowed_value > 0);
+        assert_eq!(report.liquidations, 0);
+        assert_eq!((report.liquidated_value, report.seized_value), (0, 0));
+        assert_eq!((report.bad_debt, report.peak_bad_debt, report.insolvent_users), (0, 0, 0));
+        // Supplied for 80% utilization, which interest only raises
+        assert!((8_000..8_010).contains(&report.average_utilization), "{report:?}");
+        assert!(report.peak_utilization >= report.average_utilization);
+    }
+
+    #[test]
+    fn crash_leaves_bad_debt() {
+        let report = simulate(&daily_path(&["100", "100", "30", "30"]), &scenario(), &PARAMS).unwrap();
+        assert!(report.liquidations > 0);
+        // Collateral worth 30% of its value cannot cover debt worth up to 70% of it, even after liquidations
+        assert!(report.bad_debt > 0 && report.insolvent_users > 0, "{report:?}");
+        assert!(report.peak_bad_debt >= report.bad_debt);
+        assert!(report.seized_value > report.liquidated_value);
+        assert!(report.liquidated_value + report.bad_debt <= report.initial_borrowed_value * 101 / 100);
+    }
+
+    #[test]
+    fn invalid_inputs_are_rejected() {
+        assert!(simulate(&[], &scenario(), &PARAMS).is_err());
+        let path = daily_path(&["100"]);
+        assert!(simulate(&path, &scenario(), &CollateralParams { max_ltv: 90, ..PARAMS }).is_err());
+        assert!(simulate(&path, &Scenario { usage: (50, 120), ..scenario() }, &PARAMS).is_err());
+        assert!(simulate(&path, &Scenario { base_rate: 2000, ..scenario() }, &PARAMS).is_err());
+    }
+}
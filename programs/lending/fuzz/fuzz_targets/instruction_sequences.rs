@@ -0,0 +1,115 @@
+//! Drives the program with arbitrary sequences of deposits, withdrawals, collateral moves, borrows,
+//! repays, liquidations, oracle prices and clock jumps. Any panic in the program, such as an `unwrap`
+//! or an overflowing `+=`, crashes the target, and the bank accounting invariants are checked after
+//! every instruction.
+
+#![no_main]
+
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+use arbitrary::Arbitrary;
+use common::market::{Market, USERS};
+use common::*;
+use lending_client::instructions;
+use lending_client::mock_oracle::MockPriceUpdate;
+use libfuzzer_sys::fuzz_target;
+
+/// Users and banks are taken modulo their count, amounts and shares are used as is
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Deposit { user: u8, bank: u8, amount: u64 },
+    Withdraw { user: u8, bank: u8, amount: u64 },
+    DepositCollateral { user: u8, bank: u8, shares: u64 },
+    WithdrawCollateral { user: u8, bank: u8, shares: u64 },
+    Borrow { user: u8, bank: u8, amount: u64 },
+    Repay { user: u8, bank: u8, amount: u64 },
+    Liquidate { user: u8, collateral_bank: u8, borrowed_bank: u8 },
+    /// Publishes a price `age` seconds old, with an exponent from 0 down to -12 like Pyth's feeds
+    SetPrice { bank: u8, price: i64, exponent: u8, age: u8 },
+    Warp { seconds: u32 },
+    AccrueInterest { bank: u8 },
+}
+
+fn apply(market: &mut Market, op: Op) {
+    let user = |user: u8| market.users[user as usize % USERS];
+    let mint = |bank: u8| market.mints[bank as usize % market.mints.len()];
+    let token_program = anchor_spl::token::spl_token::ID;
+    match op {
+        Op::Deposit { user: index, bank, amount } => {
+            let (owner, mint) = (user(index), mint(bank));
+            // Tokens are minted on demand, so that deposits of any size reach the program
+            let balance = market.env.balance(&owner, &mint);
+            market.env.mint_to(&owner, &mint, amount.saturating_sub(balance));
+            let _ = market.env.process(
+                &[instructions::deposit(&owner, &mint, &market.env.price_update(&mint), &token_program, amount)],
+                &[owner],
+            );
+        }
+        Op::Withdraw { user: index, bank, amount } => {
+            let (owner, mint) = (user(index), mint(bank));
+            let shares = market.env.receipt_balance(&owner, &mint);
+            let mut accrued_bank = market.env.bank(&mint);
+            accrued_bank.accrue_interest(market.env.now()).unwrap();
+            let ix = instructions::withdraw(&owner, &mint, &market.env.price_update(&mint), &token_program, amount);
+            if market.env.process(&[ix], &[owner]).is_ok() {
+                let burned = shares - market.env.receipt_balance(&owner, &mint);
+                assert!(accrued_bank.deposit_amount_for(burned) >= amount, "withdrew {amount} for {burned} shares");
+            }
+        }
+        Op::DepositCollateral { user: index, bank, shares } => {
+            let (owner, mint) = (user(index), mint(bank));
+            let _ = market.env.process(&[instructions::deposit_collateral(&owner, &mint, &token_program, 0, shares)], &[owner]);
+        }
+        Op::WithdrawCollateral { user: index, bank, shares } => {
+            let (owner, mint) = (user(index), mint(bank));
+            let health_accounts = market.env.health_accounts(&owner, 0);
+            let ix = instructions::withdraw_collateral(
+                &owner,
+                &mint,
+                &market.env.price_update(&mint),
+                &token_program,
+                0,
+                shares,
+                &health_accounts,
+            );
+            if market.env.process(&[ix], &[owner]).is_ok() {
+                market.assert_healthy(index as usize % USERS);
+            }
+        }
+        Op::Borrow { user: index, bank, amount } => {
+            let (owner, mint) = (user(index), mint(bank));
+            if market.env.borrow(&owner, 0, &mint, amount).is_ok() {
+                market.assert_healthy(index as usize % USERS);
+            }
+        }
+        Op::Repay { user: index, bank, amount } => {
+            let (owner, mint) = (user(index), mint(bank));
+            let balance = market.env.balance(&owner, &mint);
+            market.env.mint_to(&owner, &mint, amount.saturating_sub(balance));
+            let _ = market.env.repay(&owner, 0, &mint, amount);
+        }
+        Op::Liquidate { user, collateral_bank, borrowed_bank } => {
+            let banks = market.mints.len();
+            let _ = market.liquidate(user as usize % USERS, collateral_bank as usize % banks, borrowed_bank as usize % banks);
+        }
+        Op::SetPrice { bank, price, exponent, age } => {
+            let mint = mint(bank);
+            let exponent = -((exponent % 13) as i32);
+            let update = MockPriceUpdate::new(feed_id(&mint), price, exponent).publish_time(market.env.now() - age as i64);
+            market.env.set_price_update(&mint, update);
+        }
+        Op::Warp { seconds } => market.env.warp(seconds as i64),
+        Op::AccrueInterest { bank } => {
+            let _ = market.env.process(&[instructions::accrue_interest(&mint(bank))], &[]);
+        }
+    }
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let mut market = Market::new();
+    for op in ops {
+        apply(&mut market, op);
+        market.assert_invariants();
+    }
+});
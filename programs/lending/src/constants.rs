@@ -1,7 +1,35 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::extension::ExtensionType;
 
-#[constant]
+// Feed ids for listing the SOL and USDC banks, also published in the IDL
 // https://pyth.network/developers/price-feed-ids#solana-stable
+#[constant]
 pub const SOL_USD_FEED_ID: &str = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
+#[constant]
 pub const USDC_USD_FEED_ID: &str = "0xeaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a";
 pub const MAXIMUM_AGE: u64 = 100; // allow price feed 100 sec old, to avoid stale price feed errors
+pub const MAX_POSITIONS: usize = 8; // banks a single user account can hold positions in
+pub const VALUE_DECIMALS: i32 = 6; // USD values are scaled by 10^6
+pub const DEFAULT_LIQUIDATION_BONUS: u64 = 5;
+pub const DEFAULT_LIQUIDATION_CLOSE_FACTOR: u64 = 50;
+pub const BASIS_POINTS: u64 = 10_000; // denominator for fees expressed in basis points
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+pub const INDEX_SCALE: u128 = 1_000_000_000_000; // interest indices start at 1.0, scaled by 10^12
+pub const CONFIG_CHANGE_DELAY: i64 = 2 * 24 * 60 * 60; // risk parameter changes wait 2 days before they can be executed
+
+// Flags for `Bank::pause_flags` and `ProtocolConfig::pause_flags`
+pub const PAUSE_DEPOSIT: u8 = 1 << 0;
+pub const PAUSE_WITHDRAW: u8 = 1 << 1;
+pub const PAUSE_BORROW: u8 = 1 << 2;
+pub const PAUSE_REPAY: u8 = 1 << 3;
+pub const PAUSE_LIQUIDATE: u8 = 1 << 4;
+pub const PAUSE_ALL: u8 = PAUSE_DEPOSIT | PAUSE_WITHDRAW | PAUSE_BORROW | PAUSE_REPAY | PAUSE_LIQUIDATE; // emergency mode
+
+// Token-2022 mint extensions a bank cannot be created for: they let a third party move or lock the treasury's tokens,
+// make the tokens non-transferable, or need extra accounts on every transfer
+pub const UNSUPPORTED_MINT_EXTENSIONS: [ExtensionType; 4] = [
+    ExtensionType::PermanentDelegate,
+    ExtensionType::NonTransferable,
+    ExtensionType::TransferHook,
+    ExtensionType::ConfidentialTransferMint,
+];
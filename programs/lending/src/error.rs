@@ -13,5 +13,63 @@ pub enum ErrorCode {
     #[msg("Attempting to borrow more than allowed.")]
     OverBorrowableAmount,
     #[msg("User is not undercollateralized.")]
-    NotUndercollateralized
+    NotUndercollateralized,
+    #[msg("Source and destination sub-accounts must differ.")]
+    SameSubAccount,
+    #[msg("Signer is not the bank authority.")]
+    Unauthorized,
+    #[msg("Invalid bank configuration.")]
+    InvalidBankConfig,
+    #[msg("A flash loan is already in progress for this bank.")]
+    FlashLoanInProgress,
+    #[msg("No flash loan is in progress for this bank.")]
+    NoFlashLoanInProgress,
+    #[msg("Flash borrow must be followed by a flash repay for the same bank.")]
+    MissingFlashRepay,
+    #[msg("Flash loans cannot be invoked through CPI.")]
+    FlashLoanCpi,
+    #[msg("Deposit exceeds the bank's deposit limit.")]
+    DepositLimitExceeded,
+    #[msg("Borrow exceeds the bank's borrow limit.")]
+    BorrowLimitExceeded,
+    #[msg("This action is paused.")]
+    ActionPaused,
+    #[msg("Invalid pause flags.")]
+    InvalidPauseFlags,
+    #[msg("Bank is not listed.")]
+    BankNotListed,
+    #[msg("Config change timelock has not expired yet.")]
+    TimelockNotExpired,
+    #[msg("User has no free position slot.")]
+    TooManyPositions,
+    #[msg("User has no position in this bank.")]
+    PositionNotFound,
+    #[msg("Missing or invalid bank and price update accounts for the user's positions.")]
+    InvalidRemainingAccounts,
+    #[msg("Oracle price is invalid.")]
+    InvalidPrice,
+    #[msg("Isolated collateral cannot be combined with other collateral.")]
+    IsolatedCollateral,
+    #[msg("Bank cannot be borrowed from in isolation mode.")]
+    NotBorrowableInIsolation,
+    #[msg("Borrow exceeds the isolation debt ceiling.")]
+    IsolationDebtCeilingExceeded,
+    #[msg("The user's isolated bank account is missing.")]
+    MissingIsolatedBank,
+    #[msg("Bank is not in the user's e-mode category.")]
+    EModeMismatch,
+    #[msg("Asset cannot be used as collateral.")]
+    CollateralDisabled,
+    #[msg("Asset cannot be borrowed.")]
+    BorrowDisabled,
+    #[msg("Mint has an unsupported Token-2022 extension.")]
+    UnsupportedMintExtension,
+    #[msg("Mint is not the native SOL mint.")]
+    NotNativeMint,
+    #[msg("Collateral and borrowed banks must differ.")]
+    SameBank,
+    #[msg("Less than the flash loan and its fee arrived in the treasury.")]
+    FlashLoanNotRepaid,
+    #[msg("Math operation overflowed.")]
+    MathOverflow,
 }
\ No newline at end of file
@@ -0,0 +1,312 @@
+use anchor_lang::prelude::*;
+use crate::state::{Bank, EModeCategory, ProtocolConfig, RiskParams};
+
+// Events about a user account carry the `User` account as `user` and its wallet as `owner`,
+// events about a wallet alone carry it as `owner`. Prices are the latest oracle price of the bank's asset
+// with its exponent, and health factors are those of the user after the instruction.
+
+#[event]
+pub struct DepositEvent {
+    pub bank: Pubkey,
+    /// Wallet that deposited and received the receipt tokens
+    pub owner: Pubkey,
+    /// Tokens credited to the bank, net of any transfer fee
+    pub amount: u64,
+    /// Deposit shares minted as receipt tokens
+    pub shares: u64,
+    pub price: i64,
+    pub price_exponent: i32,
+}
+
+#[event]
+pub struct WithdrawEvent {
+    pub bank: Pubkey,
+    /// Wallet that burned the receipt tokens and received the tokens
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Deposit shares burned
+    pub shares: u64,
+    pub price: i64,
+    pub price_exponent: i32,
+}
+
+#[event]
+pub struct BorrowEvent {
+    pub bank: Pubkey,
+    /// User account the borrow is recorded on
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Borrow shares added to the user's position
+    pub shares: u64,
+    pub price: i64,
+    pub price_exponent: i32,
+    pub health_factor: u64,
+}
+
+#[event]
+pub struct RepayEvent {
+    pub bank: Pubkey,
+    /// User account the repaid borrow is recorded on
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    /// Tokens credited against the debt, net of any transfer fee
+    pub amount: u64,
+    /// Borrow shares removed from the user's position
+    pub shares: u64,
+    pub price: i64,
+    pub price_exponent: i32,
+    pub health_factor: u64,
+}
+
+#[event]
+pub struct DepositCollateralEvent {
+    pub bank: Pubkey,
+    /// User account the collateral is locked in
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    /// Deposit shares moved from receipt tokens into the user's position
+    pub shares: u64,
+}
+
+#[event]
+pub struct WithdrawCollateralEvent {
+    pub bank: Pubkey,
+    /// User account the collateral is released from
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    /// Deposit shares moved from the user's position back into receipt tokens
+    pub shares: u64,
+    pub price: i64,
+    pub price_exponent: i32,
+    pub health_factor: u64,
+}
+
+#[event]
+pub struct SetCollateralEnabledEvent {
+    pub bank: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    /// Whether the user's deposit in the bank now counts as collateral
+    pub enabled: bool,
+}
+
+#[event]
+pub struct TransferCollateralEvent {
+    pub bank: Pubkey,
+    /// User accounts of the same owner the collateral moved between
+    pub from_user: Pubkey,
+    pub to_user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Deposit shares moved between the positions
+    pub shares: u64,
+    pub price: i64,
+    pub price_exponent: i32,
+    pub from_health_factor: u64,
+    pub to_health_factor: u64,
+}
+
+#[event]
+pub struct FlashBorrowEvent {
+    pub bank: Pubkey,
+    /// Wallet that received the loan
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct FlashRepayEvent {
+    pub bank: Pubkey,
+    /// Wallet that repaid the loan
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Fee credited to the depositors
+    pub fee: u64,
+}
+
+#[event]
+pub struct InitUserEvent {
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub sub_account: u8,
+}
+
+#[event]
+pub struct SetUserEModeEvent {
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    /// Selected e-mode category, 0 for none
+    pub emode_category: u8,
+}
+
+#[event]
+pub struct RefreshUserEvent {
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub health_factor: u64,
+}
+
+#[event]
+pub struct LiquidationEvent {
+    /// User account that was liquidated
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub liquidator: Pubkey,
+    pub collateral_bank: Pubkey,
+    pub borrowed_bank: Pubkey,
+    /// Debt repaid by the liquidator, in borrowed tokens
+    pub repaid_amount: u64,
+    pub repaid_shares: u64,
+    /// Collateral paid to the liquidator, including the bonus
+    pub seized_amount: u64,
+    pub seized_shares: u64,
+    /// Oracle prices used, with their exponents
+    pub collateral_price: i64,
+    pub collateral_price_exponent: i32,
+    pub borrowed_price: i64,
+    pub borrowed_price_exponent: i32,
+    /// Health factor of the user after the liquidation
+    pub health_factor: u64,
+}
+
+/// Emitted whenever a bank is created or its configuration changes, with the resulting configuration
+#[event]
+pub struct BankConfigUpdated {
+    pub bank: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub liquidation_threshold: u64,
+    pub liquidation_bonus: u64,
+    pub liquidation_close_factor: u64,
+    pub max_ltv: u64,
+    pub interest_rate: u64,
+    pub base_rate: u64,
+    pub oracle_feed_id: [u8; 32],
+    pub max_price_age: u64,
+    pub flash_loan_fee: u64,
+    pub deposit_limit: u64,
+    pub borrow_limit: u64,
+    pub pause_flags: u8,
+    pub listed: bool,
+    pub isolated: bool,
+    pub isolation_debt_ceiling: u64,
+    pub borrowable_in_isolation: bool,
+    pub emode_category: u8,
+    pub collateral_enabled: bool,
+    pub borrow_enabled: bool,
+}
+
+impl BankConfigUpdated {
+    pub fn new(key: Pubkey, bank: &Bank) -> Self {
+        Self {
+            bank: key,
+            authority: bank.authority,
+            pending_authority: bank.pending_authority,
+            liquidation_threshold: bank.liquidation_threshold,
+            liquidation_bonus: bank.liquidation_bonus,
+            liquidation_close_factor: bank.liquidation_close_factor,
+            max_ltv: bank.max_ltv,
+            interest_rate: bank.interest_rate,
+            base_rate: bank.base_rate,
+            oracle_feed_id: bank.oracle_feed_id,
+            max_price_age: bank.max_price_age,
+            flash_loan_fee: bank.flash_loan_fee,
+            deposit_limit: bank.deposit_limit,
+            borrow_limit: bank.borrow_limit,
+            pause_flags: bank.pause_flags,
+            listed: bank.listed,
+            isolated: bank.isolated,
+            isolation_debt_ceiling: bank.isolation_debt_ceiling,
+            borrowable_in_isolation: bank.borrowable_in_isolation,
+            emode_category: bank.emode_category,
+            collateral_enabled: bank.collateral_enabled,
+            borrow_enabled: bank.borrow_enabled,
+        }
+    }
+}
+
+#[event]
+pub struct ConfigChangeQueued {
+    pub bank: Pubkey,
+    pub proposer: Pubkey,
+    /// Timestamp after which the change can be executed
+    pub eta: i64,
+    pub params: RiskParams,
+}
+
+#[event]
+pub struct ConfigChangeCancelled {
+    pub bank: Pubkey,
+    pub proposer: Pubkey,
+}
+
+/// Emitted when an e-mode category is created or its parameters change, with the resulting parameters
+#[event]
+pub struct EModeCategoryUpdated {
+    pub emode_category: Pubkey,
+    pub id: u8,
+    pub max_ltv: u64,
+    pub liquidation_threshold: u64,
+    pub liquidation_bonus: u64,
+}
+
+impl EModeCategoryUpdated {
+    pub fn new(key: Pubkey, emode_category: &EModeCategory) -> Self {
+        Self {
+            emode_category: key,
+            id: emode_category.id,
+            max_ltv: emode_category.max_ltv,
+            liquidation_threshold: emode_category.liquidation_threshold,
+            liquidation_bonus: emode_category.liquidation_bonus,
+        }
+    }
+}
+
+#[event]
+pub struct EModeCategoryChangeQueued {
+    pub emode_category: Pubkey,
+    pub proposer: Pubkey,
+    /// Timestamp after which the change can be executed
+    pub eta: i64,
+    pub max_ltv: u64,
+    pub liquidation_threshold: u64,
+    pub liquidation_bonus: u64,
+}
+
+#[event]
+pub struct EModeCategoryChangeCancelled {
+    pub emode_category: Pubkey,
+    pub proposer: Pubkey,
+}
+
+/// Emitted whenever the protocol config is created or changes, with the resulting config
+#[event]
+pub struct ProtocolConfigUpdated {
+    pub admin: Pubkey,
+    pub guardian: Pubkey,
+    pub pause_flags: u8,
+    pub pending_admin: Pubkey,
+}
+
+impl ProtocolConfigUpdated {
+    pub fn new(config: &ProtocolConfig) -> Self {
+        Self {
+            admin: config.admin,
+            guardian: config.guardian,
+            pause_flags: config.pause_flags,
+            pending_admin: config.pending_admin,
+        }
+    }
+}
+
+/// Emitted whenever interest accrues on a bank, by the crank even when nothing accrued
+#[event]
+pub struct InterestAccrued {
+    pub bank: Pubkey,
+    /// Interest added to the bank's borrows and deposits since the last accrual
+    pub interest: u64,
+    pub total_deposits: u64,
+    pub total_borrowed: u64,
+    pub timestamp: i64,
+}
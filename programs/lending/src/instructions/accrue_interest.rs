@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use crate::events::InterestAccrued;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct AccrueInterest<'info> {
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+// Permissionless crank that brings the bank's totals, indices and utilization up to date,
+// so that rates stay fresh between user transactions
+
+pub fn process_accrue_interest(ctx: Context<AccrueInterest>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let bank = &mut ctx.accounts.bank;
+    let interest = bank.accrue_interest(now)?;
+
+    emit!(InterestAccrued {
+        bank: bank.key(),
+        interest,
+        total_deposits: bank.total_deposits,
+        total_borrowed: bank.total_borrowed,
+        timestamp: now,
+    });
+
+    Ok(())
+}
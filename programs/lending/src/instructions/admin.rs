@@ -1,11 +1,23 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use anchor_spl::token_2022::spl_token_2022::state::Mint as MintState;
 use anchor_spl::token_interface::{ Mint, TokenAccount, TokenInterface };
+use crate::constants::{BASIS_POINTS, DEFAULT_LIQUIDATION_BONUS, DEFAULT_LIQUIDATION_CLOSE_FACTOR, INDEX_SCALE, MAXIMUM_AGE, PAUSE_ALL, UNSUPPORTED_MINT_EXTENSIONS};
 use crate::state::*;
+use crate::events::{BankConfigUpdated, InitUserEvent, ProtocolConfigUpdated};
+use crate::error::ErrorCode;
+use crate::program::LendingProtocol;
 
 #[derive(Accounts)]
 pub struct InitBank<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = config.admin == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         init, 
@@ -24,11 +36,21 @@ pub struct InitBank<'info> {
         bump,
     )]
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        mint::decimals = mint.decimals,
+        mint::authority = bank,
+        payer = signer,
+        seeds = [b"receipt", mint.key().as_ref()],
+        bump,
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
     pub token_program: Interface<'info, TokenInterface>, 
     pub system_program: Program <'info, System>,
 }
 
 #[derive(Accounts)]
+#[instruction(sub_account: u8)]
 pub struct InitUser<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
@@ -36,29 +58,294 @@ pub struct InitUser<'info> {
         init,
         payer = signer, 
         space = 8 + User::INIT_SPACE,
-        seeds = [signer.key().as_ref()],
+        seeds = [signer.key().as_ref(), &[sub_account]],
         bump,
     )]
     pub user_account: Account<'info, User>,
     pub system_program: Program <'info, System>,
 }
 
-pub fn process_init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64) -> Result<()> {
+#[derive(Accounts)]
+pub struct UpdateBank<'info> {
+    pub signer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+        constraint = bank.authority == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct InitProtocolConfig<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + ProtocolConfig::INIT_SPACE,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    // Only the program's upgrade authority can create the config, so the admin key cannot be squatted
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, LendingProtocol>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(signer.key()) @ ErrorCode::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program <'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetBankPause<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct AdminUpdateBank<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = config.admin == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptBankAuthority<'info> {
+    pub signer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+        constraint = bank.pending_authority == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub bank: Account<'info, Bank>,
+}
+
+pub fn process_init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64, oracle_feed_id: [u8; 32]) -> Result<()> {
+    let extensions = mint_extensions(&ctx.accounts.mint.to_account_info())?;
+    if extensions.iter().any(|extension| UNSUPPORTED_MINT_EXTENSIONS.contains(extension)) {
+        return Err(ErrorCode::UnsupportedMintExtension.into());
+    }
+
+    // Held to the same bounds as the changes queued later through the timelock
+    let params = RiskParams {
+        liquidation_threshold,
+        liquidation_bonus: DEFAULT_LIQUIDATION_BONUS,
+        liquidation_close_factor: DEFAULT_LIQUIDATION_CLOSE_FACTOR,
+        max_ltv,
+        interest_rate: 0,
+        base_rate: 0,
+        oracle_feed_id,
+        max_price_age: MAXIMUM_AGE,
+        emode_category: 0,
+        collateral_enabled: true,
+        isolated: false,
+        isolation_debt_ceiling: 0,
+        borrowable_in_isolation: false,
+    };
+    params.validate()?;
+
     let bank = &mut ctx.accounts.bank;
     bank.mint_address = ctx.accounts.mint.key();
+    bank.mint_decimals = ctx.accounts.mint.decimals;
+    bank.has_transfer_fee = extensions.contains(&ExtensionType::TransferFeeConfig);
     bank.authority = ctx.accounts.signer.key();
-    bank.liquidation_threshold = liquidation_threshold;
-    bank.max_ltv = max_ltv;
+    bank.apply_risk_params(&params);
+    bank.borrow_index = INDEX_SCALE;
+    bank.deposit_index = INDEX_SCALE;
+    bank.last_updated = Clock::get()?.unix_timestamp;
+    bank.borrow_enabled = true;
+    bank.listed = true;
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
     Ok(())
 }
 
-pub fn process_init_user(ctx: Context<InitUser>, usdc_address: Pubkey) -> Result<()> {
+/// Token-2022 extensions of the mint, none for SPL Token mints
+fn mint_extensions(mint: &AccountInfo) -> Result<Vec<ExtensionType>> {
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<MintState>::unpack(&data)?;
+    Ok(mint.get_extension_types()?)
+}
+
+pub fn process_init_user(ctx: Context<InitUser>, sub_account: u8) -> Result<()> {
     let user = &mut ctx.accounts.user_account;
     user.owner = ctx.accounts.signer.key();
-    user.usdc_address = usdc_address;
+    user.sub_account = sub_account;
     
     let now = Clock::get()?.unix_timestamp; 
     user.last_updated = now;
 
+    emit!(InitUserEvent {
+        user: user.key(),
+        owner: user.owner,
+        sub_account,
+    });
+    Ok(())
+}
+
+pub fn process_set_flash_loan_fee(ctx: Context<UpdateBank>, flash_loan_fee: u64) -> Result<()> {
+    if flash_loan_fee > BASIS_POINTS {
+        return Err(ErrorCode::InvalidBankConfig.into());
+    }
+    ctx.accounts.bank.flash_loan_fee = flash_loan_fee;
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+pub fn process_set_bank_limits(ctx: Context<UpdateBank>, deposit_limit: u64, borrow_limit: u64) -> Result<()> {
+    let bank = &mut ctx.accounts.bank;
+    bank.deposit_limit = deposit_limit;
+    bank.borrow_limit = borrow_limit;
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+// Stopping borrows cannot make anyone liquidatable, so unlike `RiskParams::collateral_enabled` it takes effect immediately
+
+pub fn process_set_borrow_enabled(ctx: Context<UpdateBank>, borrow_enabled: bool) -> Result<()> {
+    let bank = &mut ctx.accounts.bank;
+    bank.borrow_enabled = borrow_enabled;
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+pub fn process_init_protocol_config(ctx: Context<InitProtocolConfig>, guardian: Pubkey) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    config.admin = ctx.accounts.signer.key();
+    config.guardian = guardian;
+    emit!(ProtocolConfigUpdated::new(config));
+    Ok(())
+}
+
+pub fn process_set_guardian(ctx: Context<UpdateProtocolConfig>, guardian: Pubkey) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    if config.admin != ctx.accounts.signer.key() {
+        return Err(ErrorCode::Unauthorized.into());
+    }
+    config.guardian = guardian;
+    emit!(ProtocolConfigUpdated::new(config));
     Ok(())
-}
\ No newline at end of file
+}
+
+// Proposing the default pubkey cancels a pending transfer
+pub fn process_propose_admin(ctx: Context<UpdateProtocolConfig>, admin: Pubkey) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    if config.admin != ctx.accounts.signer.key() {
+        return Err(ErrorCode::Unauthorized.into());
+    }
+    config.pending_admin = admin;
+    emit!(ProtocolConfigUpdated::new(config));
+    Ok(())
+}
+
+pub fn process_accept_admin(ctx: Context<UpdateProtocolConfig>) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    if config.pending_admin != ctx.accounts.signer.key() {
+        return Err(ErrorCode::Unauthorized.into());
+    }
+    config.admin = config.pending_admin;
+    config.pending_admin = Pubkey::default();
+    emit!(ProtocolConfigUpdated::new(config));
+    Ok(())
+}
+
+pub fn process_set_protocol_pause(ctx: Context<UpdateProtocolConfig>, pause_flags: u8) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    check_pause_update(ctx.accounts.signer.key(), config.admin, config.guardian, config.pause_flags, pause_flags)?;
+    config.pause_flags = pause_flags;
+    emit!(ProtocolConfigUpdated::new(config));
+    Ok(())
+}
+
+pub fn process_set_bank_pause(ctx: Context<SetBankPause>, pause_flags: u8) -> Result<()> {
+    let bank = &mut ctx.accounts.bank;
+    check_pause_update(ctx.accounts.signer.key(), bank.authority, ctx.accounts.config.guardian, bank.pause_flags, pause_flags)?;
+    bank.pause_flags = pause_flags;
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+pub fn process_list_bank(ctx: Context<AdminUpdateBank>) -> Result<()> {
+    ctx.accounts.bank.listed = true;
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+pub fn process_delist_bank(ctx: Context<AdminUpdateBank>) -> Result<()> {
+    ctx.accounts.bank.listed = false;
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+pub fn process_set_bank_authority(ctx: Context<AdminUpdateBank>, authority: Pubkey) -> Result<()> {
+    let bank = &mut ctx.accounts.bank;
+    bank.authority = authority;
+    bank.pending_authority = Pubkey::default();
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+// Proposing the default pubkey cancels a pending transfer
+pub fn process_propose_bank_authority(ctx: Context<UpdateBank>, authority: Pubkey) -> Result<()> {
+    ctx.accounts.bank.pending_authority = authority;
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+pub fn process_accept_bank_authority(ctx: Context<AcceptBankAuthority>) -> Result<()> {
+    let bank = &mut ctx.accounts.bank;
+    bank.authority = bank.pending_authority;
+    bank.pending_authority = Pubkey::default();
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+// The authority can set any flags, the guardian can only pause more actions
+fn check_pause_update(signer: Pubkey, authority: Pubkey, guardian: Pubkey, current_flags: u8, new_flags: u8) -> Result<()> {
+    if new_flags & !PAUSE_ALL != 0 {
+        return Err(ErrorCode::InvalidPauseFlags.into());
+    }
+    if signer == authority {
+        return Ok(());
+    }
+    if signer == guardian && new_flags & current_flags == current_flags {
+        return Ok(());
+    }
+    Err(ErrorCode::Unauthorized.into())
+}
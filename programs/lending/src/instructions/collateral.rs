@@ -0,0 +1,209 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{ self, Burn, Mint, MintTo, TokenAccount, TokenInterface };
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+use crate::risk::{check_health, get_event_price};
+use crate::constants::PAUSE_WITHDRAW;
+use crate::state::*;
+use crate::events::{DepositCollateralEvent, SetCollateralEnabledEvent, WithdrawCollateralEvent};
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+#[instruction(sub_account: u8)]
+pub struct DepositCollateral<'info> {
+    pub signer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        seeds = [b"receipt", mint.key().as_ref()],
+        bump,
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [signer.key().as_ref(), &[sub_account]],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        mut,
+        associated_token::mint = receipt_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_receipt_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(sub_account: u8)]
+pub struct WithdrawCollateral<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
+    #[account(
+        mut,
+        seeds = [b"receipt", mint.key().as_ref()],
+        bump,
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [signer.key().as_ref(), &[sub_account]],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = receipt_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_receipt_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(sub_account: u8)]
+pub struct SetCollateralEnabled<'info> {
+    pub signer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        seeds = [signer.key().as_ref(), &[sub_account]],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+// 1. Check that the collateral can be combined with the user's other collateral
+// 2. Burn the user's receipt tokens, they are now held by the User obligation
+// 3. Credit the shares to the user's position in the bank
+
+pub fn process_deposit_collateral(ctx: Context<DepositCollateral>, _sub_account: u8, shares: u64) -> Result<()> {
+    let bank_key = ctx.accounts.bank.key();
+    if !ctx.accounts.bank.collateral_enabled {
+        return Err(ErrorCode::CollateralDisabled.into());
+    }
+    let user: &mut User = &mut ctx.accounts.user_account;
+    user.check_isolation(&bank_key, &ctx.accounts.bank)?;
+
+    let burn_cpi_accounts = Burn {
+        mint: ctx.accounts.receipt_mint.to_account_info(),
+        from: ctx.accounts.user_receipt_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, burn_cpi_accounts);
+    token_interface::burn(cpi_ctx, shares)?;
+
+    user.get_or_create_position(&bank_key)?.deposit_shares += shares;
+    user.last_updated = Clock::get()?.unix_timestamp;
+
+    emit!(DepositCollateralEvent {
+        bank: bank_key,
+        user: ctx.accounts.user_account.key(),
+        owner: ctx.accounts.user_account.owner,
+        shares,
+    });
+    Ok(())
+}
+
+// 1. Remove the shares from the user's position in the bank
+// 2. Check the user's health factor without the released collateral,
+//    the remaining accounts hold a (bank, price update) pair for each of the user's positions
+// 3. Mint the shares back to the user as receipt tokens
+
+pub fn process_withdraw_collateral(ctx: Context<WithdrawCollateral>, _sub_account: u8, shares: u64) -> Result<()> {
+    ctx.accounts.bank.check_not_paused(&ctx.accounts.config, PAUSE_WITHDRAW)?;
+    let bank_key = ctx.accounts.bank.key();
+    let user: &mut User = &mut ctx.accounts.user_account;
+
+    let position = user.position_mut(&bank_key).ok_or(ErrorCode::PositionNotFound)?;
+    if shares > position.deposit_shares {
+        return Err(ErrorCode::InsufficientFunds.into());
+    }
+    position.deposit_shares -= shares;
+
+    check_health(user, ctx.remaining_accounts, &[])?;
+    user.close_empty_positions();
+    user.last_updated = Clock::get()?.unix_timestamp;
+
+    let mint_to_cpi_accounts = MintTo {
+        mint: ctx.accounts.receipt_mint.to_account_info(),
+        to: ctx.accounts.user_receipt_token_account.to_account_info(),
+        authority: ctx.accounts.bank.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let mint_key = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            mint_key.as_ref(),
+            &[ctx.bumps.bank],
+        ],
+    ];
+    let cpi_ctx = CpiContext::new(cpi_program, mint_to_cpi_accounts).with_signer(signer_seeds);
+    token_interface::mint_to(cpi_ctx, shares)?;
+
+    let price = get_event_price(&ctx.accounts.bank, &ctx.accounts.price_update)?;
+    emit!(WithdrawCollateralEvent {
+        bank: bank_key,
+        user: ctx.accounts.user_account.key(),
+        owner: ctx.accounts.user_account.owner,
+        shares,
+        price: price.price,
+        price_exponent: price.exponent,
+        health_factor: ctx.accounts.user_account.health_factor,
+    });
+    Ok(())
+}
+
+// 1. Toggle whether the user's deposit in the bank counts as collateral
+// 2. When disabling, check the user's health factor without it,
+//    the remaining accounts hold a (bank, price update) pair for each of the user's positions
+
+pub fn process_set_collateral_enabled(ctx: Context<SetCollateralEnabled>, _sub_account: u8, enabled: bool) -> Result<()> {
+    let bank_key = ctx.accounts.bank.key();
+    let user: &mut User = &mut ctx.accounts.user_account;
+
+    let position = user.position_mut(&bank_key).ok_or(ErrorCode::PositionNotFound)?;
+    position.collateral_disabled = !enabled;
+
+    if !enabled {
+        check_health(user, ctx.remaining_accounts, &[])?;
+    }
+    user.last_updated = Clock::get()?.unix_timestamp;
+
+    emit!(SetCollateralEnabledEvent {
+        bank: bank_key,
+        user: ctx.accounts.user_account.key(),
+        owner: ctx.accounts.user_account.owner,
+        enabled,
+    });
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use crate::constants::CONFIG_CHANGE_DELAY;
+use crate::state::*;
+use crate::events::{BankConfigUpdated, ConfigChangeCancelled, ConfigChangeQueued};
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct QueueConfigChange<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [mint.key().as_ref()],
+        bump,
+        constraint = bank.authority == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + ConfigChange::INIT_SPACE,
+        seeds = [b"config_change", bank.key().as_ref()],
+        bump,
+    )]
+    pub config_change: Account<'info, ConfigChange>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteConfigChange<'info> {
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        close = proposer,
+        has_one = bank,
+        has_one = proposer,
+        seeds = [b"config_change", bank.key().as_ref()],
+        bump,
+    )]
+    pub config_change: Account<'info, ConfigChange>,
+    /// CHECK: rent receiver, checked against the config change proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelConfigChange<'info> {
+    pub signer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [mint.key().as_ref()],
+        bump,
+        constraint = bank.authority == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        close = proposer,
+        has_one = bank,
+        has_one = proposer,
+        seeds = [b"config_change", bank.key().as_ref()],
+        bump,
+    )]
+    pub config_change: Account<'info, ConfigChange>,
+    /// CHECK: rent receiver, checked against the config change proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+// Risk parameter changes are queued by the bank authority and can be executed by anyone once the
+// timelock has expired, giving users time to adjust positions that the change would make liquidatable.
+
+pub fn process_queue_config_change(ctx: Context<QueueConfigChange>, params: RiskParams) -> Result<()> {
+    params.validate()?;
+
+    let config_change = &mut ctx.accounts.config_change;
+    config_change.bank = ctx.accounts.bank.key();
+    config_change.proposer = ctx.accounts.signer.key();
+    config_change.eta = Clock::get()?.unix_timestamp + CONFIG_CHANGE_DELAY;
+    config_change.params = params;
+
+    emit!(ConfigChangeQueued {
+        bank: config_change.bank,
+        proposer: config_change.proposer,
+        eta: config_change.eta,
+        params: config_change.params.clone(),
+    });
+    Ok(())
+}
+
+pub fn process_execute_config_change(ctx: Context<ExecuteConfigChange>) -> Result<()> {
+    let config_change = &ctx.accounts.config_change;
+    if Clock::get()?.unix_timestamp < config_change.eta {
+        return Err(ErrorCode::TimelockNotExpired.into());
+    }
+
+    // Interest up to now accrues at the old rates
+    let bank_key = ctx.accounts.bank.key();
+    let bank = &mut ctx.accounts.bank;
+    bank.accrue_interest_and_emit(bank_key, Clock::get()?.unix_timestamp)?;
+    bank.apply_risk_params(&config_change.params);
+
+    emit!(BankConfigUpdated::new(ctx.accounts.bank.key(), &ctx.accounts.bank));
+    Ok(())
+}
+
+pub fn process_cancel_config_change(ctx: Context<CancelConfigChange>) -> Result<()> {
+    // The queued change is closed by the account constraints
+    emit!(ConfigChangeCancelled {
+        bank: ctx.accounts.bank.key(),
+        proposer: ctx.accounts.proposer.key(),
+    });
+    Ok(())
+}
@@ -1,12 +1,21 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use anchor_spl::token_interface::{ self, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked };
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+use crate::constants::PAUSE_DEPOSIT;
+use crate::risk::get_event_price;
 use crate::state::*;
+use crate::events::DepositEvent;
 
 #[derive(Accounts)]
 pub struct Deposit<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut, 
@@ -14,6 +23,8 @@ pub struct Deposit<'info> {
         bump,
     )]  
     pub bank: Account<'info, Bank>,
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
     #[account(
         mut, 
         seeds = [b"treasury", mint.key().as_ref()],
@@ -21,32 +32,45 @@ pub struct Deposit<'info> {
     )]  
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(
-        mut, 
-        seeds = [signer.key().as_ref()],
+        mut,
+        seeds = [b"receipt", mint.key().as_ref()],
         bump,
-    )]  
-    pub user_account: Account<'info, User>,
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
     #[account( 
-        mut,
+        init_if_needed,
+        payer = signer,
         associated_token::mint = mint, 
         associated_token::authority = signer,
         associated_token::token_program = token_program,
     )]
     pub user_token_account: InterfaceAccount<'info, TokenAccount>, 
+    #[account( 
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = receipt_mint, 
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_receipt_token_account: InterfaceAccount<'info, TokenAccount>, 
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
-// 1. CPI transfer from user's token account to bank's token account
-// 2. Calculate new shares to be added to the bank
-// 3. Update user's deposited amount and total collateral value
-// 4. Update bank's total deposits and total deposit shares
-// 5. Update users health factor ?? 
+// 1. CPI transfer from user's token account to bank's token account, net of any transfer fee
+// 2. Credit the deposit and its new shares to the bank's totals, within the bank's deposit limit
+// 3. Mint the shares to the user as receipt tokens
 
 pub fn process_deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+    let bank_key = ctx.accounts.bank.key();
+    let bank = &mut ctx.accounts.bank;
+    bank.accrue_interest_and_emit(bank_key, Clock::get()?.unix_timestamp)?;
+    bank.check_listed()?;
+    bank.check_not_paused(&ctx.accounts.config, PAUSE_DEPOSIT)?;
 
     // Token transfer via CPI
+    let balance_before = ctx.accounts.bank_token_account.amount;
     let transfer_cpi_accounts = TransferChecked {
         from: ctx.accounts.user_token_account.to_account_info(),
         mint: ctx.accounts.mint.to_account_info(),
@@ -55,40 +79,47 @@ pub fn process_deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
     };
 
     let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts);
+    let cpi_ctx = CpiContext::new(cpi_program.clone(), transfer_cpi_accounts);
     let decimals = ctx.accounts.mint.decimals;
     token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
-    // Updating Bank and User Shares
-    let bank = &mut ctx.accounts.bank; // bank mutable reference
+    // Token-2022 transfer fees are withheld from the transferred tokens, only what arrived in the treasury is credited
+    let amount = if ctx.accounts.bank.has_transfer_fee {
+        ctx.accounts.bank_token_account.reload()?;
+        ctx.accounts.bank_token_account.amount - balance_before
+    } else {
+        amount
+    };
 
-    if bank.total_deposits == 0 { // If this is the first deposit 
-        bank.total_deposits = amount;  // Initialsing total deposit and total shares
-        bank.total_deposit_shares = amount;
-    }
-    
-    let deposit_ratio = amount.checked_div(bank.total_deposits).unwrap();
-    let users_shares = bank.total_deposit_shares.checked_mul(deposit_ratio).unwrap();
-    
-    let user = &mut ctx.accounts.user_account; // user mutable reference
-    
-    // Updating the user deposited amount and shares based on the mint key(either usdc or sol)
-    match ctx.accounts.mint.to_account_info().key() {
-        key if key == user.usdc_address => {
-            user.deposited_usdc += amount;
-            user.deposited_usdc_shares += users_shares;
-        },
-        _ => {
-            user.deposited_sol += amount;
-            user.deposited_sol_shares += users_shares; 
-        }
-    }
+    // Crediting the deposit at the current share price, within the bank's deposit cap
+    let bank = &mut ctx.accounts.bank; // bank mutable reference
+    let users_shares = bank.add_deposit(amount)?;
 
-    // Updating Bank deposit and shares(incrementing since more amount and shares are being added to account)
-    bank.total_deposits += amount;
-    bank.total_deposit_shares += users_shares;
+    // Minting the shares to the user as receipt tokens, signed by the bank PDA
+    let mint_to_cpi_accounts = MintTo {
+        mint: ctx.accounts.receipt_mint.to_account_info(),
+        to: ctx.accounts.user_receipt_token_account.to_account_info(),
+        authority: bank.to_account_info(),
+    };
+    let mint_key = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            mint_key.as_ref(),
+            &[ctx.bumps.bank],
+        ],
+    ];
+    let cpi_ctx = CpiContext::new(cpi_program, mint_to_cpi_accounts).with_signer(signer_seeds);
+    token_interface::mint_to(cpi_ctx, users_shares)?;
 
-    user.last_updated = Clock::get()?.unix_timestamp;
+    let price = get_event_price(bank, &ctx.accounts.price_update)?;
+    emit!(DepositEvent {
+        bank: bank_key,
+        owner: ctx.accounts.signer.key(),
+        amount,
+        shares: users_shares,
+        price: price.price,
+        price_exponent: price.exponent,
+    });
 
     Ok(())
-}
\ No newline at end of file
+}
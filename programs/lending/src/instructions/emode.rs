@@ -0,0 +1,206 @@
+use anchor_lang::prelude::*;
+use crate::risk::{calculate_user_values, check_emode_borrows, load_emode, load_priced_banks, HEALTHY_HEALTH_FACTOR};
+use crate::constants::CONFIG_CHANGE_DELAY;
+use crate::state::*;
+use crate::events::{EModeCategoryChangeCancelled, EModeCategoryChangeQueued, EModeCategoryUpdated, SetUserEModeEvent};
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+#[instruction(id: u8)]
+pub struct InitEModeCategory<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = config.admin == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + EModeCategory::INIT_SPACE,
+        seeds = [b"emode".as_ref(), &[id]],
+        bump,
+    )]
+    pub emode_category: Account<'info, EModeCategory>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct QueueEModeCategoryChange<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = config.admin == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"emode".as_ref(), &[emode_category.id]],
+        bump,
+    )]
+    pub emode_category: Account<'info, EModeCategory>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + EModeCategoryChange::INIT_SPACE,
+        seeds = [b"config_change", emode_category.key().as_ref()],
+        bump,
+    )]
+    pub emode_category_change: Account<'info, EModeCategoryChange>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteEModeCategoryChange<'info> {
+    #[account(
+        mut,
+        seeds = [b"emode".as_ref(), &[emode_category.id]],
+        bump,
+    )]
+    pub emode_category: Account<'info, EModeCategory>,
+    #[account(
+        mut,
+        close = proposer,
+        has_one = emode_category,
+        has_one = proposer,
+        seeds = [b"config_change", emode_category.key().as_ref()],
+        bump,
+    )]
+    pub emode_category_change: Account<'info, EModeCategoryChange>,
+    /// CHECK: rent receiver, checked against the change proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelEModeCategoryChange<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = config.admin == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"emode".as_ref(), &[emode_category.id]],
+        bump,
+    )]
+    pub emode_category: Account<'info, EModeCategory>,
+    #[account(
+        mut,
+        close = proposer,
+        has_one = emode_category,
+        has_one = proposer,
+        seeds = [b"config_change", emode_category.key().as_ref()],
+        bump,
+    )]
+    pub emode_category_change: Account<'info, EModeCategoryChange>,
+    /// CHECK: rent receiver, checked against the change proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(sub_account: u8)]
+pub struct SetUserEMode<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [signer.key().as_ref(), &[sub_account]],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+// E-mode categories are managed by the protocol admin. Banks in a category can be borrowed against each other
+// with the category's LTV, liquidation threshold and bonus by users who select it. Like the risk parameters of
+// a bank, the parameters of an existing category and the category of a bank (see `RiskParams`) only change
+// through the timelock.
+
+pub fn process_init_emode_category(ctx: Context<InitEModeCategory>, id: u8, max_ltv: u64, liquidation_threshold: u64, liquidation_bonus: u64) -> Result<()> {
+    let emode_category = &mut ctx.accounts.emode_category;
+    emode_category.id = id;
+    emode_category.max_ltv = max_ltv;
+    emode_category.liquidation_threshold = liquidation_threshold;
+    emode_category.liquidation_bonus = liquidation_bonus;
+    emode_category.validate()?;
+    emit!(EModeCategoryUpdated::new(ctx.accounts.emode_category.key(), &ctx.accounts.emode_category));
+    Ok(())
+}
+
+pub fn process_queue_emode_category_change(ctx: Context<QueueEModeCategoryChange>, max_ltv: u64, liquidation_threshold: u64, liquidation_bonus: u64) -> Result<()> {
+    EModeCategory { id: ctx.accounts.emode_category.id, max_ltv, liquidation_threshold, liquidation_bonus }.validate()?;
+
+    let change = &mut ctx.accounts.emode_category_change;
+    change.emode_category = ctx.accounts.emode_category.key();
+    change.proposer = ctx.accounts.signer.key();
+    change.eta = Clock::get()?.unix_timestamp + CONFIG_CHANGE_DELAY;
+    change.max_ltv = max_ltv;
+    change.liquidation_threshold = liquidation_threshold;
+    change.liquidation_bonus = liquidation_bonus;
+
+    emit!(EModeCategoryChangeQueued {
+        emode_category: change.emode_category,
+        proposer: change.proposer,
+        eta: change.eta,
+        max_ltv,
+        liquidation_threshold,
+        liquidation_bonus,
+    });
+    Ok(())
+}
+
+pub fn process_execute_emode_category_change(ctx: Context<ExecuteEModeCategoryChange>) -> Result<()> {
+    let change = &ctx.accounts.emode_category_change;
+    if Clock::get()?.unix_timestamp < change.eta {
+        return Err(ErrorCode::TimelockNotExpired.into());
+    }
+
+    let emode_category = &mut ctx.accounts.emode_category;
+    emode_category.max_ltv = change.max_ltv;
+    emode_category.liquidation_threshold = change.liquidation_threshold;
+    emode_category.liquidation_bonus = change.liquidation_bonus;
+
+    emit!(EModeCategoryUpdated::new(ctx.accounts.emode_category.key(), &ctx.accounts.emode_category));
+    Ok(())
+}
+
+pub fn process_cancel_emode_category_change(ctx: Context<CancelEModeCategoryChange>) -> Result<()> {
+    // The queued change is closed by the account constraints
+    emit!(EModeCategoryChangeCancelled {
+        emode_category: ctx.accounts.emode_category.key(),
+        proposer: ctx.accounts.proposer.key(),
+    });
+    Ok(())
+}
+
+// 1. Select the category, the remaining accounts hold the category account when one is selected,
+//    followed by a (bank, price update) pair for each of the user's positions if the user has borrows
+// 2. Check that every borrow of the user is in a bank of the category
+// 3. Check the user's health factor with the category's parameters
+
+pub fn process_set_user_emode(ctx: Context<SetUserEMode>, _sub_account: u8, emode_category: u8) -> Result<()> {
+    let user: &mut User = &mut ctx.accounts.user_account;
+    user.emode_category = emode_category;
+
+    let (emode, accounts) = load_emode(user, ctx.remaining_accounts)?;
+    if user.has_borrows() {
+        let priced_banks = load_priced_banks(user, accounts, &[])?;
+        check_emode_borrows(user, &priced_banks, emode.as_ref())?;
+        user.health_factor = calculate_user_values(user, &priced_banks, emode.as_ref()).health_factor();
+        if user.health_factor < HEALTHY_HEALTH_FACTOR {
+            return Err(ErrorCode::UnderCollateralized.into());
+        }
+    }
+    user.last_updated = Clock::get()?.unix_timestamp;
+
+    emit!(SetUserEModeEvent {
+        user: ctx.accounts.user_account.key(),
+        owner: ctx.accounts.user_account.owner,
+        emode_category,
+    });
+    Ok(())
+}
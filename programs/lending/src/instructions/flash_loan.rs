@@ -0,0 +1,226 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use anchor_lang::solana_program::instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT};
+use anchor_lang::solana_program::sysvar::instructions::{self as instructions_sysvar, load_current_index_checked, load_instruction_at_checked};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::token_2022::spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use anchor_spl::token_2022::spl_token_2022::state::Mint as MintState;
+use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use crate::constants::{BASIS_POINTS, PAUSE_BORROW};
+use crate::state::*;
+use crate::events::{FlashBorrowEvent, FlashRepayEvent};
+use crate::error::ErrorCode;
+
+/// Position of the `bank` account in the `FlashRepay` accounts
+const FLASH_REPAY_BANK_INDEX: usize = 2;
+
+#[derive(Accounts)]
+pub struct FlashBorrow<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: address is checked against the instructions sysvar id
+    #[account(address = instructions_sysvar::ID)]
+    pub instruction_sysvar: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    pub signer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// 1. Check that no flash loan is outstanding and that we are not called through CPI
+// 2. Look ahead in the transaction for a flash repay against the same bank
+// 3. CPI transfer from bank's token account to user's token account
+// 4. Record the outstanding flash loan on the bank
+
+pub fn process_flash_borrow(ctx: Context<FlashBorrow>, amount: u64) -> Result<()> {
+    // Inner instructions are not visible in the instructions sysvar, so the repay lookup only works at the top level
+    if get_stack_height() != TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Err(ErrorCode::FlashLoanCpi.into());
+    }
+
+    let bank_key = ctx.accounts.bank.key();
+    let bank = &mut ctx.accounts.bank;
+    bank.check_listed()?;
+    bank.check_not_paused(&ctx.accounts.config, PAUSE_BORROW)?;
+    bank.accrue_interest_and_emit(bank_key, Clock::get()?.unix_timestamp)?;
+    if bank.flash_loan_amount != 0 {
+        return Err(ErrorCode::FlashLoanInProgress.into());
+    }
+
+    // Search the rest of the transaction for a matching flash repay
+    let instruction_sysvar = ctx.accounts.instruction_sysvar.to_account_info();
+    let current_index = load_current_index_checked(&instruction_sysvar)? as usize;
+    let mut index = current_index + 1;
+    loop {
+        let instruction = match load_instruction_at_checked(index, &instruction_sysvar) {
+            Ok(instruction) => instruction,
+            Err(_) => return Err(ErrorCode::MissingFlashRepay.into()),
+        };
+        if instruction.program_id == crate::ID
+            && instruction.data.get(..8) == Some(&crate::instruction::FlashRepay::DISCRIMINATOR[..])
+            && instruction.accounts.get(FLASH_REPAY_BANK_INDEX).map(|meta| meta.pubkey) == Some(bank_key)
+        {
+            break;
+        }
+        index += 1;
+    }
+
+    let transfer_cpi_accounts = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        to: ctx.accounts.user_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+    };
+
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let mint_key = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            mint_key.as_ref(),
+            &[ctx.bumps.bank_token_account],
+        ],
+    ];
+    let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+    let decimals = ctx.accounts.mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+
+    bank.flash_loan_amount = amount;
+
+    emit!(FlashBorrowEvent {
+        bank: bank_key,
+        owner: ctx.accounts.signer.key(),
+        amount,
+    });
+    Ok(())
+}
+
+// 1. Calculate the flash loan fee, rounded up in favour of the bank
+// 2. CPI transfer of the loan and fee from user's token account to bank's token account,
+//    grossed up by the mint's transfer fee so that both arrive in the treasury
+// 3. Credit what arrived beyond the loan to depositors and clear the outstanding flash loan
+
+pub fn process_flash_repay(ctx: Context<FlashRepay>) -> Result<()> {
+    if get_stack_height() != TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Err(ErrorCode::FlashLoanCpi.into());
+    }
+
+    let bank = &mut ctx.accounts.bank;
+    if bank.flash_loan_amount == 0 {
+        return Err(ErrorCode::NoFlashLoanInProgress.into());
+    }
+
+    let fee = (bank.flash_loan_amount as u128 * bank.flash_loan_fee as u128).div_ceil(BASIS_POINTS as u128) as u64;
+    let repay_amount = bank.flash_loan_amount + fee;
+    let transfer_amount = if bank.has_transfer_fee {
+        amount_before_transfer_fee(&ctx.accounts.mint.to_account_info(), repay_amount)?
+    } else {
+        repay_amount
+    };
+
+    let balance_before = ctx.accounts.bank_token_account.amount;
+
+    let transfer_cpi_accounts = TransferChecked {
+        from: ctx.accounts.user_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        to: ctx.accounts.bank_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+    };
+
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts);
+    let decimals = ctx.accounts.mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, transfer_amount, decimals)?;
+
+    // Token-2022 transfer fees are withheld from the transferred tokens, only what arrived in the treasury is credited
+    let received = if bank.has_transfer_fee {
+        ctx.accounts.bank_token_account.reload()?;
+        ctx.accounts.bank_token_account.amount - balance_before
+    } else {
+        repay_amount
+    };
+    if received < repay_amount {
+        return Err(ErrorCode::FlashLoanNotRepaid.into());
+    }
+
+    // The fee stays in the treasury and raises the value of every deposit share
+    let amount = bank.flash_loan_amount;
+    let fee = received - amount;
+    bank.total_deposits = bank.total_deposits.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+    bank.flash_loan_amount = 0;
+
+    emit!(FlashRepayEvent {
+        bank: bank.key(),
+        owner: ctx.accounts.signer.key(),
+        amount,
+        fee,
+    });
+    Ok(())
+}
+
+/// Amount to transfer for `amount` to arrive after the mint's current Token-2022 transfer fee
+fn amount_before_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<MintState>::unpack(&data)?;
+    let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(amount);
+    };
+    let fee = transfer_fee_config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    Ok(amount + fee)
+}
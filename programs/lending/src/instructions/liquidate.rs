@@ -50,16 +50,21 @@ In summary, liquidation in DeFi is a crucial mechanism to maintain the health an
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
-use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2};
-use crate::constants::{MAXIMUM_AGE, SOL_USD_FEED_ID, USDC_USD_FEED_ID};
+use crate::constants::PAUSE_LIQUIDATE;
+use crate::risk::{calculate_liquidation, calculate_user_values, load_emode, load_priced_banks, HEALTHY_HEALTH_FACTOR};
 use crate::state::*;
+use crate::events::LiquidationEvent;
 use crate::error::ErrorCode;
 
 #[derive(Accounts)]
 pub struct Liquidate<'info> {
     #[account(mut)]
     pub liquidator: Signer<'info>,
-    pub price_update: Account<'info, PriceUpdateV2>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
     pub collateral_mint: InterfaceAccount<'info, Mint>,
     pub borrowed_mint: InterfaceAccount<'info, Mint>,
     #[account(
@@ -88,7 +93,7 @@ pub struct Liquidate<'info> {
     pub borrowed_bank_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut, 
-        seeds = [liquidator.key().as_ref()],
+        seeds = [user_account.owner.as_ref(), &[user_account.sub_account]],
         bump,
     )]  
     pub user_account: Account<'info, User>,
@@ -116,42 +121,69 @@ pub struct Liquidate<'info> {
 
 // Core logic of liquidation
 pub fn process_liquidate(ctx: Context<Liquidate>) -> Result<()> {
-    let collateral_bank = &mut ctx.accounts.collateral_bank;
-    let user = &mut ctx.accounts.user_account;
+    let collateral_bank_key = ctx.accounts.collateral_bank.key();
+    let borrowed_bank_key = ctx.accounts.borrowed_bank.key();
+    // Both sides would deserialize the same account and the second write would win
+    if collateral_bank_key == borrowed_bank_key {
+        return Err(ErrorCode::SameBank.into());
+    }
+    let collateral_bank: &mut Bank = &mut ctx.accounts.collateral_bank;
+    let borrowed_bank: &mut Bank = &mut ctx.accounts.borrowed_bank;
+    collateral_bank.check_not_paused(&ctx.accounts.config, PAUSE_LIQUIDATE)?;
+    borrowed_bank.check_not_paused(&ctx.accounts.config, PAUSE_LIQUIDATE)?;
+    let now = Clock::get()?.unix_timestamp;
+    collateral_bank.accrue_interest_and_emit(collateral_bank_key, now)?;
+    borrowed_bank.accrue_interest_and_emit(borrowed_bank_key, now)?;
+    let user: &mut User = &mut ctx.accounts.user_account;
 
     /*
     Oracles are services that provide external data to a blockchain network. Blockchains are siloed environments that do not inherently know the outside world. Oracles solve this limitation by offering a decentralized way to get various types of data onchain, such as: Results of sporting events.
     1.Retrieve Prices from Oracles
+    The remaining accounts hold the user's e-mode category if one is selected,
+    followed by a (bank, price update) pair for each of the user's positions, in position order.
     */
-    let price_update = &mut ctx.accounts.price_update;
-    // Retrieve price feed ids from sol and isdc
-    let sol_feed_id = get_feed_id_from_hex(SOL_USD_FEED_ID)?;
-    let usdc_feed_id = get_feed_id_from_hex(USDC_USD_FEED_ID)?;
-    // fetching latest prices from sol and usdc using price_update account
-    let sol_price =
-        price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &sol_feed_id)?;
-    let usdc_price =
-        price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &usdc_feed_id)?;
-
-    // 2. Calculate total collateral and total borrowed
-    let total_collateral = (sol_price.price as u64 * user.deposited_sol)
-        + (usdc_price.price as u64 * user.deposited_usdc);
-    let total_borrowed = (sol_price.price as u64 * user.borrowed_sol)
-        + (usdc_price.price as u64 * user.borrowed_usdc);
-
-    // 3. Calculate Health Factor of user's account. If it is >=1, the user is not undercollaterised,liquidation cant proceed
-    let health_factor = (total_collateral * collateral_bank.liquidation_threshold) / total_borrowed;
-    if health_factor >= 1 {
+    let (emode, accounts) = load_emode(user, ctx.remaining_accounts)?;
+    let mut priced_banks = load_priced_banks(
+        user,
+        accounts,
+        &[(collateral_bank_key, &*collateral_bank), (borrowed_bank_key, &*borrowed_bank)],
+    )?;
+    let price_of = |key: Pubkey| priced_banks.iter().find(|priced_bank| priced_bank.key == key).map(|priced_bank| priced_bank.price);
+    let collateral_price = price_of(collateral_bank_key).ok_or(ErrorCode::PositionNotFound)?;
+    let borrowed_price = price_of(borrowed_bank_key).ok_or(ErrorCode::PositionNotFound)?;
+
+    // 2. Calculate total collateral and total borrowed, and the health factor of user's account.
+    // If it is at least 100%, the user is not undercollaterised, liquidation cant proceed
+    let values = calculate_user_values(user, &priced_banks, emode.as_ref());
+    if values.health_factor() >= HEALTHY_HEALTH_FACTOR {
         return Err(ErrorCode::NotUndercollateralized.into());
     }
 
-    //4. Determine liquidation amount
-    let liquidation_amount = total_borrowed * collateral_bank.liquidation_close_factor;
+    //3. Determine liquidation amount
+    let borrowed_shares = user.position(&borrowed_bank_key).map_or(0, |position| position.borrow_shares);
+    let deposit_shares = match user.position(&collateral_bank_key) {
+        Some(position) if position.is_seizable() => position.deposit_shares,
+        _ => return Err(ErrorCode::CollateralDisabled.into()),
+    };
+    let borrowed = borrowed_bank.borrow_amount_for(borrowed_shares);
+    let collateral = collateral_bank.deposit_amount_for(deposit_shares);
+
+    let (liquidation_amount, seized_amount) = calculate_liquidation(
+        borrowed,
+        collateral,
+        borrowed_bank,
+        &borrowed_price,
+        collateral_bank,
+        &collateral_price,
+        emode.as_ref(),
+    );
     /*
-    Calculate the amount to be liquidated based on the total borrowed and the bank's liquidation close factor.
+    Calculate the amount to be liquidated based on the user's debt in the borrowed bank and the bank's liquidation close factor,
+    and the collateral paid to the liquidator for it, worth the liquidated amount plus the liquidation bonus.
     */
 
-    // 5. Transfer borrowed Tokens to Bank
+    // 4. Transfer borrowed Tokens to Bank
+    let balance_before = ctx.accounts.borrowed_bank_token_account.amount;
     let transfer_to_bank = TransferChecked {
         from: ctx
             .accounts
@@ -166,10 +198,40 @@ pub fn process_liquidate(ctx: Context<Liquidate>) -> Result<()> {
     let decimals = ctx.accounts.borrowed_mint.decimals;
     token_interface::transfer_checked(cpi_ctx_to_bank, liquidation_amount, decimals)?;
 
-    // 6. Transfer collateral  and bonus to liquidator
-    let liquidation_bonus =
-        (liquidation_amount * collateral_bank.liquidation_bonus) + liquidation_amount; // Calculating the bonus for the liquidator
+    // Token-2022 transfer fees are withheld from the transferred tokens, only what arrived in the treasury repays the debt
+    // and the seized collateral shrinks with it, so that the liquidator bears the fee
+    let (liquidation_amount, seized_amount) = if borrowed_bank.has_transfer_fee {
+        ctx.accounts.borrowed_bank_token_account.reload()?;
+        let received = ctx.accounts.borrowed_bank_token_account.amount - balance_before;
+        if received < liquidation_amount {
+            (received, (seized_amount as u128 * received as u128 / liquidation_amount as u128) as u64)
+        } else {
+            (liquidation_amount, seized_amount)
+        }
+    } else {
+        (liquidation_amount, seized_amount)
+    };
 
+    // 5. Update the user's position and the banks
+    let (repaid_shares, seized_shares) = user.apply_liquidation(
+        &borrowed_bank_key,
+        borrowed_bank,
+        liquidation_amount,
+        &collateral_bank_key,
+        collateral_bank,
+        seized_amount,
+    )?;
+    for priced_bank in priced_banks.iter_mut() {
+        if priced_bank.key == collateral_bank_key {
+            priced_bank.bank = Bank::clone(collateral_bank);
+        } else if priced_bank.key == borrowed_bank_key {
+            priced_bank.bank = Bank::clone(borrowed_bank);
+        }
+    }
+    user.health_factor = calculate_user_values(user, &priced_banks, emode.as_ref()).health_factor();
+    user.last_updated = now;
+
+    // 6. Transfer collateral and bonus to liquidator
     let transfer_to_liquidator = TransferChecked {
         from: ctx.accounts.collateral_bank_token_account.to_account_info(),
         mint: ctx.accounts.collateral_mint.to_account_info(),
@@ -190,9 +252,26 @@ pub fn process_liquidate(ctx: Context<Liquidate>) -> Result<()> {
     let collateral_decimals = ctx.accounts.collateral_mint.decimals;
     token_interface::transfer_checked( // Perform the token transfer
         cpi_ctx_to_liquidator,
-        liquidation_bonus,
+        seized_amount,
         collateral_decimals,
     )?;
+
+    emit!(LiquidationEvent {
+        user: ctx.accounts.user_account.key(),
+        owner: ctx.accounts.user_account.owner,
+        liquidator: ctx.accounts.liquidator.key(),
+        collateral_bank: collateral_bank_key,
+        borrowed_bank: borrowed_bank_key,
+        repaid_amount: liquidation_amount,
+        repaid_shares,
+        seized_amount,
+        seized_shares,
+        collateral_price: collateral_price.price,
+        collateral_price_exponent: collateral_price.exponent,
+        borrowed_price: borrowed_price.price,
+        borrowed_price_exponent: borrowed_price.exponent,
+        health_factor: ctx.accounts.user_account.health_factor,
+    });
     
     Ok(())
-}
+}
\ No newline at end of file
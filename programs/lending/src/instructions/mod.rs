@@ -9,4 +9,22 @@ pub mod withdraw;
 pub use repay::*;
 pub mod repay;
 pub use liquidate::*;
-pub mod liquidate;
\ No newline at end of file
+pub mod liquidate;
+pub use transfer_collateral::*;
+pub mod transfer_collateral;
+pub use collateral::*;
+pub mod collateral;
+pub use flash_loan::*;
+pub mod flash_loan;
+pub use config_change::*;
+pub mod config_change;
+pub use emode::*;
+pub mod emode;
+pub use native_sol::*;
+pub mod native_sol;
+pub use accrue_interest::*;
+pub mod accrue_interest;
+pub use refresh_user::*;
+pub mod refresh_user;
+pub use views::*;
+pub mod views;
@@ -0,0 +1,409 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token_interface::{ self, CloseAccount, Mint, SyncNative, TokenAccount, TokenInterface };
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+use crate::instructions::*;
+use crate::state::*;
+use crate::error::ErrorCode;
+
+// Variants of deposit, withdraw, borrow and repay for the SOL bank that work with plain lamports.
+// They take the accounts of their token counterparts, except that the signer's token account is an
+// ephemeral wSOL account (PDA seeds `["wsol", signer]`) created for the instruction. Lamports are
+// wrapped into it before the instruction runs, and it is closed afterwards, unwrapping its balance
+// (and rent) back to the signer, so that a wSOL balance the signer holds elsewhere is left alone.
+
+#[derive(Accounts)]
+pub struct DepositSol<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(address = native_mint::ID @ ErrorCode::NotNativeMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"receipt", mint.key().as_ref()],
+        bump,
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"wsol", signer.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub wsol_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = receipt_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_receipt_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSol<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(address = native_mint::ID @ ErrorCode::NotNativeMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"receipt", mint.key().as_ref()],
+        bump,
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"wsol", signer.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub wsol_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = receipt_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_receipt_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(sub_account: u8)]
+pub struct BorrowSol<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(address = native_mint::ID @ ErrorCode::NotNativeMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [signer.key().as_ref(), &[sub_account]],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"wsol", signer.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub wsol_account: InterfaceAccount<'info, TokenAccount>,
+    /// Bank of the user's isolated collateral, required when the user is in isolation mode
+    #[account(mut)]
+    pub isolated_bank: Option<Account<'info, Bank>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(sub_account: u8)]
+pub struct RepaySol<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(address = native_mint::ID @ ErrorCode::NotNativeMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [signer.key().as_ref(), &[sub_account]],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"wsol", signer.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub wsol_account: InterfaceAccount<'info, TokenAccount>,
+    /// Bank of the user's isolated collateral, required when the user is in isolation mode
+    #[account(mut)]
+    pub isolated_bank: Option<Account<'info, Bank>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+/// The ephemeral wSOL account and the programs needed to wrap and unwrap it
+struct NativeSol<'info> {
+    signer: AccountInfo<'info>,
+    wsol_account: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+}
+
+impl<'info> NativeSol<'info> {
+    /// Moves `lamports` from the signer into the wSOL account and syncs its token balance
+    fn wrap(&self, lamports: u64) -> Result<()> {
+        let transfer_cpi_accounts = Transfer {
+            from: self.signer.clone(),
+            to: self.wsol_account.clone(),
+        };
+        system_program::transfer(CpiContext::new(self.system_program.clone(), transfer_cpi_accounts), lamports)?;
+
+        let sync_cpi_accounts = SyncNative {
+            account: self.wsol_account.clone(),
+        };
+        token_interface::sync_native(CpiContext::new(self.token_program.clone(), sync_cpi_accounts))
+    }
+
+    /// Closes the wSOL account, returning its balance and rent to the signer as lamports
+    fn unwrap(&self) -> Result<()> {
+        let close_cpi_accounts = CloseAccount {
+            account: self.wsol_account.clone(),
+            destination: self.signer.clone(),
+            authority: self.signer.clone(),
+        };
+        token_interface::close_account(CpiContext::new(self.token_program.clone(), close_cpi_accounts))
+    }
+}
+
+// The token counterparts run on a copy of the accounts with the wSOL account as the signer's token account,
+// the program accounts they change are copied back to be written when the instruction exits.
+
+pub fn process_deposit_sol<'info>(ctx: Context<'_, '_, '_, 'info, DepositSol<'info>>, amount: u64) -> Result<()> {
+    let accounts = ctx.accounts;
+    let native_sol = NativeSol {
+        signer: accounts.signer.to_account_info(),
+        wsol_account: accounts.wsol_account.to_account_info(),
+        token_program: accounts.token_program.to_account_info(),
+        system_program: accounts.system_program.to_account_info(),
+    };
+    native_sol.wrap(amount)?;
+
+    let mut deposit = Deposit {
+        signer: accounts.signer.clone(),
+        config: accounts.config.clone(),
+        mint: accounts.mint.clone(),
+        bank: accounts.bank.clone(),
+        price_update: accounts.price_update.clone(),
+        bank_token_account: accounts.bank_token_account.clone(),
+        receipt_mint: accounts.receipt_mint.clone(),
+        user_token_account: accounts.wsol_account.clone(),
+        user_receipt_token_account: accounts.user_receipt_token_account.clone(),
+        token_program: accounts.token_program.clone(),
+        associated_token_program: accounts.associated_token_program.clone(),
+        system_program: accounts.system_program.clone(),
+    };
+    let bumps = DepositBumps {
+        config: ctx.bumps.config,
+        bank: ctx.bumps.bank,
+        bank_token_account: ctx.bumps.bank_token_account,
+        receipt_mint: ctx.bumps.receipt_mint,
+    };
+    process_deposit(Context::new(ctx.program_id, &mut deposit, ctx.remaining_accounts, bumps), amount)?;
+    accounts.bank.set_inner(deposit.bank.into_inner());
+
+    native_sol.unwrap()
+}
+
+pub fn process_withdraw_sol<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawSol<'info>>, amount: u64) -> Result<()> {
+    let accounts = ctx.accounts;
+    let native_sol = NativeSol {
+        signer: accounts.signer.to_account_info(),
+        wsol_account: accounts.wsol_account.to_account_info(),
+        token_program: accounts.token_program.to_account_info(),
+        system_program: accounts.system_program.to_account_info(),
+    };
+
+    let mut withdraw = Withdraw {
+        signer: accounts.signer.clone(),
+        config: accounts.config.clone(),
+        mint: accounts.mint.clone(),
+        bank: accounts.bank.clone(),
+        price_update: accounts.price_update.clone(),
+        bank_token_account: accounts.bank_token_account.clone(),
+        receipt_mint: accounts.receipt_mint.clone(),
+        user_token_account: accounts.wsol_account.clone(),
+        user_receipt_token_account: accounts.user_receipt_token_account.clone(),
+        token_program: accounts.token_program.clone(),
+        associated_token_program: accounts.associated_token_program.clone(),
+        system_program: accounts.system_program.clone(),
+    };
+    let bumps = WithdrawBumps {
+        config: ctx.bumps.config,
+        bank: ctx.bumps.bank,
+        bank_token_account: ctx.bumps.bank_token_account,
+        receipt_mint: ctx.bumps.receipt_mint,
+    };
+    process_withdraw(Context::new(ctx.program_id, &mut withdraw, ctx.remaining_accounts, bumps), amount)?;
+    accounts.bank.set_inner(withdraw.bank.into_inner());
+
+    native_sol.unwrap()
+}
+
+pub fn process_borrow_sol<'info>(ctx: Context<'_, '_, '_, 'info, BorrowSol<'info>>, sub_account: u8, amount: u64) -> Result<()> {
+    let accounts = ctx.accounts;
+    let native_sol = NativeSol {
+        signer: accounts.signer.to_account_info(),
+        wsol_account: accounts.wsol_account.to_account_info(),
+        token_program: accounts.token_program.to_account_info(),
+        system_program: accounts.system_program.to_account_info(),
+    };
+
+    let mut borrow = Borrow {
+        signer: accounts.signer.clone(),
+        config: accounts.config.clone(),
+        mint: accounts.mint.clone(),
+        bank: accounts.bank.clone(),
+        price_update: accounts.price_update.clone(),
+        bank_token_account: accounts.bank_token_account.clone(),
+        user_account: accounts.user_account.clone(),
+        user_token_account: accounts.wsol_account.clone(),
+        isolated_bank: accounts.isolated_bank.clone(),
+        token_program: accounts.token_program.clone(),
+        associated_token_program: accounts.associated_token_program.clone(),
+        system_program: accounts.system_program.clone(),
+    };
+    let bumps = BorrowBumps {
+        config: ctx.bumps.config,
+        bank: ctx.bumps.bank,
+        bank_token_account: ctx.bumps.bank_token_account,
+        user_account: ctx.bumps.user_account,
+    };
+    process_borrow(Context::new(ctx.program_id, &mut borrow, ctx.remaining_accounts, bumps), sub_account, amount)?;
+    accounts.bank.set_inner(borrow.bank.into_inner());
+    accounts.user_account.set_inner(borrow.user_account.into_inner());
+    if let (Some(isolated_bank), Some(updated)) = (accounts.isolated_bank.as_mut(), borrow.isolated_bank) {
+        isolated_bank.set_inner(updated.into_inner());
+    }
+
+    native_sol.unwrap()
+}
+
+pub fn process_repay_sol<'info>(ctx: Context<'_, '_, '_, 'info, RepaySol<'info>>, sub_account: u8, amount: u64) -> Result<()> {
+    let accounts = ctx.accounts;
+    let native_sol = NativeSol {
+        signer: accounts.signer.to_account_info(),
+        wsol_account: accounts.wsol_account.to_account_info(),
+        token_program: accounts.token_program.to_account_info(),
+        system_program: accounts.system_program.to_account_info(),
+    };
+    native_sol.wrap(amount)?;
+
+    let mut repay = Repay {
+        signer: accounts.signer.clone(),
+        config: accounts.config.clone(),
+        mint: accounts.mint.clone(),
+        bank: accounts.bank.clone(),
+        price_update: accounts.price_update.clone(),
+        bank_token_account: accounts.bank_token_account.clone(),
+        user_account: accounts.user_account.clone(),
+        user_token_account: accounts.wsol_account.clone(),
+        isolated_bank: accounts.isolated_bank.clone(),
+        token_program: accounts.token_program.clone(),
+        associated_token_program: accounts.associated_token_program.clone(),
+        system_program: accounts.system_program.clone(),
+    };
+    let bumps = RepayBumps {
+        config: ctx.bumps.config,
+        bank: ctx.bumps.bank,
+        bank_token_account: ctx.bumps.bank_token_account,
+        user_account: ctx.bumps.user_account,
+    };
+    process_repay(Context::new(ctx.program_id, &mut repay, ctx.remaining_accounts, bumps), sub_account, amount)?;
+    accounts.bank.set_inner(repay.bank.into_inner());
+    accounts.user_account.set_inner(repay.user_account.into_inner());
+    if let (Some(isolated_bank), Some(updated)) = (accounts.isolated_bank.as_mut(), repay.isolated_bank) {
+        isolated_bank.set_inner(updated.into_inner());
+    }
+
+    native_sol.unwrap()
+}
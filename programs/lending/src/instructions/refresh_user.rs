@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+use crate::risk::{calculate_user_values, deserialize_account, load_emode, load_priced_banks};
+use crate::state::*;
+use crate::events::RefreshUserEvent;
+
+#[derive(Accounts)]
+pub struct RefreshUser<'info> {
+    #[account(
+        mut,
+        seeds = [user_account.owner.as_ref(), &[user_account.sub_account]],
+        bump,
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+// 1. Load the user's e-mode category if one is selected, then a (bank, price update) pair for each of
+//    the user's positions, from the remaining accounts
+// 2. Accrue interest on every bank, writing it back for banks passed as writable
+// 3. Reprice every position and store the user's health factor
+
+pub fn process_refresh_user(ctx: Context<RefreshUser>) -> Result<()> {
+    let user: &mut User = &mut ctx.accounts.user_account;
+
+    let now = Clock::get()?.unix_timestamp;
+    let (emode, accounts) = load_emode(user, ctx.remaining_accounts)?;
+
+    let bank_infos = accounts.iter().step_by(2).take(user.active_positions().count());
+    for bank_info in bank_infos.filter(|bank_info| bank_info.is_writable) {
+        let mut bank = deserialize_account::<Bank>(bank_info)?;
+        bank.accrue_interest_and_emit(bank_info.key(), now)?;
+        bank.try_serialize(&mut &mut bank_info.try_borrow_mut_data()?[..])?;
+    }
+    let priced_banks = load_priced_banks(user, accounts, &[])?;
+
+    user.health_factor = calculate_user_values(user, &priced_banks, emode.as_ref()).health_factor();
+    user.last_updated = now;
+
+    emit!(RefreshUserEvent {
+        user: ctx.accounts.user_account.key(),
+        owner: ctx.accounts.user_account.owner,
+        health_factor: ctx.accounts.user_account.health_factor,
+    });
+    Ok(())
+}
@@ -1,13 +1,23 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+use crate::constants::PAUSE_REPAY;
+use crate::risk::{calculate_isolation_debt, get_event_price, refresh_health};
 use crate::state::*;
+use crate::events::RepayEvent;
 use crate::error::ErrorCode;
 
 #[derive(Accounts)]
+#[instruction(sub_account: u8)]
 pub struct Repay<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut, 
@@ -15,6 +25,8 @@ pub struct Repay<'info> {
         bump,
     )]  
     pub bank: Account<'info, Bank>,
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
     #[account(
         mut, 
         seeds = [b"treasury", mint.key().as_ref()],
@@ -23,7 +35,7 @@ pub struct Repay<'info> {
     pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut, 
-        seeds = [signer.key().as_ref()],
+        seeds = [signer.key().as_ref(), &[sub_account]],
         bump,
     )]  
     pub user_account: Account<'info, User>,
@@ -35,34 +47,29 @@ pub struct Repay<'info> {
         associated_token::token_program = token_program,
     )]
     pub user_token_account: InterfaceAccount<'info, TokenAccount>, 
+    /// Bank of the user's isolated collateral, required when the user is in isolation mode
+    #[account(mut)]
+    pub isolated_bank: Option<Account<'info, Bank>>,
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
-pub fn process_repay(ctx:Context<Repay>,amount : u64) -> Result<()>{
+pub fn process_repay(ctx:Context<Repay>, _sub_account: u8, amount : u64) -> Result<()>{
+    ctx.accounts.bank.check_not_paused(&ctx.accounts.config, PAUSE_REPAY)?;
+    let bank_key = ctx.accounts.bank.key();
+    ctx.accounts.bank.accrue_interest_and_emit(bank_key, Clock::get()?.unix_timestamp)?;
+
     // 1. User account reference
-    let user = &mut ctx.accounts.user_account;
+    let user: &mut User = &mut ctx.accounts.user_account;
 
-    // 2. Determine Borrowed Asset
-    let borrowed_asset;
+    // 2. Determine Borrowed Amount
+    let position = user.position_mut(&bank_key).ok_or(ErrorCode::PositionNotFound)?;
+    let borrowed_asset = ctx.accounts.bank.borrow_amount_for(position.borrow_shares);
 
-     match ctx.accounts.mint.to_account_info().key(){
-        key if key == user.usdc_address => { // Checks the mint key to determine if borrowed asset is usdc or sol
-            borrowed_asset = user.borrowed_usdc;
-        }
-        _ => {
-            borrowed_asset = user.borrowed_sol;
-        }
-    }
-
-    // 3. Over repay check
-    if amount > borrowed_asset {  // return error if amount to repaid exceeds the borrowed amount
-        return Err(ErrorCode::OverRepay.into());
-    }
-    
-    // 4. Create CPI Context for Transfer
+    // 3. Create CPI Context for Transfer
     // Prepares the accounts required for CPI to transfer tokens from user's token account to bank's token account
+    let balance_before = ctx.accounts.bank_token_account.amount;
     let transfer_cpi_accounts = TransferChecked {
         from: ctx.accounts.user_token_account.to_account_info(),
         mint: ctx.accounts.mint.to_account_info(),
@@ -74,28 +81,63 @@ pub fn process_repay(ctx:Context<Repay>,amount : u64) -> Result<()>{
     let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts);
     let decimals = ctx.accounts.mint.decimals;
 
-    // 5. Perform Token Transfer
+    // 4. Perform Token Transfer
     token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
+    // Token-2022 transfer fees are withheld from the transferred tokens, only what arrived in the treasury repays the debt
+    let amount = if ctx.accounts.bank.has_transfer_fee {
+        ctx.accounts.bank_token_account.reload()?;
+        ctx.accounts.bank_token_account.amount - balance_before
+    } else {
+        amount
+    };
+
+    // 5. Over repay check
+    if amount > borrowed_asset {  // return error if amount to repaid exceeds the borrowed amount
+        return Err(ErrorCode::OverRepay.into());
+    }
+
     // 6. Update Borrowed Amount and Shares
     let bank = &mut ctx.accounts.bank;
 
-    let borrowed_ratio = amount.checked_div(bank.total_borrowed).unwrap();
-    let users_shares = bank.total_borrowed_shares.checked_mul(borrowed_ratio).unwrap();
+    // Repaying the whole debt cancels all shares, otherwise shares are rounded down in favour of the bank
+    let users_shares = if amount == borrowed_asset {
+        position.borrow_shares
+    } else {
+        bank.borrow_shares_to_repay(amount)
+    };
 
     // since repay so borrowed amount is getting lowered
-    match ctx.accounts.mint.to_account_info().key(){
-        key if key == user.usdc_address =>{
-            user.borrowed_usdc -= amount;
-            user.borrowed_usdc_shares -= users_shares;
-        },
-        _=>{
-            user.borrowed_sol -= amount;
-            user.borrowed_sol_shares -= users_shares;
-        }
+    position.borrow_shares -= users_shares;
+    bank.total_borrowed = bank.total_borrowed.saturating_sub(amount);
+    bank.total_borrowed_shares = bank.total_borrowed_shares.checked_sub(users_shares).ok_or(ErrorCode::MathOverflow)?;
+
+    // 7. Release isolation debt
+    if user.is_isolated() {
+        let isolated_bank = match ctx.accounts.isolated_bank.as_mut() {
+            Some(isolated_bank) if isolated_bank.key() == user.isolated_bank => isolated_bank,
+            _ => return Err(ErrorCode::MissingIsolatedBank.into()),
+        };
+        isolated_bank.isolation_debt = isolated_bank.isolation_debt.saturating_sub(calculate_isolation_debt(amount, bank.mint_decimals));
     }
-    bank.total_borrowed -= amount;
-    bank.total_borrowed_shares -= users_shares;
+
+    // 8. Health factor after the repayment, the remaining accounts hold a (bank, price update) pair for each of
+    // the user's positions when the user still has borrows
+    refresh_health(user, ctx.remaining_accounts, &[(bank_key, &*bank)])?;
+    user.close_empty_positions();
+    user.last_updated = Clock::get()?.unix_timestamp;
+
+    let price = get_event_price(bank, &ctx.accounts.price_update)?;
+    emit!(RepayEvent {
+        bank: bank_key,
+        user: ctx.accounts.user_account.key(),
+        owner: ctx.accounts.signer.key(),
+        amount,
+        shares: users_shares,
+        price: price.price,
+        price_exponent: price.exponent,
+        health_factor: ctx.accounts.user_account.health_factor,
+    });
     Ok(())
 
 }
\ No newline at end of file
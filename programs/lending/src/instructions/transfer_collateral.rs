@@ -0,0 +1,99 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+use crate::risk::{check_health, get_event_price, priced_accounts_len};
+use crate::constants::PAUSE_WITHDRAW;
+use crate::state::*;
+use crate::events::TransferCollateralEvent;
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+#[instruction(from_sub_account: u8, to_sub_account: u8)]
+pub struct TransferCollateral<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
+    #[account(
+        mut,
+        seeds = [signer.key().as_ref(), &[from_sub_account]],
+        bump,
+    )]
+    pub from_user_account: Account<'info, User>,
+    #[account(
+        mut,
+        seeds = [signer.key().as_ref(), &[to_sub_account]],
+        bump,
+    )]
+    pub to_user_account: Account<'info, User>,
+}
+
+// 1. Remove the shares backing the amount from the source sub-account's position
+// 2. Credit the same shares to the destination sub-account, respecting isolation mode and, for a new position,
+//    the bank's `collateral_enabled` like `deposit_collateral`
+// 3. Check the health factor of both sub-accounts, the remaining accounts hold the (bank, price update)
+//    pairs of the source's positions followed by those of the destination, each only if that side has borrows
+
+pub fn process_transfer_collateral(ctx: Context<TransferCollateral>, from_sub_account: u8, to_sub_account: u8, amount: u64) -> Result<()> {
+    ctx.accounts.bank.check_not_paused(&ctx.accounts.config, PAUSE_WITHDRAW)?;
+
+    // Both sides would deserialize the same account and the second write would win
+    if from_sub_account == to_sub_account {
+        return Err(ErrorCode::SameSubAccount.into());
+    }
+
+    let bank_key = ctx.accounts.bank.key();
+    let bank = &ctx.accounts.bank;
+    let from_user: &mut User = &mut ctx.accounts.from_user_account;
+    let to_user: &mut User = &mut ctx.accounts.to_user_account;
+
+    // Shares are rounded up so that the source cannot move more than it holds
+    let shares = bank.deposit_shares_to_burn(amount);
+    let from_position = from_user.position_mut(&bank_key).ok_or(ErrorCode::PositionNotFound)?;
+    if shares > from_position.deposit_shares {
+        return Err(ErrorCode::InsufficientFunds.into());
+    }
+    from_position.deposit_shares -= shares;
+
+    // Topping up an existing position stays possible once the bank stops taking collateral
+    if to_user.position(&bank_key).is_none() && !bank.collateral_enabled {
+        return Err(ErrorCode::CollateralDisabled.into());
+    }
+    to_user.check_isolation(&bank_key, bank)?;
+    to_user.get_or_create_position(&bank_key)?.deposit_shares += shares;
+
+    let (from_accounts, to_accounts) = ctx.remaining_accounts.split_at(priced_accounts_len(from_user).min(ctx.remaining_accounts.len()));
+    check_health(from_user, from_accounts, &[])?;
+    check_health(to_user, to_accounts, &[])?;
+    from_user.close_empty_positions();
+
+    let now = Clock::get()?.unix_timestamp;
+    from_user.last_updated = now;
+    to_user.last_updated = now;
+
+    let price = get_event_price(bank, &ctx.accounts.price_update)?;
+    emit!(TransferCollateralEvent {
+        bank: bank_key,
+        from_user: ctx.accounts.from_user_account.key(),
+        to_user: ctx.accounts.to_user_account.key(),
+        owner: ctx.accounts.signer.key(),
+        amount,
+        shares,
+        price: price.price,
+        price_exponent: price.exponent,
+        from_health_factor: ctx.accounts.from_user_account.health_factor,
+        to_health_factor: ctx.accounts.to_user_account.health_factor,
+    });
+
+    Ok(())
+}
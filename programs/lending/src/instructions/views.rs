@@ -0,0 +1,157 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+use crate::risk::*;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct ViewUser<'info> {
+    pub user_account: Account<'info, User>,
+}
+
+#[derive(Accounts)]
+pub struct ViewUserBank<'info> {
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    pub price_update: Account<'info, PriceUpdateV2>,
+    pub user_account: Account<'info, User>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PositionView {
+    pub bank: Pubkey,
+    pub deposit_shares: u64,
+    /// Tokens the deposit shares are worth, including accrued interest
+    pub deposited: u64,
+    pub borrow_shares: u64,
+    /// Tokens owed for the borrow shares, including accrued interest
+    pub borrowed: u64,
+    pub is_collateral: bool,
+}
+
+/// USD values are scaled by 10^VALUE_DECIMALS, see `UserValues`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct UserPositionView {
+    pub health_factor: u64,
+    pub collateral_value: u128,
+    pub borrowable_value: u128,
+    pub liquidation_value: u128,
+    pub borrowed_value: u128,
+    pub positions: Vec<PositionView>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LiquidationPriceView {
+    pub price: i64,
+    pub exponent: i32,
+}
+
+// Read-only instructions meant to be simulated, returning their result as return data.
+// They compute with the same risk code as the state-changing instructions: the remaining accounts hold the
+// user's e-mode category if one is selected, followed by a (bank, price update) pair for each of the user's positions.
+
+pub fn process_get_user_position(ctx: Context<ViewUser>) -> Result<UserPositionView> {
+    let user = &ctx.accounts.user_account;
+    let (emode, accounts) = load_emode(user, ctx.remaining_accounts)?;
+    let priced_banks = load_priced_banks(user, accounts, &[])?;
+    let values = calculate_user_values(user, &priced_banks, emode.as_ref());
+
+    let positions = user.active_positions().zip(priced_banks.iter()).map(|(position, priced_bank)| PositionView {
+        bank: position.bank,
+        deposit_shares: position.deposit_shares,
+        deposited: priced_bank.bank.deposit_amount_for(position.deposit_shares),
+        borrow_shares: position.borrow_shares,
+        borrowed: priced_bank.bank.borrow_amount_for(position.borrow_shares),
+        is_collateral: position.is_collateral(&priced_bank.bank),
+    }).collect();
+
+    Ok(UserPositionView {
+        health_factor: values.health_factor(),
+        collateral_value: values.collateral_value,
+        borrowable_value: values.borrowable_value,
+        liquidation_value: values.liquidation_value,
+        borrowed_value: values.borrowed_value,
+        positions,
+    })
+}
+
+// Tokens of the bank the user can borrow, within the max LTV of their collateral, the bank's liquidity and limits,
+// and the e-mode and isolation mode rules
+pub fn process_get_max_borrowable(ctx: Context<ViewUserBank>) -> Result<u64> {
+    let bank_key = ctx.accounts.bank.key();
+    let user = &ctx.accounts.user_account;
+    let (bank, price, priced_banks, emode) = load_view(&ctx.accounts.bank, &ctx.accounts.price_update, user, ctx.remaining_accounts)?;
+
+    if !bank.borrow_enabled || (user.emode_category != 0 && bank.emode_category != user.emode_category) {
+        return Ok(0);
+    }
+
+    let values = calculate_user_values(user, &priced_banks, emode.as_ref());
+    let mut max_borrowable = calculate_max_borrowable(&values, &bank, &price)
+        .min(bank.total_deposits.saturating_sub(bank.total_borrowed));
+    if bank.borrow_limit != 0 {
+        max_borrowable = max_borrowable.min(bank.borrow_limit.saturating_sub(bank.total_borrowed));
+    }
+
+    if user.is_isolated() {
+        if !bank.borrowable_in_isolation || bank_key == user.isolated_bank {
+            return Ok(0);
+        }
+        if let Some(isolated) = priced_banks.iter().find(|priced_bank| priced_bank.key == user.isolated_bank) {
+            let remaining_debt = isolated.bank.isolation_debt_ceiling.saturating_sub(isolated.bank.isolation_debt);
+            max_borrowable = max_borrowable.min(calculate_isolation_amount(remaining_debt, bank.mint_decimals));
+        }
+    }
+
+    Ok(max_borrowable)
+}
+
+// Tokens of the user's collateral in the bank that `withdraw_collateral` would release
+pub fn process_get_max_withdrawable(ctx: Context<ViewUserBank>) -> Result<u64> {
+    let user = &ctx.accounts.user_account;
+    let (bank, price, priced_banks, emode) = load_view(&ctx.accounts.bank, &ctx.accounts.price_update, user, ctx.remaining_accounts)?;
+    let Some(position) = user.position(&ctx.accounts.bank.key()) else {
+        return Ok(0);
+    };
+
+    let values = calculate_user_values(user, &priced_banks, emode.as_ref());
+    Ok(calculate_max_withdrawable(&values, position, &bank, &price, emode.as_ref()))
+}
+
+// Price of the bank's asset at which the user can be liquidated, if any
+pub fn process_get_liquidation_price(ctx: Context<ViewUserBank>) -> Result<Option<LiquidationPriceView>> {
+    let user = &ctx.accounts.user_account;
+    let (bank, price, priced_banks, emode) = load_view(&ctx.accounts.bank, &ctx.accounts.price_update, user, ctx.remaining_accounts)?;
+    let Some(position) = user.position(&ctx.accounts.bank.key()) else {
+        return Ok(None);
+    };
+
+    let values = calculate_user_values(user, &priced_banks, emode.as_ref());
+    Ok(calculate_liquidation_price(&values, position, &bank, &price, emode.as_ref())
+        .map(|liquidation_price| LiquidationPriceView { price: liquidation_price, exponent: price.exponent }))
+}
+
+/// Loads the bank with its interest up to now and its price, and prices the user's positions.
+/// When the bank is one of the user's positions, its price from the remaining accounts is used so that all values agree.
+fn load_view(
+    bank_account: &Account<Bank>,
+    price_update: &PriceUpdateV2,
+    user: &User,
+    remaining_accounts: &[AccountInfo],
+) -> Result<(Bank, Price, Vec<PricedBank>, Option<EModeCategory>)> {
+    let clock = Clock::get()?;
+    let mut bank = Bank::clone(bank_account);
+    bank.accrue_interest(clock.unix_timestamp)?;
+
+    let (emode, accounts) = load_emode(user, remaining_accounts)?;
+    let priced_banks = load_priced_banks(user, accounts, &[(bank_account.key(), &bank)])?;
+    let price = match priced_banks.iter().find(|priced_bank| priced_bank.key == bank_account.key()) {
+        Some(priced_bank) => priced_bank.price,
+        None => get_price(&bank, price_update, &clock)?,
+    };
+    Ok((bank, price, priced_banks, emode))
+}
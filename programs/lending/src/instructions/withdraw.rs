@@ -1,13 +1,22 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use anchor_spl::token_interface::{ self, Burn, Mint, TokenAccount, TokenInterface, TransferChecked };
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+use crate::constants::PAUSE_WITHDRAW;
+use crate::risk::get_event_price;
 use crate::state::*;
+use crate::events::WithdrawEvent;
 use crate::error::ErrorCode;
 
 #[derive(Accounts)]
 pub struct Withdraw<'info>{
     #[account(mut)]
     pub signer : Signer<'info>, // user initiating the withdrawal
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
     pub mint : InterfaceAccount<'info,Mint>, // token mint
     #[account(
         mut,
@@ -15,6 +24,8 @@ pub struct Withdraw<'info>{
         bump
     )]
     pub bank : Account<'info,Bank>, // the bank's state account
+    /// Price update of the bank's asset, its price is reported in the event
+    pub price_update: Account<'info, PriceUpdateV2>,
     #[account(
         mut,
         seeds = [b"treasury",mint.key().as_ref()],
@@ -22,11 +33,11 @@ pub struct Withdraw<'info>{
     )]
     pub bank_token_account : InterfaceAccount<'info,TokenAccount>, // the bank's token account holding the tokens to be withdrawn
     #[account(
-        mut, 
-        seeds = [signer.key().as_ref()],
-        bump,
-    )]  
-    pub user_account: Account<'info, User>,
+        mut,
+        seeds = [b"receipt",mint.key().as_ref()],
+        bump
+    )]
+    pub receipt_mint : InterfaceAccount<'info,Mint>, // the bank's receipt token mint
     #[account(
         init_if_needed,
         payer = signer,
@@ -35,6 +46,13 @@ pub struct Withdraw<'info>{
         associated_token::token_program = token_program
     )]
     pub user_token_account: InterfaceAccount<'info, TokenAccount>, // the user's token account where tokens will be transferred
+    #[account(
+        mut,
+        associated_token::mint = receipt_mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program
+    )]
+    pub user_receipt_token_account: InterfaceAccount<'info, TokenAccount>, // the user's receipt tokens to be burned
 
     // Programs necessary for handling token operations and system instuctions
     pub token_program: Interface<'info, TokenInterface>,
@@ -42,30 +60,36 @@ pub struct Withdraw<'info>{
     pub system_program: Program<'info, System>,
 }
 
-// 1. CPI transfer from bank's token account to user's token account
-// 2. Calculate new shares to be removed from the bank
-// 3. Update user's deposited amount and total collateral value
+// 1. Calculate the shares to be burned for the requested amount
+// 2. Burn the user's receipt tokens
+// 3. CPI transfer from bank's token account to user's token account
 // 4. Update bank's total deposits and total deposit shares
-// 5. Update users health factor ??
 
 pub fn process_withdraw(ctx : Context<Withdraw>,amount : u64) -> Result<()>{
-    let user = &mut ctx.accounts.user_account;
-
-    let deposited_value; 
-
-    // Initialising deposited value based on the mint address
+    let bank_key = ctx.accounts.bank.key();
+    let bank = &mut ctx.accounts.bank;
+    bank.check_not_paused(&ctx.accounts.config, PAUSE_WITHDRAW)?;
+    bank.accrue_interest_and_emit(bank_key, Clock::get()?.unix_timestamp)?;
 
-    // FIXME: Change from if statement to match statement?? Use PDA deserialization to get the mint address??
-    if ctx.accounts.mint.to_account_info().key() == user.usdc_address {
-        deposited_value = user.deposited_usdc;
-    } else {
-        deposited_value = user.deposited_sol;
+    if amount > bank.total_deposits {
+        return Err(ErrorCode::InsufficientFunds.into());
     }
-// Ensuring user has enough deposited value to withdraw the requested amoint
-    if amount > deposited_value {
+    // Ensuring the user holds enough receipt tokens to withdraw the requested amount
+    let shares_to_burn = bank.deposit_shares_to_burn(amount);
+    if shares_to_burn > ctx.accounts.user_receipt_token_account.amount {
         return Err(ErrorCode::InsufficientFunds.into());
     }
 
+    // Burn the receipt tokens backing the withdrawn amount
+    let burn_cpi_accounts = Burn {
+        mint: ctx.accounts.receipt_mint.to_account_info(),
+        from: ctx.accounts.user_receipt_token_account.to_account_info(),
+        authority: ctx.accounts.signer.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program.clone(), burn_cpi_accounts);
+    token_interface::burn(cpi_ctx, shares_to_burn)?;
+
     // Token Transfer
     let transfer_cpi_accounts = TransferChecked {
         from: ctx.accounts.bank_token_account.to_account_info(),
@@ -74,7 +98,6 @@ pub fn process_withdraw(ctx : Context<Withdraw>,amount : u64) -> Result<()>{
         authority: ctx.accounts.bank_token_account.to_account_info(),
     };
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
     let mint_key = ctx.accounts.mint.key();
     let signer_seeds: &[&[&[u8]]] = &[
         &[
@@ -89,22 +112,19 @@ pub fn process_withdraw(ctx : Context<Withdraw>,amount : u64) -> Result<()>{
 
     token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
+    // Updates the protocol's total deposits and shares to reflect the withdrawal
+    bank.total_deposits = bank.total_deposits.checked_sub(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_shares = bank.total_deposit_shares.checked_sub(shares_to_burn).ok_or(ErrorCode::MathOverflow)?;
 
-    // Calculates the proportion of deposit shares to remove based on the amount withdrawn
-    // It updates the user's deposited values and the protocol's total deposits and shares to reflect the withdrawal
-    let bank = &mut ctx.accounts.bank;
-    let shares_to_remove = (amount as f64 / bank.total_deposits as f64) * bank.total_deposit_shares as f64;
-
-    let user = &mut ctx.accounts.user_account;
-    
-    if ctx.accounts.mint.to_account_info().key() == user.usdc_address {
-        user.deposited_usdc -= shares_to_remove as u64;
-    } else {
-        user.deposited_sol -= shares_to_remove as u64;
-    }
-
-    bank.total_deposits -= amount;
-    bank.total_deposit_shares -= shares_to_remove as u64;
+    let price = get_event_price(bank, &ctx.accounts.price_update)?;
+    emit!(WithdrawEvent {
+        bank: bank_key,
+        owner: ctx.accounts.signer.key(),
+        amount,
+        shares: shares_to_burn,
+        price: price.price,
+        price_exponent: price.exponent,
+    });
     
     Ok(())     
-}
\ No newline at end of file
+}
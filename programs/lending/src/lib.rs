@@ -1,10 +1,13 @@
 use anchor_lang::prelude::*;
 use instructions::*;
+use state::RiskParams;
 
-mod state;
-mod instructions;
-mod error;
-mod constants;
+pub mod state;
+pub mod instructions;
+pub mod error;
+pub mod constants;
+pub mod risk;
+pub mod events;
 
 declare_id!("CdZeD33fXsAHfZYS8jdxg4qHgXYJwBQ1Bv6GJyETtLST");
 
@@ -13,12 +16,100 @@ pub mod lending_protocol {
 
     use super::*;
 
-    pub fn init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64) -> Result<()> {
-        process_init_bank(ctx, liquidation_threshold, max_ltv)
+    pub fn init_protocol_config(ctx: Context<InitProtocolConfig>, guardian: Pubkey) -> Result<()> {
+        process_init_protocol_config(ctx, guardian)
     }
 
-    pub fn init_user(ctx: Context<InitUser>, usdc_address: Pubkey) -> Result<()> {
-        process_init_user(ctx, usdc_address)
+    pub fn set_guardian(ctx: Context<UpdateProtocolConfig>, guardian: Pubkey) -> Result<()> {
+        process_set_guardian(ctx, guardian)
+    }
+
+    pub fn propose_admin(ctx: Context<UpdateProtocolConfig>, admin: Pubkey) -> Result<()> {
+        process_propose_admin(ctx, admin)
+    }
+
+    pub fn accept_admin(ctx: Context<UpdateProtocolConfig>) -> Result<()> {
+        process_accept_admin(ctx)
+    }
+
+    pub fn set_protocol_pause(ctx: Context<UpdateProtocolConfig>, pause_flags: u8) -> Result<()> {
+        process_set_protocol_pause(ctx, pause_flags)
+    }
+
+    pub fn init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64, oracle_feed_id: [u8; 32]) -> Result<()> {
+        process_init_bank(ctx, liquidation_threshold, max_ltv, oracle_feed_id)
+    }
+
+    pub fn list_bank(ctx: Context<AdminUpdateBank>) -> Result<()> {
+        process_list_bank(ctx)
+    }
+
+    pub fn delist_bank(ctx: Context<AdminUpdateBank>) -> Result<()> {
+        process_delist_bank(ctx)
+    }
+
+    pub fn set_bank_authority(ctx: Context<AdminUpdateBank>, authority: Pubkey) -> Result<()> {
+        process_set_bank_authority(ctx, authority)
+    }
+
+    pub fn propose_bank_authority(ctx: Context<UpdateBank>, authority: Pubkey) -> Result<()> {
+        process_propose_bank_authority(ctx, authority)
+    }
+
+    pub fn accept_bank_authority(ctx: Context<AcceptBankAuthority>) -> Result<()> {
+        process_accept_bank_authority(ctx)
+    }
+
+    pub fn set_flash_loan_fee(ctx: Context<UpdateBank>, flash_loan_fee: u64) -> Result<()> {
+        process_set_flash_loan_fee(ctx, flash_loan_fee)
+    }
+
+    pub fn set_bank_limits(ctx: Context<UpdateBank>, deposit_limit: u64, borrow_limit: u64) -> Result<()> {
+        process_set_bank_limits(ctx, deposit_limit, borrow_limit)
+    }
+
+    pub fn set_borrow_enabled(ctx: Context<UpdateBank>, borrow_enabled: bool) -> Result<()> {
+        process_set_borrow_enabled(ctx, borrow_enabled)
+    }
+
+    pub fn set_bank_pause(ctx: Context<SetBankPause>, pause_flags: u8) -> Result<()> {
+        process_set_bank_pause(ctx, pause_flags)
+    }
+
+    pub fn queue_config_change(ctx: Context<QueueConfigChange>, params: RiskParams) -> Result<()> {
+        process_queue_config_change(ctx, params)
+    }
+
+    pub fn execute_config_change(ctx: Context<ExecuteConfigChange>) -> Result<()> {
+        process_execute_config_change(ctx)
+    }
+
+    pub fn cancel_config_change(ctx: Context<CancelConfigChange>) -> Result<()> {
+        process_cancel_config_change(ctx)
+    }
+
+    pub fn init_emode_category(ctx: Context<InitEModeCategory>, id: u8, max_ltv: u64, liquidation_threshold: u64, liquidation_bonus: u64) -> Result<()> {
+        process_init_emode_category(ctx, id, max_ltv, liquidation_threshold, liquidation_bonus)
+    }
+
+    pub fn queue_emode_category_change(ctx: Context<QueueEModeCategoryChange>, max_ltv: u64, liquidation_threshold: u64, liquidation_bonus: u64) -> Result<()> {
+        process_queue_emode_category_change(ctx, max_ltv, liquidation_threshold, liquidation_bonus)
+    }
+
+    pub fn execute_emode_category_change(ctx: Context<ExecuteEModeCategoryChange>) -> Result<()> {
+        process_execute_emode_category_change(ctx)
+    }
+
+    pub fn cancel_emode_category_change(ctx: Context<CancelEModeCategoryChange>) -> Result<()> {
+        process_cancel_emode_category_change(ctx)
+    }
+
+    pub fn init_user(ctx: Context<InitUser>, sub_account: u8) -> Result<()> {
+        process_init_user(ctx, sub_account)
+    }
+
+    pub fn set_user_emode(ctx: Context<SetUserEMode>, sub_account: u8, emode_category: u8) -> Result<()> {
+        process_set_user_emode(ctx, sub_account, emode_category)
     }
 
     pub fn deposit (ctx: Context<Deposit>, amount: u64) -> Result<()> {
@@ -29,15 +120,79 @@ pub mod lending_protocol {
         process_withdraw(ctx, amount)
     }
 
-    pub fn borrow(ctx: Context<Borrow>, amount: u64) -> Result<()> {
-        process_borrow(ctx, amount)
+    pub fn deposit_sol<'info>(ctx: Context<'_, '_, '_, 'info, DepositSol<'info>>, amount: u64) -> Result<()> {
+        process_deposit_sol(ctx, amount)
+    }
+
+    pub fn withdraw_sol<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawSol<'info>>, amount: u64) -> Result<()> {
+        process_withdraw_sol(ctx, amount)
+    }
+
+    pub fn deposit_collateral(ctx: Context<DepositCollateral>, sub_account: u8, shares: u64) -> Result<()> {
+        process_deposit_collateral(ctx, sub_account, shares)
+    }
+
+    pub fn withdraw_collateral(ctx: Context<WithdrawCollateral>, sub_account: u8, shares: u64) -> Result<()> {
+        process_withdraw_collateral(ctx, sub_account, shares)
+    }
+
+    pub fn set_collateral_enabled(ctx: Context<SetCollateralEnabled>, sub_account: u8, enabled: bool) -> Result<()> {
+        process_set_collateral_enabled(ctx, sub_account, enabled)
+    }
+
+    pub fn borrow(ctx: Context<Borrow>, sub_account: u8, amount: u64) -> Result<()> {
+        process_borrow(ctx, sub_account, amount)
+    }
+
+    pub fn repay(ctx: Context<Repay>, sub_account: u8, amount: u64) -> Result<()> {
+        process_repay(ctx, sub_account, amount)
+    }
+
+    pub fn borrow_sol<'info>(ctx: Context<'_, '_, '_, 'info, BorrowSol<'info>>, sub_account: u8, amount: u64) -> Result<()> {
+        process_borrow_sol(ctx, sub_account, amount)
     }
 
-    pub fn repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
-        process_repay(ctx, amount)
+    pub fn repay_sol<'info>(ctx: Context<'_, '_, '_, 'info, RepaySol<'info>>, sub_account: u8, amount: u64) -> Result<()> {
+        process_repay_sol(ctx, sub_account, amount)
     }
 
     pub fn liquidate(ctx: Context<Liquidate>) -> Result<()> {
         process_liquidate(ctx)
     }
+
+    pub fn transfer_collateral(ctx: Context<TransferCollateral>, from_sub_account: u8, to_sub_account: u8, amount: u64) -> Result<()> {
+        process_transfer_collateral(ctx, from_sub_account, to_sub_account, amount)
+    }
+
+    pub fn accrue_interest(ctx: Context<AccrueInterest>) -> Result<()> {
+        process_accrue_interest(ctx)
+    }
+
+    pub fn refresh_user(ctx: Context<RefreshUser>) -> Result<()> {
+        process_refresh_user(ctx)
+    }
+
+    pub fn get_user_position(ctx: Context<ViewUser>) -> Result<UserPositionView> {
+        process_get_user_position(ctx)
+    }
+
+    pub fn get_max_borrowable(ctx: Context<ViewUserBank>) -> Result<u64> {
+        process_get_max_borrowable(ctx)
+    }
+
+    pub fn get_max_withdrawable(ctx: Context<ViewUserBank>) -> Result<u64> {
+        process_get_max_withdrawable(ctx)
+    }
+
+    pub fn get_liquidation_price(ctx: Context<ViewUserBank>) -> Result<Option<LiquidationPriceView>> {
+        process_get_liquidation_price(ctx)
+    }
+
+    pub fn flash_borrow(ctx: Context<FlashBorrow>, amount: u64) -> Result<()> {
+        process_flash_borrow(ctx, amount)
+    }
+
+    pub fn flash_repay(ctx: Context<FlashRepay>) -> Result<()> {
+        process_flash_repay(ctx)
+    }
 }
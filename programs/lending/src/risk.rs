@@ -0,0 +1,406 @@
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::price_update::{Price, PriceUpdateV2};
+use crate::constants::VALUE_DECIMALS;
+use crate::state::*;
+use crate::error::ErrorCode;
+
+/// Health factor of a position exactly at its liquidation threshold.
+/// Health factors are expressed in percent, like `liquidation_threshold`.
+pub const HEALTHY_HEALTH_FACTOR: u64 = 100;
+
+/// A bank of one of the user's positions, together with the oracle price of its asset
+pub struct PricedBank {
+    pub key: Pubkey,
+    pub bank: Bank,
+    pub price: Price,
+}
+
+/// USD values of a user's positions, scaled by 10^VALUE_DECIMALS
+#[derive(Default)]
+pub struct UserValues {
+    /// Value of all collateral
+    pub collateral_value: u128,
+    /// Collateral value weighted by each bank's max LTV
+    pub borrowable_value: u128,
+    /// Collateral value weighted by each bank's liquidation threshold
+    pub liquidation_value: u128,
+    /// Value of all borrows
+    pub borrowed_value: u128,
+}
+
+impl UserValues {
+    /// Threshold-weighted collateral value divided by the borrowed value.
+    /// A user without any borrows is always healthy.
+    pub fn health_factor(&self) -> u64 {
+        if self.borrowed_value == 0 {
+            return u64::MAX;
+        }
+        u64::try_from(self.liquidation_value * 100 / self.borrowed_value).unwrap_or(u64::MAX)
+    }
+}
+
+/// Reads the bank's oracle price, rejecting stale and non-positive prices
+pub fn get_price(bank: &Bank, price_update: &PriceUpdateV2, clock: &Clock) -> Result<Price> {
+    let price = price_update.get_price_no_older_than(clock, bank.max_price_age, &bank.oracle_feed_id)?;
+    if price.price <= 0 {
+        return Err(ErrorCode::InvalidPrice.into());
+    }
+    Ok(price)
+}
+
+/// Latest price of the bank's asset in `price_update`, for events. Unlike `get_price` stale prices are accepted,
+/// so that an instruction that does not depend on the price is not blocked by the oracle.
+pub fn get_event_price(bank: &Bank, price_update: &PriceUpdateV2) -> Result<Price> {
+    Ok(price_update.get_price_unchecked(&bank.oracle_feed_id)?)
+}
+
+/// USD value of `amount` tokens, scaled by 10^VALUE_DECIMALS
+pub fn calculate_value(amount: u64, price: &Price, decimals: u8) -> u128 {
+    let value = amount as u128 * price.price as u128;
+    let exponent = price.exponent + VALUE_DECIMALS - decimals as i32;
+    if exponent >= 0 {
+        value.saturating_mul(10u128.pow(exponent as u32))
+    } else {
+        value / 10u128.pow(exponent.unsigned_abs())
+    }
+}
+
+/// Number of tokens worth `value`, the inverse of `calculate_value`
+pub fn calculate_amount(value: u128, price: &Price, decimals: u8) -> u64 {
+    let exponent = price.exponent + VALUE_DECIMALS - decimals as i32;
+    let amount = if exponent >= 0 {
+        value / (price.price as u128 * 10u128.pow(exponent as u32))
+    } else {
+        value.saturating_mul(10u128.pow(exponent.unsigned_abs())) / price.price as u128
+    };
+    u64::try_from(amount).unwrap_or(u64::MAX)
+}
+
+/// Number of remaining accounts `check_health` reads for `user`.
+/// Users without borrows are always healthy and need no price accounts.
+pub fn priced_accounts_len(user: &User) -> usize {
+    if user.has_borrows() {
+        (user.emode_category != 0) as usize + 2 * user.active_positions().count()
+    } else {
+        0
+    }
+}
+
+/// Loads the user's e-mode category, given as the first remaining account when one is selected,
+/// and returns the accounts following it
+pub fn load_emode<'a, 'info>(user: &User, accounts: &'a [AccountInfo<'info>]) -> Result<(Option<EModeCategory>, &'a [AccountInfo<'info>])> {
+    if user.emode_category == 0 {
+        return Ok((None, accounts));
+    }
+    let (emode_info, accounts) = accounts.split_first().ok_or(ErrorCode::InvalidRemainingAccounts)?;
+    let emode = deserialize_account::<EModeCategory>(emode_info)?;
+    if emode.id != user.emode_category {
+        return Err(ErrorCode::InvalidRemainingAccounts.into());
+    }
+    Ok((Some(emode), accounts))
+}
+
+/// Max LTV and liquidation threshold of collateral in `bank`, those of the user's e-mode category when the bank belongs to it
+pub fn collateral_weights(bank: &Bank, emode: Option<&EModeCategory>) -> (u64, u64) {
+    match emode {
+        Some(emode) if emode.id == bank.emode_category => (emode.max_ltv, emode.liquidation_threshold),
+        _ => (bank.max_ltv, bank.liquidation_threshold),
+    }
+}
+
+/// Liquidation bonus of collateral in `bank`, that of the user's e-mode category when the bank belongs to it
+pub fn liquidation_bonus(bank: &Bank, emode: Option<&EModeCategory>) -> u64 {
+    match emode {
+        Some(emode) if emode.id == bank.emode_category => emode.liquidation_bonus,
+        _ => bank.liquidation_bonus,
+    }
+}
+
+/// Loads the bank and price of every active position of `user` from `(bank, price_update)` pairs,
+/// given in the order of the user's positions. Banks the instruction already holds are passed in
+/// `loaded_banks` so that their in-memory state is used instead of the account data.
+/// The returned banks include interest accrued up to now.
+pub fn load_priced_banks(user: &User, accounts: &[AccountInfo], loaded_banks: &[(Pubkey, &Bank)]) -> Result<Vec<PricedBank>> {
+    let clock = Clock::get()?;
+    let mut accounts = accounts.iter();
+    let mut priced_banks = Vec::new();
+
+    for position in user.active_positions() {
+        let (Some(bank_info), Some(price_info)) = (accounts.next(), accounts.next()) else {
+            return Err(ErrorCode::InvalidRemainingAccounts.into());
+        };
+        if bank_info.key() != position.bank {
+            return Err(ErrorCode::InvalidRemainingAccounts.into());
+        }
+
+        let mut bank = match loaded_banks.iter().find(|(key, _)| *key == position.bank) {
+            Some((_, bank)) => Bank::clone(bank),
+            None => deserialize_account::<Bank>(bank_info)?,
+        };
+        // Banks that were not accrued in this transaction are valued with their interest up to now
+        bank.accrue_interest(clock.unix_timestamp)?;
+        let price_update = deserialize_account::<PriceUpdateV2>(price_info)?;
+        let price = get_price(&bank, &price_update, &clock)?;
+
+        priced_banks.push(PricedBank { key: position.bank, bank, price });
+    }
+    Ok(priced_banks)
+}
+
+/// Values the user's deposits and borrows with the given banks and prices
+pub fn calculate_user_values(user: &User, priced_banks: &[PricedBank], emode: Option<&EModeCategory>) -> UserValues {
+    let mut values = UserValues::default();
+    for position in user.active_positions() {
+        let Some(priced_bank) = priced_banks.iter().find(|priced_bank| priced_bank.key == position.bank) else {
+            continue;
+        };
+        let bank = &priced_bank.bank;
+
+        if position.is_collateral(bank) {
+            let deposited = bank.deposit_amount_for(position.deposit_shares);
+            let deposited_value = calculate_value(deposited, &priced_bank.price, bank.mint_decimals);
+            let (max_ltv, liquidation_threshold) = collateral_weights(bank, emode);
+            values.collateral_value += deposited_value;
+            values.borrowable_value += deposited_value * max_ltv as u128 / 100;
+            values.liquidation_value += deposited_value * liquidation_threshold as u128 / 100;
+        }
+
+        let borrowed = bank.borrow_amount_for(position.borrow_shares);
+        values.borrowed_value += calculate_value(borrowed, &priced_bank.price, bank.mint_decimals);
+    }
+    values
+}
+
+/// Recomputes and stores the user's health factor from the accounts read by `check_health`
+pub fn refresh_health(user: &mut User, accounts: &[AccountInfo], loaded_banks: &[(Pubkey, &Bank)]) -> Result<()> {
+    if !user.has_borrows() {
+        user.health_factor = u64::MAX;
+        return Ok(());
+    }
+
+    let (emode, accounts) = load_emode(user, accounts)?;
+    let priced_banks = load_priced_banks(user, accounts, loaded_banks)?;
+    user.health_factor = calculate_user_values(user, &priced_banks, emode.as_ref()).health_factor();
+    Ok(())
+}
+
+/// Recomputes and stores the user's health factor, failing if the user can be liquidated
+pub fn check_health(user: &mut User, accounts: &[AccountInfo], loaded_banks: &[(Pubkey, &Bank)]) -> Result<()> {
+    refresh_health(user, accounts, loaded_banks)?;
+    if user.health_factor < HEALTHY_HEALTH_FACTOR {
+        return Err(ErrorCode::UnderCollateralized.into());
+    }
+    Ok(())
+}
+
+/// Like `check_health`, but new borrows must also stay within the max LTV of the collateral
+pub fn check_borrowable(user: &mut User, accounts: &[AccountInfo], loaded_banks: &[(Pubkey, &Bank)]) -> Result<()> {
+    let (emode, accounts) = load_emode(user, accounts)?;
+    let priced_banks = load_priced_banks(user, accounts, loaded_banks)?;
+    let values = calculate_user_values(user, &priced_banks, emode.as_ref());
+    user.health_factor = values.health_factor();
+    if values.borrowed_value > values.borrowable_value {
+        return Err(ErrorCode::OverLTV.into());
+    }
+    Ok(())
+}
+
+/// Fails if the user borrows from a bank outside of their e-mode category
+pub fn check_emode_borrows(user: &User, priced_banks: &[PricedBank], emode: Option<&EModeCategory>) -> Result<()> {
+    let Some(emode) = emode else {
+        return Ok(());
+    };
+    for position in user.active_positions().filter(|position| position.borrow_shares > 0) {
+        match priced_banks.iter().find(|priced_bank| priced_bank.key == position.bank) {
+            Some(priced_bank) if priced_bank.bank.emode_category == emode.id => {}
+            _ => return Err(ErrorCode::EModeMismatch.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Assets borrowable in isolation are valued at $1 against the isolation debt ceiling
+const ISOLATION_PRICE: Price = Price { price: 1, conf: 0, exponent: 0, publish_time: 0 };
+
+/// Debt counted against an isolated bank's ceiling for borrowing `amount` tokens
+pub fn calculate_isolation_debt(amount: u64, decimals: u8) -> u64 {
+    let debt = calculate_value(amount, &ISOLATION_PRICE, decimals);
+    u64::try_from(debt).unwrap_or(u64::MAX)
+}
+
+/// Tokens that can be borrowed for `debt` of an isolated bank's ceiling, the inverse of `calculate_isolation_debt`
+pub fn calculate_isolation_amount(debt: u64, decimals: u8) -> u64 {
+    calculate_amount(debt as u128, &ISOLATION_PRICE, decimals)
+}
+
+/// Tokens of `bank` the user can borrow on top of their borrows without exceeding the max LTV of their collateral
+pub fn calculate_max_borrowable(values: &UserValues, bank: &Bank, price: &Price) -> u64 {
+    let available_value = values.borrowable_value.saturating_sub(values.borrowed_value);
+    calculate_amount(available_value, price, bank.mint_decimals)
+}
+
+/// Amount of the `borrowed` debt a liquidator repays and the amount of the `collateral` deposit seized for it.
+/// The repaid amount is capped by the close factor, and the seized collateral is worth it plus the liquidation bonus.
+pub fn calculate_liquidation(
+    borrowed: u64,
+    collateral: u64,
+    borrowed_bank: &Bank,
+    borrowed_price: &Price,
+    collateral_bank: &Bank,
+    collateral_price: &Price,
+    emode: Option<&EModeCategory>,
+) -> (u64, u64) {
+    let mut liquidation_amount = (borrowed as u128 * collateral_bank.liquidation_close_factor as u128 / 100) as u64;
+    let bonus_percentage = 100 + liquidation_bonus(collateral_bank, emode) as u128;
+    let repaid_value = calculate_value(liquidation_amount, borrowed_price, borrowed_bank.mint_decimals);
+    let mut seized_amount = calculate_amount(repaid_value * bonus_percentage / 100, collateral_price, collateral_bank.mint_decimals);
+    if seized_amount > collateral {
+        // Not enough collateral left for the full close factor, repay only what the collateral covers
+        seized_amount = collateral;
+        let seized_value = calculate_value(seized_amount, collateral_price, collateral_bank.mint_decimals);
+        liquidation_amount = calculate_amount(seized_value * 100 / bonus_percentage, borrowed_price, borrowed_bank.mint_decimals);
+    }
+    (liquidation_amount, seized_amount)
+}
+
+/// Tokens of the user's deposit in `bank` that can be withdrawn without making the user liquidatable
+pub fn calculate_max_withdrawable(values: &UserValues, position: &Position, bank: &Bank, price: &Price, emode: Option<&EModeCategory>) -> u64 {
+    let deposited = bank.deposit_amount_for(position.deposit_shares);
+    let (_, liquidation_threshold) = collateral_weights(bank, emode);
+    if values.borrowed_value == 0 || !position.is_collateral(bank) || liquidation_threshold == 0 {
+        return deposited;
+    }
+    let excess_value = values.liquidation_value.saturating_sub(values.borrowed_value);
+    calculate_amount(excess_value * 100 / liquidation_threshold as u128, price, bank.mint_decimals).min(deposited)
+}
+
+/// Price of the asset of `bank`, in the exponent of `price`, at which the user becomes liquidatable with every other
+/// price unchanged. `None` if no positive price makes the user liquidatable.
+pub fn calculate_liquidation_price(values: &UserValues, position: &Position, bank: &Bank, price: &Price, emode: Option<&EModeCategory>) -> Option<i64> {
+    let (_, liquidation_threshold) = collateral_weights(bank, emode);
+    let deposited_value = if position.is_collateral(bank) {
+        let deposited = bank.deposit_amount_for(position.deposit_shares);
+        calculate_value(deposited, price, bank.mint_decimals) * liquidation_threshold as u128 / 100
+    } else {
+        0
+    };
+    let borrowed_value = calculate_value(bank.borrow_amount_for(position.borrow_shares), price, bank.mint_decimals);
+
+    // Both sides of the health check scale linearly with the price of this asset, the rest of the positions stay fixed
+    let other_shortfall = values.borrowed_value.saturating_sub(borrowed_value) as i128 - values.liquidation_value.saturating_sub(deposited_value) as i128;
+    let net_value = deposited_value as i128 - borrowed_value as i128;
+    if net_value == 0 {
+        return None;
+    }
+    let liquidation_price = price.price as i128 * other_shortfall / net_value;
+    if liquidation_price <= 0 {
+        return None;
+    }
+    i64::try_from(liquidation_price).ok()
+}
+
+pub fn deserialize_account<T: AccountDeserialize + Owner>(info: &AccountInfo) -> Result<T> {
+    if *info.owner != T::owner() {
+        return Err(ErrorCode::InvalidRemainingAccounts.into());
+    }
+    T::try_deserialize(&mut &info.try_borrow_data()?[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL_DECIMALS: u8 = 9;
+    const USDC_DECIMALS: u8 = 6;
+
+    /// Price of `dollars` USD with a Pyth exponent of -8
+    fn price(dollars: i64) -> Price {
+        Price { price: dollars * 100_000_000, conf: 0, exponent: -8, publish_time: 0 }
+    }
+
+    /// A bank with deposit and borrow shares worth one token each
+    fn bank(mint_decimals: u8, liquidation_threshold: u64) -> Bank {
+        let mut bank = Bank::deserialize(&mut vec![0; Bank::INIT_SPACE].as_slice()).unwrap();
+        bank.mint_decimals = mint_decimals;
+        bank.liquidation_threshold = liquidation_threshold;
+        bank.liquidation_bonus = 5;
+        bank.liquidation_close_factor = 50;
+        bank.collateral_enabled = true;
+        (bank.total_deposits, bank.total_deposit_shares) = (1 << 50, 1 << 50);
+        (bank.total_borrowed, bank.total_borrowed_shares) = (1 << 40, 1 << 40);
+        bank
+    }
+
+    #[test]
+    fn liquidation_repays_the_close_factor_of_the_debt() {
+        let (usdc, sol) = (bank(USDC_DECIMALS, 80), bank(SOL_DECIMALS, 80));
+        // Half of $70 of debt, for $35 of SOL plus the 5% bonus
+        let liquidation = calculate_liquidation(70_000_000, 1_000_000_000, &usdc, &price(1), &sol, &price(100), None);
+        assert_eq!(liquidation, (35_000_000, 367_500_000));
+    }
+
+    #[test]
+    fn liquidation_is_limited_by_the_collateral() {
+        let (usdc, sol) = (bank(USDC_DECIMALS, 80), bank(SOL_DECIMALS, 80));
+        // $20 of SOL only covers $19.05 of debt with the bonus
+        let liquidation = calculate_liquidation(70_000_000, 200_000_000, &usdc, &price(1), &sol, &price(100), None);
+        assert_eq!(liquidation, (19_047_619, 200_000_000));
+    }
+
+    #[test]
+    fn liquidation_pays_the_emode_bonus() {
+        let usdc = bank(USDC_DECIMALS, 80);
+        let mut sol = bank(SOL_DECIMALS, 80);
+        sol.emode_category = 1;
+        let emode = EModeCategory { id: 1, max_ltv: 90, liquidation_threshold: 95, liquidation_bonus: 2 };
+        let liquidation = calculate_liquidation(70_000_000, 1_000_000_000, &usdc, &price(1), &sol, &price(100), Some(&emode));
+        assert_eq!(liquidation, (35_000_000, 357_000_000));
+
+        // Outside the category the bank's bonus applies
+        let emode = EModeCategory { id: 2, ..emode };
+        let liquidation = calculate_liquidation(70_000_000, 1_000_000_000, &usdc, &price(1), &sol, &price(100), Some(&emode));
+        assert_eq!(liquidation, (35_000_000, 367_500_000));
+    }
+
+    /// $100 of collateral with an 80% liquidation threshold against $40 of debt
+    fn values() -> UserValues {
+        UserValues { collateral_value: 100_000_000, borrowable_value: 70_000_000, liquidation_value: 80_000_000, borrowed_value: 40_000_000 }
+    }
+
+    fn position(deposit_shares: u64, borrow_shares: u64) -> Position {
+        Position { bank: Pubkey::new_unique(), deposit_shares, borrow_shares, collateral_disabled: false }
+    }
+
+    #[test]
+    fn max_withdrawable_keeps_the_user_above_the_threshold() {
+        let sol = bank(SOL_DECIMALS, 80);
+        let deposit = position(1_000_000_000, 0);
+        // $40 of liquidation value to spare is $50 of SOL at the 80% threshold
+        assert_eq!(calculate_max_withdrawable(&values(), &deposit, &sol, &price(100), None), 500_000_000);
+
+        // The whole deposit without borrows, or when it does not count as collateral
+        let no_borrows = UserValues { borrowed_value: 0, ..values() };
+        assert_eq!(calculate_max_withdrawable(&no_borrows, &deposit, &sol, &price(100), None), 1_000_000_000);
+        let excluded = Position { collateral_disabled: true, ..deposit };
+        assert_eq!(calculate_max_withdrawable(&values(), &excluded, &sol, &price(100), None), 1_000_000_000);
+
+        // Nothing from an unhealthy user
+        let unhealthy = UserValues { borrowed_value: 90_000_000, ..values() };
+        assert_eq!(calculate_max_withdrawable(&unhealthy, &deposit, &sol, &price(100), None), 0);
+    }
+
+    #[test]
+    fn liquidation_price_of_collateral_and_debt() {
+        // The SOL collateral's liquidation value falls to the $40 of debt at $50
+        let sol = bank(SOL_DECIMALS, 80);
+        assert_eq!(calculate_liquidation_price(&values(), &position(1_000_000_000, 0), &sol, &price(100), None), Some(50 * 100_000_000));
+
+        // The USDC debt grows to the $80 of liquidation value at $2
+        let usdc = bank(USDC_DECIMALS, 80);
+        assert_eq!(calculate_liquidation_price(&values(), &position(0, 40_000_000), &usdc, &price(1), None), Some(2 * 100_000_000));
+
+        // No price makes a user without borrows liquidatable
+        let no_borrows = UserValues { borrowed_value: 0, ..values() };
+        assert_eq!(calculate_liquidation_price(&no_borrows, &position(1_000_000_000, 0), &sol, &price(100), None), None);
+    }
+}
@@ -1,4 +1,8 @@
 use anchor_lang::prelude::*;
+use crate::constants::{BASIS_POINTS, MAX_POSITIONS, SECONDS_PER_YEAR};
+use crate::error::ErrorCode;
+use crate::events::InterestAccrued;
+use crate::risk::calculate_isolation_debt;
 
 #[account]
 #[derive(InitSpace)]
@@ -23,37 +27,598 @@ pub struct Bank {
     pub liquidation_close_factor: u64,
     /// Max percentage of collateral that can be borrowed
     pub max_ltv: u64,
-    /// Last updated timestamp
+    /// Timestamp interest was last accrued at
     pub last_updated: i64,
+    /// Borrow rate at full utilization, in basis points per year
     pub interest_rate: u64,
+    /// Borrow rate at zero utilization, in basis points per year
+    pub base_rate: u64,
+    /// Share of the deposits that is borrowed, in basis points, as of `last_updated`
+    pub utilization: u64,
+    /// Cumulative growth of a borrowed amount through accrued interest, scaled by `INDEX_SCALE`
+    pub borrow_index: u128,
+    /// Cumulative growth of a deposited amount through accrued interest, scaled by `INDEX_SCALE`
+    pub deposit_index: u128,
+    /// Fee charged on flash loans, in basis points of the borrowed amount
+    pub flash_loan_fee: u64,
+    /// Amount currently lent out by an unrepaid flash loan
+    pub flash_loan_amount: u64,
+    /// Maximum total deposits in tokens, 0 means no limit
+    pub deposit_limit: u64,
+    /// Maximum total borrows in tokens, 0 means no limit
+    pub borrow_limit: u64,
+    /// Actions paused for this bank, see the `PAUSE_*` flags
+    pub pause_flags: u8,
+    /// Whether the bank accepts new deposits and borrows, cleared when the admin delists it
+    pub listed: bool,
+    /// Authority proposed by the current authority, must accept before taking over
+    pub pending_authority: Pubkey,
+    /// Decimals of the bank's mint
+    pub mint_decimals: u8,
+    /// Whether the mint charges a Token-2022 transfer fee, so that less than the transferred amount arrives
+    pub has_transfer_fee: bool,
+    /// Pyth feed id of the asset's USD price
+    pub oracle_feed_id: [u8; 32],
+    /// Maximum age of the price in seconds
+    pub max_price_age: u64,
+    /// Collateral from this bank puts the user in isolation mode
+    pub isolated: bool,
+    /// Maximum debt of users isolated on this bank, in USD scaled by `VALUE_DECIMALS`
+    pub isolation_debt_ceiling: u64,
+    /// Current debt of users isolated on this bank, in USD scaled by `VALUE_DECIMALS`
+    pub isolation_debt: u64,
+    /// Whether users in isolation mode can borrow from this bank
+    pub borrowable_in_isolation: bool,
+    /// E-mode category the bank belongs to, 0 for none
+    pub emode_category: u8,
+    /// Whether deposits in this bank count as collateral
+    pub collateral_enabled: bool,
+    /// Whether users can borrow from this bank
+    pub borrow_enabled: bool,
+}
+
+impl Bank {
+    /// Deposit shares (receipt tokens) issued for depositing `amount` tokens. Shares rounded down to dust can
+    /// outlive the deposits, then they are worth nothing and the deposit is issued shares one for one.
+    pub fn deposit_shares_for(&self, amount: u64) -> u64 {
+        if self.total_deposit_shares == 0 || self.total_deposits == 0 {
+            return amount;
+        }
+        (amount as u128 * self.total_deposit_shares as u128 / self.total_deposits as u128) as u64
+    }
+
+    /// Deposit shares to burn for withdrawing `amount` tokens, rounded up in favour of the bank
+    pub fn deposit_shares_to_burn(&self, amount: u64) -> u64 {
+        if self.total_deposits == 0 {
+            return 0;
+        }
+        (amount as u128 * self.total_deposit_shares as u128).div_ceil(self.total_deposits as u128) as u64
+    }
+
+    /// Number of tokens that `shares` deposit shares can be redeemed for
+    pub fn deposit_amount_for(&self, shares: u64) -> u64 {
+        if self.total_deposit_shares == 0 {
+            return 0;
+        }
+        (shares as u128 * self.total_deposits as u128 / self.total_deposit_shares as u128) as u64
+    }
+
+    /// Borrow shares issued for borrowing `amount` tokens, rounded up in favour of the bank
+    pub fn borrow_shares_for(&self, amount: u64) -> u64 {
+        if self.total_borrowed_shares == 0 {
+            return amount;
+        }
+        (amount as u128 * self.total_borrowed_shares as u128).div_ceil(self.total_borrowed as u128) as u64
+    }
+
+    /// Borrow shares cancelled by repaying `amount` tokens
+    pub fn borrow_shares_to_repay(&self, amount: u64) -> u64 {
+        if self.total_borrowed == 0 {
+            return 0;
+        }
+        (amount as u128 * self.total_borrowed_shares as u128 / self.total_borrowed as u128) as u64
+    }
+
+    /// Number of tokens owed for `shares` borrow shares, rounded up in favour of the bank
+    pub fn borrow_amount_for(&self, shares: u64) -> u64 {
+        if self.total_borrowed_shares == 0 {
+            return 0;
+        }
+        (shares as u128 * self.total_borrowed as u128).div_ceil(self.total_borrowed_shares as u128) as u64
+    }
+
+    /// Credits a deposit of `amount` tokens to the bank's totals within its deposit limit.
+    /// Returns the deposit shares issued for it.
+    pub fn add_deposit(&mut self, amount: u64) -> Result<u64> {
+        let total_deposits = self.total_deposits.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        if self.deposit_limit != 0 && total_deposits > self.deposit_limit {
+            return Err(ErrorCode::DepositLimitExceeded.into());
+        }
+        let shares = self.deposit_shares_for(amount);
+        self.total_deposit_shares = self.total_deposit_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        self.total_deposits = total_deposits;
+        Ok(shares)
+    }
+
+    /// Borrowed share of the deposits, in basis points
+    pub fn utilization_rate(&self) -> u64 {
+        if self.total_deposits == 0 {
+            return 0;
+        }
+        (self.total_borrowed as u128 * BASIS_POINTS as u128 / self.total_deposits as u128).min(BASIS_POINTS as u128) as u64
+    }
+
+    /// Borrow rate in basis points per year, rising linearly from `base_rate` to `interest_rate` with utilization
+    pub fn borrow_rate(&self) -> Result<u64> {
+        let slope = self.interest_rate.saturating_sub(self.base_rate) as u128;
+        let utilization_rate = (slope * self.utilization_rate() as u128 / BASIS_POINTS as u128) as u64;
+        self.base_rate.checked_add(utilization_rate).ok_or(ErrorCode::MathOverflow.into())
+    }
+
+    /// Accrues interest on the borrows since `last_updated` and credits it to the depositors.
+    /// Returns the accrued interest.
+    pub fn accrue_interest(&mut self, now: i64) -> Result<u64> {
+        if now <= self.last_updated {
+            return Ok(0);
+        }
+        let elapsed = (now - self.last_updated) as u128;
+        let interest = self.total_borrowed as u128 * self.borrow_rate()? as u128 * elapsed
+            / (BASIS_POINTS as u128 * SECONDS_PER_YEAR as u128);
+        let interest = u64::try_from(interest).map_err(|_| ErrorCode::MathOverflow)?;
+
+        if interest > 0 {
+            let total_borrowed = self.total_borrowed.checked_add(interest).ok_or(ErrorCode::MathOverflow)?;
+            let total_deposits = self.total_deposits.checked_add(interest).ok_or(ErrorCode::MathOverflow)?;
+            self.borrow_index = self
+                .borrow_index
+                .checked_mul(total_borrowed as u128)
+                .ok_or(ErrorCode::MathOverflow)?
+                / self.total_borrowed as u128;
+            if self.total_deposits > 0 {
+                self.deposit_index = self
+                    .deposit_index
+                    .checked_mul(total_deposits as u128)
+                    .ok_or(ErrorCode::MathOverflow)?
+                    / self.total_deposits as u128;
+            }
+            self.total_borrowed = total_borrowed;
+            self.total_deposits = total_deposits;
+        }
+        self.utilization = self.utilization_rate();
+        self.last_updated = now;
+        Ok(interest)
+    }
+
+    /// Like `accrue_interest`, for the bank at `key` in an instruction, emitting `InterestAccrued` when interest accrued
+    pub fn accrue_interest_and_emit(&mut self, key: Pubkey, now: i64) -> Result<()> {
+        let interest = self.accrue_interest(now)?;
+        if interest > 0 {
+            emit!(InterestAccrued {
+                bank: key,
+                interest,
+                total_deposits: self.total_deposits,
+                total_borrowed: self.total_borrowed,
+                timestamp: now,
+            });
+        }
+        Ok(())
+    }
+
+    /// Applies the risk parameters of an executed `ConfigChange`
+    pub fn apply_risk_params(&mut self, params: &RiskParams) {
+        self.liquidation_threshold = params.liquidation_threshold;
+        self.liquidation_bonus = params.liquidation_bonus;
+        self.liquidation_close_factor = params.liquidation_close_factor;
+        self.max_ltv = params.max_ltv;
+        self.interest_rate = params.interest_rate;
+        self.base_rate = params.base_rate;
+        self.oracle_feed_id = params.oracle_feed_id;
+        self.max_price_age = params.max_price_age;
+        self.emode_category = params.emode_category;
+        self.collateral_enabled = params.collateral_enabled;
+        self.isolated = params.isolated;
+        self.isolation_debt_ceiling = params.isolation_debt_ceiling;
+        self.borrowable_in_isolation = params.borrowable_in_isolation;
+    }
+
+    /// Fails if the bank has been delisted by the protocol admin
+    pub fn check_listed(&self) -> Result<()> {
+        if !self.listed {
+            return Err(ErrorCode::BankNotListed.into());
+        }
+        Ok(())
+    }
+
+    /// Fails if `action` is paused for this bank or for the whole protocol
+    pub fn check_not_paused(&self, config: &ProtocolConfig, action: u8) -> Result<()> {
+        if (self.pause_flags | config.pause_flags) & action != 0 {
+            return Err(ErrorCode::ActionPaused.into());
+        }
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ProtocolConfig {
+    /// Authority to make changes to the protocol configuration
+    pub admin: Pubkey,
+    /// Key allowed to pause actions on the protocol and on any bank, but not to unpause them
+    pub guardian: Pubkey,
+    /// Actions paused for every bank, see the `PAUSE_*` flags
+    pub pause_flags: u8,
+    /// Admin proposed by the current admin, must accept before taking over
+    pub pending_admin: Pubkey,
+}
+
+/// Risk parameters of a bank that can only change through a timelocked `ConfigChange`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct RiskParams {
+    pub liquidation_threshold: u64,
+    pub liquidation_bonus: u64,
+    pub liquidation_close_factor: u64,
+    pub max_ltv: u64,
+    pub interest_rate: u64,
+    pub base_rate: u64,
+    pub oracle_feed_id: [u8; 32],
+    pub max_price_age: u64,
+    /// E-mode category the bank belongs to, 0 for none
+    pub emode_category: u8,
+    /// Whether deposits in the bank count as collateral
+    pub collateral_enabled: bool,
+    /// Collateral from the bank puts the user in isolation mode
+    pub isolated: bool,
+    /// Maximum debt of users isolated on the bank, in USD scaled by `VALUE_DECIMALS`
+    pub isolation_debt_ceiling: u64,
+    /// Whether users in isolation mode can borrow from the bank
+    pub borrowable_in_isolation: bool,
+}
+
+impl RiskParams {
+    /// Percentages must not exceed 100, borrowing must stop before the liquidation threshold
+    /// and the borrow rate must not fall with utilization. The liquidation bonus must leave a user at the
+    /// threshold with more collateral than the liquidator seizes, or every liquidation creates bad debt.
+    pub fn validate(&self) -> Result<()> {
+        if self.liquidation_threshold > 100
+            || self.liquidation_close_factor > 100
+            || self.liquidation_bonus > 100
+            || self.liquidation_threshold * (100 + self.liquidation_bonus) > 100 * 100
+            || self.max_ltv > self.liquidation_threshold
+            || self.base_rate > self.interest_rate
+        {
+            return Err(ErrorCode::InvalidBankConfig.into());
+        }
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ConfigChange {
+    /// Bank the change applies to
+    pub bank: Pubkey,
+    /// Account that paid for the queued change and receives the rent back
+    pub proposer: Pubkey,
+    /// Timestamp after which the change can be executed
+    pub eta: i64,
+    /// New risk parameters of the bank
+    pub params: RiskParams,
+}
+
+/// Group of correlated assets that can be borrowed against each other with higher LTV
+#[account]
+#[derive(InitSpace)]
+pub struct EModeCategory {
+    /// Id of the category, referenced by `Bank::emode_category` and `User::emode_category`
+    pub id: u8,
+    /// Max percentage of collateral in the category that can be borrowed
+    pub max_ltv: u64,
+    /// LTV at which a loan against collateral in the category can be liquidated
+    pub liquidation_threshold: u64,
+    /// Bonus percentage paid to liquidators of collateral in the category
+    pub liquidation_bonus: u64,
+}
+
+impl EModeCategory {
+    /// Same bounds as the risk parameters of a bank
+    pub fn validate(&self) -> Result<()> {
+        if self.id == 0
+            || self.liquidation_threshold > 100
+            || self.liquidation_bonus > 100
+            || self.liquidation_threshold * (100 + self.liquidation_bonus) > 100 * 100
+            || self.max_ltv > self.liquidation_threshold
+        {
+            return Err(ErrorCode::InvalidBankConfig.into());
+        }
+        Ok(())
+    }
+}
+
+/// Queued parameter change of an e-mode category
+#[account]
+#[derive(InitSpace)]
+pub struct EModeCategoryChange {
+    /// E-mode category the change applies to
+    pub emode_category: Pubkey,
+    /// Account that paid for the queued change and receives the rent back
+    pub proposer: Pubkey,
+    /// Timestamp after which the change can be executed
+    pub eta: i64,
+    pub max_ltv: u64,
+    pub liquidation_threshold: u64,
+    pub liquidation_bonus: u64,
+}
+
+/// A user's deposit and borrow in a single bank
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct Position {
+    /// Bank of the position, the default pubkey marks an unused slot
+    pub bank: Pubkey,
+    /// Deposit shares locked as collateral
+    pub deposit_shares: u64,
+    /// Borrow shares owed to the bank
+    pub borrow_shares: u64,
+    /// Set by the user to stop the deposit from counting as collateral
+    pub collateral_disabled: bool,
+}
+
+impl Position {
+    /// Whether the deposit counts towards the user's collateral
+    pub fn is_collateral(&self, bank: &Bank) -> bool {
+        bank.collateral_enabled && !self.collateral_disabled
+    }
+
+    /// Whether the deposit can be seized by a liquidation, which stops only when the user excluded it,
+    /// so that a bank disabling collateral cannot leave the debt it backed without anything to seize
+    pub fn is_seizable(&self) -> bool {
+        !self.collateral_disabled
+    }
 }
 
-// Challenge: How would you update the user state to save "all_deposited_assets" and "all_borrowed_assets" to accommodate for several asset listings?  
 #[account]
 #[derive(InitSpace)]
 pub struct User {
     /// Pubkey of the user's wallet 
     pub owner: Pubkey,
-    /// User's deposited tokens in the SOL bank
-    pub deposited_sol: u64,
-    /// User's deposited shares in the SOL bank 
-    pub deposited_sol_shares: u64,
-    /// User's borrowed tokens in the SOL bank
-    pub borrowed_sol: u64,
-    /// User's borrowed shares in the SOL bank
-    pub borrowed_sol_shares: u64, 
-    /// User's deposited tokens in the USDC bank
-    pub deposited_usdc: u64,
-    /// User's deposited shares in the USDC bank 
-    pub deposited_usdc_shares: u64, 
-    /// User's borrowed tokens in the USDC bank
-    pub borrowed_usdc: u64,
-    /// User's borrowed shares in the USDC bank
-    pub borrowed_usdc_shares: u64, 
-    /// USDC mint address
-    pub usdc_address: Pubkey,
+    /// Index of this sub-account among the owner's positions
+    pub sub_account: u8,
+    /// User's positions, one per bank
+    pub positions: [Position; MAX_POSITIONS],
+    /// Isolated bank the user's collateral is restricted to, the default pubkey when not in isolation mode
+    pub isolated_bank: Pubkey,
+    /// E-mode category selected by the user, 0 for none
+    pub emode_category: u8,
     /// Current health factor of the user
     pub health_factor: u64,
     /// Last updated timestamp
     pub last_updated: i64,
 }
+
+impl User {
+    pub fn position(&self, bank: &Pubkey) -> Option<&Position> {
+        self.positions.iter().find(|position| position.bank == *bank)
+    }
+
+    pub fn position_mut(&mut self, bank: &Pubkey) -> Option<&mut Position> {
+        self.positions.iter_mut().find(|position| position.bank == *bank)
+    }
+
+    /// Returns the position in `bank`, taking an unused slot if the user has none yet
+    pub fn get_or_create_position(&mut self, bank: &Pubkey) -> Result<&mut Position> {
+        let index = match self.positions.iter().position(|position| position.bank == *bank) {
+            Some(index) => index,
+            None => {
+                let index = self.positions.iter().position(|position| position.bank == Pubkey::default())
+                    .ok_or(ErrorCode::TooManyPositions)?;
+                self.positions[index].bank = *bank;
+                index
+            }
+        };
+        Ok(&mut self.positions[index])
+    }
+
+    /// Frees the slots of positions without deposits or borrows,
+    /// leaving isolation mode once the isolated collateral and all borrows are gone
+    pub fn close_empty_positions(&mut self) {
+        for position in self.positions.iter_mut() {
+            if position.deposit_shares == 0 && position.borrow_shares == 0 {
+                *position = Position::default();
+            }
+        }
+        if self.is_isolated() && self.position(&self.isolated_bank).is_none() && !self.has_borrows() {
+            self.isolated_bank = Pubkey::default();
+        }
+    }
+
+    /// Adds a borrow of `amount` tokens from `bank` to the user's position and the bank's totals, within the bank's
+    /// borrow limit and the isolation mode rules. `isolated_bank` is the user's isolated bank, required in isolation
+    /// mode to count the borrow against its debt ceiling. Returns the borrow shares issued for it.
+    pub fn add_borrow(
+        &mut self,
+        bank_key: &Pubkey,
+        bank: &mut Bank,
+        amount: u64,
+        isolated_bank: Option<(Pubkey, &mut Bank)>,
+    ) -> Result<u64> {
+        let total_borrowed = bank.total_borrowed.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        if bank.borrow_limit != 0 && total_borrowed > bank.borrow_limit {
+            return Err(ErrorCode::BorrowLimitExceeded.into());
+        }
+
+        // A user whose collateral is isolated can only borrow from banks whitelisted for isolation mode,
+        // and the debt of all users isolated on the same bank is capped by that bank's isolation debt ceiling
+        if self.is_isolated() {
+            if !bank.borrowable_in_isolation || *bank_key == self.isolated_bank {
+                return Err(ErrorCode::NotBorrowableInIsolation.into());
+            }
+            let isolated_bank = match isolated_bank {
+                Some((key, isolated_bank)) if key == self.isolated_bank => isolated_bank,
+                _ => return Err(ErrorCode::MissingIsolatedBank.into()),
+            };
+            let isolation_debt = isolated_bank.isolation_debt.saturating_add(calculate_isolation_debt(amount, bank.mint_decimals));
+            if isolation_debt > isolated_bank.isolation_debt_ceiling {
+                return Err(ErrorCode::IsolationDebtCeilingExceeded.into());
+            }
+            isolated_bank.isolation_debt = isolation_debt;
+        }
+
+        let shares = bank.borrow_shares_for(amount);
+        bank.total_borrowed_shares = bank.total_borrowed_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        bank.total_borrowed = total_borrowed;
+        let position = self.get_or_create_position(bank_key)?;
+        position.borrow_shares = position.borrow_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        Ok(shares)
+    }
+
+    /// Applies a liquidation repaying `repaid_amount` of the user's debt to `borrowed_bank` with `seized_amount` of
+    /// their collateral in `collateral_bank`, to the user's positions and the banks' totals. Repaying the whole debt
+    /// clears all its shares. Returns the repaid borrow shares and the seized deposit shares.
+    pub fn apply_liquidation(
+        &mut self,
+        borrowed_bank_key: &Pubkey,
+        borrowed_bank: &mut Bank,
+        repaid_amount: u64,
+        collateral_bank_key: &Pubkey,
+        collateral_bank: &mut Bank,
+        seized_amount: u64,
+    ) -> Result<(u64, u64)> {
+        let borrowed_shares = self.position(borrowed_bank_key).map_or(0, |position| position.borrow_shares);
+        let deposit_shares = self.position(collateral_bank_key).map_or(0, |position| position.deposit_shares);
+        let repaid_shares = if repaid_amount == borrowed_bank.borrow_amount_for(borrowed_shares) {
+            borrowed_shares
+        } else {
+            borrowed_bank.borrow_shares_to_repay(repaid_amount).min(borrowed_shares)
+        };
+        let seized_shares = collateral_bank.deposit_shares_to_burn(seized_amount).min(deposit_shares);
+        if let Some(position) = self.position_mut(borrowed_bank_key) {
+            position.borrow_shares -= repaid_shares;
+        }
+        if let Some(position) = self.position_mut(collateral_bank_key) {
+            position.deposit_shares -= seized_shares;
+        }
+        borrowed_bank.total_borrowed = borrowed_bank.total_borrowed.saturating_sub(repaid_amount);
+        borrowed_bank.total_borrowed_shares =
+            borrowed_bank.total_borrowed_shares.checked_sub(repaid_shares).ok_or(ErrorCode::MathOverflow)?;
+        collateral_bank.total_deposits = collateral_bank.total_deposits.checked_sub(seized_amount).ok_or(ErrorCode::MathOverflow)?;
+        collateral_bank.total_deposit_shares =
+            collateral_bank.total_deposit_shares.checked_sub(seized_shares).ok_or(ErrorCode::MathOverflow)?;
+
+        // An isolated user's only collateral is in the isolated bank, which tracks the isolation debt
+        if self.isolated_bank == *collateral_bank_key {
+            let repaid_debt = calculate_isolation_debt(repaid_amount, borrowed_bank.mint_decimals);
+            collateral_bank.isolation_debt = collateral_bank.isolation_debt.saturating_sub(repaid_debt);
+        }
+        self.close_empty_positions();
+        Ok((repaid_shares, seized_shares))
+    }
+
+    /// Checks that collateral from `bank` can be added, entering isolation mode for isolated banks.
+    /// Isolated collateral can only be used alone, by a user without any other position.
+    pub fn check_isolation(&mut self, bank_key: &Pubkey, bank: &Bank) -> Result<()> {
+        if bank.isolated {
+            if self.isolated_bank == *bank_key {
+                return Ok(());
+            }
+            if self.is_isolated() || self.active_positions().next().is_some() {
+                return Err(ErrorCode::IsolatedCollateral.into());
+            }
+            self.isolated_bank = *bank_key;
+        } else if self.is_isolated() {
+            return Err(ErrorCode::IsolatedCollateral.into());
+        }
+        Ok(())
+    }
+
+    pub fn active_positions(&self) -> impl Iterator<Item = &Position> {
+        self.positions.iter().filter(|position| position.bank != Pubkey::default())
+    }
+
+    pub fn has_borrows(&self) -> bool {
+        self.positions.iter().any(|position| position.borrow_shares > 0)
+    }
+
+    pub fn is_isolated(&self) -> bool {
+        self.isolated_bank != Pubkey::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bank(total_deposits: u64, total_deposit_shares: u64) -> Bank {
+        let mut bank = Bank::deserialize(&mut vec![0; Bank::INIT_SPACE].as_slice()).unwrap();
+        bank.total_deposits = total_deposits;
+        bank.total_deposit_shares = total_deposit_shares;
+        bank
+    }
+
+    #[test]
+    fn deposit_shares_follow_the_exchange_rate() {
+        assert_eq!(bank(0, 0).deposit_shares_for(1_000), 1_000);
+        assert_eq!(bank(2_000, 1_000).deposit_shares_for(1_000), 500);
+        assert_eq!(bank(2_000, 1_000).deposit_shares_to_burn(1_001), 501);
+    }
+
+    #[test]
+    fn deposit_into_a_bank_emptied_down_to_dust_shares() {
+        // A withdrawal or a seizure rounded in favour of the bank can leave shares without deposits
+        let bank = bank(0, 3);
+        assert_eq!(bank.deposit_shares_for(1_000), 1_000);
+        assert_eq!(bank.deposit_shares_to_burn(1_000), 0);
+        assert_eq!(bank.deposit_amount_for(3), 0);
+    }
+
+    #[test]
+    fn interest_overflowing_the_totals_is_an_error() {
+        let mut bank = bank(1 << 63, 1 << 63);
+        bank.total_borrowed = 1 << 63;
+        bank.total_borrowed_shares = 1 << 63;
+        bank.interest_rate = BASIS_POINTS;
+        bank.borrow_index = 1 << 64;
+        bank.deposit_index = 1 << 64;
+
+        // A year at 100% doubles the borrows, more than a u64 holds
+        let overflow = Err(ErrorCode::MathOverflow.into());
+        assert_eq!(bank.accrue_interest(SECONDS_PER_YEAR as i64), overflow);
+        // After a hundred years so does the interest itself
+        assert_eq!(bank.accrue_interest(100 * SECONDS_PER_YEAR as i64), overflow);
+        assert_eq!((bank.total_borrowed, bank.total_deposits, bank.last_updated), (1 << 63, 1 << 63, 0));
+
+        let interest = bank.accrue_interest(SECONDS_PER_YEAR as i64 / 2).unwrap();
+        assert_eq!(interest, 1 << 62);
+        assert_eq!((bank.total_borrowed, bank.total_deposits), (3 << 62, 3 << 62));
+    }
+
+    #[test]
+    fn deposits_stop_at_the_deposit_limit() {
+        let mut bank = bank(2_000, 1_000);
+        bank.deposit_limit = 3_000;
+        assert_eq!(bank.add_deposit(1_001), Err(ErrorCode::DepositLimitExceeded.into()));
+        assert_eq!(bank.add_deposit(1_000), Ok(500));
+        assert_eq!((bank.total_deposits, bank.total_deposit_shares), (3_000, 1_500));
+    }
+
+    #[test]
+    fn isolated_borrows_count_against_the_ceiling() {
+        let (isolated_key, bank_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut isolated_bank = bank(0, 0);
+        isolated_bank.isolation_debt_ceiling = 2_000_000;
+        let mut bank = bank(0, 0);
+        bank.borrowable_in_isolation = true;
+        bank.mint_decimals = 6;
+        let mut user = User::deserialize(&mut vec![0; User::INIT_SPACE].as_slice()).unwrap();
+        user.isolated_bank = isolated_key;
+
+        let missing = Err(ErrorCode::MissingIsolatedBank.into());
+        assert_eq!(user.add_borrow(&bank_key, &mut bank, 1_000_000, None), missing);
+        assert_eq!(user.add_borrow(&bank_key, &mut bank, 1_000_000, Some((bank_key, &mut isolated_bank))), missing);
+        let exceeded = Err(ErrorCode::IsolationDebtCeilingExceeded.into());
+        assert_eq!(user.add_borrow(&bank_key, &mut bank, 3_000_000, Some((isolated_key, &mut isolated_bank))), exceeded);
+
+        assert_eq!(user.add_borrow(&bank_key, &mut bank, 1_000_000, Some((isolated_key, &mut isolated_bank))), Ok(1_000_000));
+        assert_eq!(isolated_bank.isolation_debt, 1_000_000);
+        assert_eq!((bank.total_borrowed, bank.total_borrowed_shares), (1_000_000, 1_000_000));
+        assert_eq!(user.position(&bank_key).unwrap().borrow_shares, 1_000_000);
+    }
+}
@@ -0,0 +1,312 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use lending::constants::{CONFIG_CHANGE_DELAY, DEFAULT_LIQUIDATION_BONUS, MAXIMUM_AGE, PAUSE_BORROW, PAUSE_DEPOSIT};
+use lending::error::ErrorCode;
+use lending::events::{
+    BankConfigUpdated, ConfigChangeCancelled, ConfigChangeQueued, EModeCategoryChangeCancelled, EModeCategoryChangeQueued,
+    EModeCategoryUpdated, ProtocolConfigUpdated,
+};
+use lending::state::{ConfigChange, EModeCategory, ProtocolConfig, RiskParams};
+use lending_client::{instructions, pda};
+
+fn protocol_config(env: &TestEnv) -> ProtocolConfig {
+    env.account(&pda::config())
+}
+
+#[test]
+fn init_protocol_config_requires_upgrade_authority() {
+    let mut env = TestEnv::new();
+    let config = protocol_config(&env);
+    assert_eq!(config.admin, env.admin);
+    assert_eq!(config.guardian, env.guardian);
+
+    // A fresh deployment whose config is squatted by someone else
+    let admin = Pubkey::new_unique();
+    let squatter = Pubkey::new_unique();
+    env.runtime = Runtime::new(admin);
+    env.runtime.airdrop(&squatter, LAMPORTS_PER_SOL);
+    assert_error(env.process(&[instructions::init_protocol_config(&squatter, squatter)], &[squatter]), ErrorCode::Unauthorized);
+}
+
+#[test]
+fn admin_transfer_and_guardian() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let new_admin = env.wallet();
+    let guardian = env.wallet();
+
+    assert_error(env.process(&[instructions::set_guardian(&new_admin, guardian)], &[new_admin]), ErrorCode::Unauthorized);
+    let metadata = env.send(&[instructions::set_guardian(&admin, guardian)], &[admin]);
+    assert_eq!(protocol_config(&env).guardian, guardian);
+    assert_eq!(events::<ProtocolConfigUpdated>(&metadata)[0].guardian, guardian);
+
+    let metadata = env.send(&[instructions::propose_admin(&admin, new_admin)], &[admin]);
+    assert_eq!(events::<ProtocolConfigUpdated>(&metadata)[0].pending_admin, new_admin);
+    assert_error(env.process(&[instructions::accept_admin(&guardian)], &[guardian]), ErrorCode::Unauthorized);
+    let metadata = env.send(&[instructions::accept_admin(&new_admin)], &[new_admin]);
+    let event = &events::<ProtocolConfigUpdated>(&metadata)[0];
+    assert_eq!((event.admin, event.pending_admin), (new_admin, Pubkey::default()));
+    let config = protocol_config(&env);
+    assert_eq!(config.admin, new_admin);
+    assert_eq!(config.pending_admin, Pubkey::default());
+    assert_error(env.process(&[instructions::propose_admin(&admin, admin)], &[admin]), ErrorCode::Unauthorized);
+}
+
+#[test]
+fn guardian_can_only_add_pauses() {
+    let mut env = TestEnv::new();
+    let (admin, guardian) = (env.admin, env.guardian);
+    let mint = env.create_mint(6, &spl_token::ID);
+    env.init_bank(&mint, 80, 70, usd(1));
+    let user = env.wallet();
+    env.mint_to(&user, &mint, 100);
+
+    env.send(&[instructions::set_protocol_pause(&guardian, PAUSE_DEPOSIT)], &[guardian]);
+    assert_error(
+        env.process(&[instructions::deposit(&user, &mint, &env.price_update(&mint), &spl_token::ID, 100)], &[user]),
+        ErrorCode::ActionPaused,
+    );
+    assert_error(env.process(&[instructions::set_protocol_pause(&guardian, 0)], &[guardian]), ErrorCode::Unauthorized);
+    assert_error(env.process(&[instructions::set_protocol_pause(&admin, 1 << 7)], &[admin]), ErrorCode::InvalidPauseFlags);
+    env.send(&[instructions::set_protocol_pause(&admin, 0)], &[admin]);
+
+    env.send(&[instructions::set_bank_pause(&guardian, &mint, PAUSE_DEPOSIT | PAUSE_BORROW)], &[guardian]);
+    assert_error(
+        env.process(&[instructions::deposit(&user, &mint, &env.price_update(&mint), &spl_token::ID, 100)], &[user]),
+        ErrorCode::ActionPaused,
+    );
+    assert_error(env.process(&[instructions::set_bank_pause(&guardian, &mint, PAUSE_BORROW)], &[guardian]), ErrorCode::Unauthorized);
+    env.send(&[instructions::set_bank_pause(&admin, &mint, 0)], &[admin]);
+    env.send(&[instructions::deposit(&user, &mint, &env.price_update(&mint), &spl_token::ID, 100)], &[user]);
+}
+
+#[test]
+fn init_bank_defaults() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let mint = env.create_mint(9, &spl_token::ID);
+    let outsider = env.wallet();
+    assert_error(
+        env.process(&[instructions::init_bank(&outsider, &mint, &spl_token::ID, 80, 70, feed_id(&mint))], &[outsider]),
+        ErrorCode::Unauthorized,
+    );
+    for (liquidation_threshold, max_ltv) in [(70, 80), (101, 70)] {
+        assert_error(
+            env.process(
+                &[instructions::init_bank(&admin, &mint, &spl_token::ID, liquidation_threshold, max_ltv, feed_id(&mint))],
+                &[admin],
+            ),
+            ErrorCode::InvalidBankConfig,
+        );
+    }
+
+    let metadata = env.send(&[instructions::init_bank(&admin, &mint, &spl_token::ID, 80, 70, feed_id(&mint))], &[admin]);
+    let bank = env.bank(&mint);
+    assert_eq!(bank.authority, admin);
+    assert_eq!(bank.mint_address, mint);
+    assert_eq!(bank.mint_decimals, 9);
+    assert_eq!((bank.liquidation_threshold, bank.max_ltv), (80, 70));
+    assert_eq!(bank.liquidation_bonus, DEFAULT_LIQUIDATION_BONUS);
+    assert_eq!(bank.max_price_age, MAXIMUM_AGE);
+    assert_eq!(bank.last_updated, env.now());
+    assert!(bank.listed && bank.collateral_enabled && bank.borrow_enabled);
+    assert_eq!(env.treasury_balance(&mint), 0);
+
+    let events = events::<BankConfigUpdated>(&metadata);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].bank, pda::bank(&mint));
+    assert_eq!(events[0].base_rate, bank.base_rate);
+}
+
+#[test]
+fn delisted_bank_rejects_deposits() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let mint = env.create_mint(6, &spl_token::ID);
+    env.init_bank(&mint, 80, 70, usd(1));
+    let user = env.wallet();
+    env.mint_to(&user, &mint, 100);
+
+    env.send(&[instructions::delist_bank(&admin, &mint)], &[admin]);
+    assert!(!env.bank(&mint).listed);
+    assert_error(
+        env.process(&[instructions::deposit(&user, &mint, &env.price_update(&mint), &spl_token::ID, 100)], &[user]),
+        ErrorCode::BankNotListed,
+    );
+    assert_error(env.process(&[instructions::list_bank(&user, &mint)], &[user]), ErrorCode::Unauthorized);
+    env.send(&[instructions::list_bank(&admin, &mint)], &[admin]);
+    env.send(&[instructions::deposit(&user, &mint, &env.price_update(&mint), &spl_token::ID, 100)], &[user]);
+}
+
+#[test]
+fn bank_authority_transfer() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let mint = env.create_mint(6, &spl_token::ID);
+    env.init_bank(&mint, 80, 70, usd(1));
+    let authority = env.wallet();
+    let next_authority = env.wallet();
+
+    env.send(&[instructions::set_bank_authority(&admin, &mint, authority)], &[admin]);
+    assert_eq!(env.bank(&mint).authority, authority);
+    assert_error(env.process(&[instructions::set_flash_loan_fee(&admin, &mint, 10)], &[admin]), ErrorCode::Unauthorized);
+
+    env.send(&[instructions::propose_bank_authority(&authority, &mint, next_authority)], &[authority]);
+    assert_error(env.process(&[instructions::accept_bank_authority(&admin, &mint)], &[admin]), ErrorCode::Unauthorized);
+    env.send(&[instructions::accept_bank_authority(&next_authority, &mint)], &[next_authority]);
+    let bank = env.bank(&mint);
+    assert_eq!(bank.authority, next_authority);
+    assert_eq!(bank.pending_authority, Pubkey::default());
+}
+
+#[test]
+fn bank_settings() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let mint = env.create_mint(6, &spl_token::ID);
+    env.init_bank(&mint, 80, 70, usd(1));
+
+    assert_error(env.process(&[instructions::set_flash_loan_fee(&admin, &mint, 10_001)], &[admin]), ErrorCode::InvalidBankConfig);
+    env.send(
+        &[
+            instructions::set_flash_loan_fee(&admin, &mint, 30),
+            instructions::set_bank_limits(&admin, &mint, 1_000, 500),
+            instructions::set_borrow_enabled(&admin, &mint, false),
+        ],
+        &[admin],
+    );
+    let bank = env.bank(&mint);
+    assert_eq!(bank.flash_loan_fee, 30);
+    assert_eq!((bank.deposit_limit, bank.borrow_limit), (1_000, 500));
+    assert!(bank.collateral_enabled && !bank.borrow_enabled);
+
+    // Disabling collateral and isolating the bank can make users liquidatable, so they wait for the timelock
+    let params = RiskParams {
+        collateral_enabled: false,
+        isolated: true,
+        isolation_debt_ceiling: 10_000,
+        borrowable_in_isolation: false,
+        ..env.risk_params(&mint)
+    };
+    env.send(&[instructions::queue_config_change(&admin, &mint, params)], &[admin]);
+    let bank = env.bank(&mint);
+    assert!(bank.collateral_enabled && !bank.isolated);
+    env.warp(CONFIG_CHANGE_DELAY);
+    env.send(&[instructions::execute_config_change(&mint, &admin)], &[admin]);
+    let bank = env.bank(&mint);
+    assert!(!bank.collateral_enabled && bank.isolated && !bank.borrowable_in_isolation);
+    assert_eq!(bank.isolation_debt_ceiling, 10_000);
+
+    let user = env.wallet();
+    env.mint_to(&user, &mint, 2_000);
+    env.send(&[instructions::deposit(&user, &mint, &env.price_update(&mint), &spl_token::ID, 1_000)], &[user]);
+    assert_error(
+        env.process(&[instructions::deposit(&user, &mint, &env.price_update(&mint), &spl_token::ID, 1)], &[user]),
+        ErrorCode::DepositLimitExceeded,
+    );
+
+    env.send(&[instructions::init_user(&user, 0)], &[user]);
+    assert_error(
+        env.process(&[instructions::deposit_collateral(&user, &mint, &spl_token::ID, 0, 1_000)], &[user]),
+        ErrorCode::CollateralDisabled,
+    );
+}
+
+#[test]
+fn config_change_timelock() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let mint = env.create_mint(6, &spl_token::ID);
+    env.init_bank(&mint, 80, 70, usd(1));
+    let params = RiskParams { interest_rate: 1_000, base_rate: 200, liquidation_bonus: 8, ..env.risk_params(&mint) };
+
+    let invalid = RiskParams { max_ltv: 90, ..params.clone() };
+    assert_error(env.process(&[instructions::queue_config_change(&admin, &mint, invalid)], &[admin]), ErrorCode::InvalidBankConfig);
+    // Repaying all the debt of a user at the 80% threshold with a 26% bonus would seize more than their collateral
+    let invalid = RiskParams { liquidation_bonus: 26, ..params.clone() };
+    assert_error(env.process(&[instructions::queue_config_change(&admin, &mint, invalid)], &[admin]), ErrorCode::InvalidBankConfig);
+    let outsider = env.wallet();
+    assert_error(
+        env.process(&[instructions::queue_config_change(&outsider, &mint, params.clone())], &[outsider]),
+        ErrorCode::Unauthorized,
+    );
+
+    let metadata = env.send(&[instructions::queue_config_change(&admin, &mint, params.clone())], &[admin]);
+    let config_change: ConfigChange = env.account(&pda::config_change(&pda::bank(&mint)));
+    assert_eq!(config_change.eta, env.now() + CONFIG_CHANGE_DELAY);
+    let event = &events::<ConfigChangeQueued>(&metadata)[0];
+    assert_eq!((event.bank, event.proposer, event.eta), (pda::bank(&mint), admin, config_change.eta));
+    assert_eq!(event.params.interest_rate, 1_000);
+    assert_error(env.process(&[instructions::execute_config_change(&mint, &admin)], &[]), ErrorCode::TimelockNotExpired);
+
+    // Cancelling returns the rent and a new change can be queued
+    let lamports = env.runtime.lamports(&admin);
+    let metadata = env.send(&[instructions::cancel_config_change(&admin, &mint, &admin)], &[admin]);
+    assert_eq!(events::<ConfigChangeCancelled>(&metadata)[0].bank, pda::bank(&mint));
+    assert!(env.runtime.account(&pda::config_change(&pda::bank(&mint))).is_none());
+    assert!(env.runtime.lamports(&admin) > lamports);
+
+    // Anyone can execute once the delay has passed
+    env.send(&[instructions::queue_config_change(&admin, &mint, params)], &[admin]);
+    env.warp(CONFIG_CHANGE_DELAY);
+    env.send(&[instructions::execute_config_change(&mint, &admin)], &[]);
+    let bank = env.bank(&mint);
+    assert_eq!((bank.interest_rate, bank.base_rate, bank.liquidation_bonus), (1_000, 200, 8));
+    assert!(env.runtime.account(&pda::config_change(&pda::bank(&mint))).is_none());
+}
+
+#[test]
+fn emode_categories() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let mint = env.create_mint(6, &spl_token::ID);
+    env.init_bank(&mint, 80, 70, usd(1));
+    let outsider = env.wallet();
+
+    assert_error(env.process(&[instructions::init_emode_category(&admin, 1, 95, 90, 2)], &[admin]), ErrorCode::InvalidBankConfig);
+    assert_error(env.process(&[instructions::init_emode_category(&admin, 1, 90, 95, 6)], &[admin]), ErrorCode::InvalidBankConfig);
+    assert_error(env.process(&[instructions::init_emode_category(&outsider, 1, 90, 95, 2)], &[outsider]), ErrorCode::Unauthorized);
+    let metadata = env.send(&[instructions::init_emode_category(&admin, 1, 90, 95, 2)], &[admin]);
+    assert_eq!(events::<EModeCategoryUpdated>(&metadata)[0].max_ltv, 90);
+
+    // Parameter changes go through the timelock like those of a bank
+    assert_error(
+        env.process(&[instructions::queue_emode_category_change(&admin, 1, 97, 96, 3)], &[admin]),
+        ErrorCode::InvalidBankConfig,
+    );
+    assert_error(
+        env.process(&[instructions::queue_emode_category_change(&outsider, 1, 92, 96, 3)], &[outsider]),
+        ErrorCode::Unauthorized,
+    );
+    let metadata = env.send(&[instructions::queue_emode_category_change(&admin, 1, 92, 96, 3)], &[admin]);
+    let event = &events::<EModeCategoryChangeQueued>(&metadata)[0];
+    assert_eq!((event.emode_category, event.eta), (pda::emode_category(1), env.now() + CONFIG_CHANGE_DELAY));
+    assert_eq!((event.max_ltv, event.liquidation_threshold, event.liquidation_bonus), (92, 96, 3));
+    assert_error(env.process(&[instructions::execute_emode_category_change(1, &admin)], &[]), ErrorCode::TimelockNotExpired);
+    assert_error(
+        env.process(&[instructions::cancel_emode_category_change(&outsider, 1, &admin)], &[outsider]),
+        ErrorCode::Unauthorized,
+    );
+    let metadata = env.send(&[instructions::cancel_emode_category_change(&admin, 1, &admin)], &[admin]);
+    assert_eq!(events::<EModeCategoryChangeCancelled>(&metadata)[0].proposer, admin);
+    assert!(env.runtime.account(&pda::config_change(&pda::emode_category(1))).is_none());
+
+    env.send(&[instructions::queue_emode_category_change(&admin, 1, 92, 96, 3)], &[admin]);
+    env.warp(CONFIG_CHANGE_DELAY);
+    let metadata = env.send(&[instructions::execute_emode_category_change(1, &admin)], &[]);
+    let category: EModeCategory = env.account(&pda::emode_category(1));
+    assert_eq!((category.id, category.max_ltv, category.liquidation_threshold, category.liquidation_bonus), (1, 92, 96, 3));
+    let events = events::<EModeCategoryUpdated>(&metadata);
+    assert_eq!((events[0].emode_category, events[0].liquidation_threshold), (pda::emode_category(1), 96));
+
+    // Bank membership is one of the bank's risk parameters
+    let params = RiskParams { emode_category: 1, ..env.risk_params(&mint) };
+    env.send(&[instructions::queue_config_change(&admin, &mint, params)], &[admin]);
+    assert_eq!(env.bank(&mint).emode_category, 0);
+    env.warp(CONFIG_CHANGE_DELAY);
+    env.send(&[instructions::execute_config_change(&mint, &admin)], &[]);
+    assert_eq!(env.bank(&mint).emode_category, 1);
+}
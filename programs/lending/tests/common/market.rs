@@ -0,0 +1,109 @@
+//! A small two-bank market with several users, shared by the property tests and the fuzz target,
+//! and the accounting invariants checked after every step.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use lending::instructions::UserPositionView;
+use lending::risk::HEALTHY_HEALTH_FACTOR;
+use lending::state::RiskParams;
+use lending_client::{instructions, pda};
+
+use super::*;
+
+pub const USERS: usize = 3;
+
+/// Tokens each user and the liquidator start with, in both mints
+pub const STARTING_BALANCE: u64 = 10_000_000_000_000;
+
+/// A USDC and a SOL bank charging interest with liquidity from a supplier, users holding both tokens
+/// with some of them deposited as collateral, and a liquidator
+pub struct Market {
+    pub env: TestEnv,
+    pub mints: [Pubkey; 2],
+    pub users: Vec<Pubkey>,
+    pub liquidator: Pubkey,
+}
+
+impl Market {
+    pub fn new() -> Self {
+        let mut env = TestEnv::new();
+        let usdc = env.create_mint(6, &spl_token::ID);
+        let sol = env.create_mint(9, &spl_token::ID);
+        env.init_bank(&usdc, 80, 70, usd(1));
+        env.init_bank(&sol, 80, 70, usd(100));
+        for mint in [usdc, sol] {
+            let params = RiskParams { interest_rate: 5_000, base_rate: 200, ..env.risk_params(&mint) };
+            env.set_risk_params(&mint, params);
+        }
+
+        env.supply(&usdc, 100_000_000_000);
+        env.supply(&sol, 1_000 * LAMPORTS_PER_SOL);
+
+        let users: Vec<Pubkey> = (0..USERS).map(|_| env.wallet()).collect();
+        let liquidator = env.wallet();
+        for wallet in users.iter().chain([&liquidator]) {
+            env.send(&[instructions::init_user(wallet, 0)], &[*wallet]);
+            env.mint_to(wallet, &usdc, STARTING_BALANCE);
+            env.mint_to(wallet, &sol, STARTING_BALANCE);
+        }
+        // $1000 of USDC collateral for the first user, of SOL for the others
+        env.deposit_collateral(&users[0], 0, &usdc, 1_000_000_000);
+        for user in &users[1..] {
+            env.deposit_collateral(user, 0, &sol, 10 * LAMPORTS_PER_SOL);
+        }
+        Self { env, mints: [usdc, sol], users, liquidator }
+    }
+
+    /// Liquidation of a user's collateral in `collateral_bank` for their borrow from `borrowed_bank` by the liquidator
+    pub fn liquidate(&mut self, user: usize, collateral_bank: usize, borrowed_bank: usize) -> Result<TransactionMetadata, TransactionError> {
+        let owner = self.users[user];
+        let remaining_accounts = self.env.health_accounts(&owner, 0);
+        let ix = instructions::liquidate(
+            &self.liquidator,
+            &pda::user(&owner, 0),
+            &self.mints[collateral_bank],
+            &self.mints[borrowed_bank],
+            &spl_token::ID,
+            &remaining_accounts,
+        );
+        self.env.process(&[ix], &[self.liquidator])
+    }
+
+    /// A user's own withdrawal or borrow never leaves them liquidatable.
+    /// Users without borrows are always healthy, and may have stale prices the view would reject.
+    pub fn assert_healthy(&mut self, user: usize) {
+        let owner = self.users[user];
+        if !self.env.user(&owner, 0).has_borrows() {
+            return;
+        }
+        let health_accounts = self.env.health_accounts(&owner, 0);
+        let metadata = self.env.send(&[instructions::get_user_position(&pda::user(&owner, 0), &health_accounts)], &[]);
+        let view: UserPositionView = return_data(&metadata);
+        assert!(view.health_factor >= HEALTHY_HEALTH_FACTOR, "user {user} left with health factor {}", view.health_factor);
+    }
+
+    pub fn assert_invariants(&self) {
+        let accounts: Vec<_> = self.users.iter().chain([&self.liquidator]).map(|owner| self.env.user(owner, 0)).collect();
+        for mint in &self.mints {
+            let bank = self.env.bank(mint);
+            // Every deposited token not lent out is held by the treasury
+            let treasury = self.env.treasury_balance(mint);
+            assert!(
+                treasury >= bank.total_deposits.saturating_sub(bank.total_borrowed),
+                "treasury {treasury} below deposits {} minus borrows {}",
+                bank.total_deposits,
+                bank.total_borrowed,
+            );
+
+            // Shares are either receipt tokens or collateral and borrows of a user
+            let positions: Vec<_> = accounts.iter().filter_map(|user| user.position(&pda::bank(mint))).collect();
+            let collateral_shares: u64 = positions.iter().map(|position| position.deposit_shares).sum();
+            let borrow_shares: u64 = positions.iter().map(|position| position.borrow_shares).sum();
+            assert_eq!(self.env.receipt_supply(mint) + collateral_shares, bank.total_deposit_shares);
+            assert_eq!(borrow_shares, bank.total_borrowed_shares);
+            if bank.total_borrowed_shares == 0 {
+                assert_eq!(bank.total_borrowed, 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,431 @@
+//! Fixtures shared by the integration tests: a runtime with the program deployed and the protocol
+//! config initialized, mints and token accounts written directly, synthetic Pyth `PriceUpdateV2`
+//! accounts and a clock that can be warped.
+
+#![allow(dead_code)]
+
+pub mod market;
+pub mod runtime;
+
+use std::collections::HashMap;
+
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::instruction::{Instruction, InstructionError};
+use anchor_lang::solana_program::program_error::ProgramError;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::rent::Rent;
+use anchor_lang::solana_program::system_program;
+use anchor_lang::{AccountDeserialize, AnchorDeserialize, Discriminator};
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeAmount, TransferFeeConfig};
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, BaseStateWithExtensionsMut, ExtensionType, StateWithExtensions, StateWithExtensionsMut,
+};
+use lending::constants::CONFIG_CHANGE_DELAY;
+use lending::state::{Bank, RiskParams, User};
+use lending_client::mock_oracle::{MockPriceUpdate, PRICE_UPDATE_OWNER};
+use lending_client::{instructions, pda};
+
+pub use runtime::{Runtime, TestAccount, TransactionError, TransactionMetadata};
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+/// Price of an asset in USD as a Pyth price with exponent `PRICE_EXPONENT`
+pub const PRICE_EXPONENT: i32 = -8;
+
+pub fn usd(dollars: i64) -> i64 {
+    dollars * 10i64.pow(PRICE_EXPONENT.unsigned_abs())
+}
+
+pub struct TestEnv {
+    pub runtime: Runtime,
+    pub admin: Pubkey,
+    pub guardian: Pubkey,
+    /// Price update account and current price update of each mint with a bank
+    prices: HashMap<Pubkey, (Pubkey, MockPriceUpdate)>,
+}
+
+impl TestEnv {
+    /// The program deployed by `admin`, with the protocol config initialized
+    pub fn new() -> Self {
+        let admin = Pubkey::new_unique();
+        let mut env = Self { runtime: Runtime::new(admin), admin, guardian: Pubkey::new_unique(), prices: HashMap::new() };
+        env.runtime.airdrop(&admin, 1_000 * LAMPORTS_PER_SOL);
+        env.send(&[instructions::init_protocol_config(&admin, env.guardian)], &[admin]);
+        env
+    }
+
+    pub fn process(&mut self, instructions: &[Instruction], signers: &[Pubkey]) -> Result<TransactionMetadata, TransactionError> {
+        self.runtime.process_transaction(instructions, signers)
+    }
+
+    /// Processes the transaction, panicking with its logs if it fails
+    pub fn send(&mut self, instructions: &[Instruction], signers: &[Pubkey]) -> TransactionMetadata {
+        match self.process(instructions, signers) {
+            Ok(metadata) => metadata,
+            Err(err) => panic!("transaction failed at instruction {}: {}\n{}", err.index, err.error, err.logs.join("\n")),
+        }
+    }
+
+    /// A funded wallet
+    pub fn wallet(&mut self) -> Pubkey {
+        let wallet = Pubkey::new_unique();
+        self.runtime.airdrop(&wallet, 100 * LAMPORTS_PER_SOL);
+        wallet
+    }
+
+    /// Moves the clock forward by `seconds`, republishing every price at the new time
+    pub fn warp(&mut self, seconds: i64) {
+        self.runtime.warp(seconds);
+        let now = self.now();
+        let prices: Vec<(Pubkey, MockPriceUpdate)> = self.prices.iter().map(|(mint, (_, update))| (*mint, update.clone())).collect();
+        for (mint, update) in prices {
+            self.set_price_update(&mint, update.publish_time(now).prev_publish_time(now));
+        }
+    }
+
+    pub fn now(&self) -> i64 {
+        self.runtime.clock().unix_timestamp
+    }
+
+    // Tokens
+
+    pub fn create_mint(&mut self, decimals: u8, token_program: &Pubkey) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        self.write_mint(mint, decimals, token_program);
+        mint
+    }
+
+    /// A Token-2022 mint withholding `fee_bps` of every transfer, up to `maximum_fee` tokens
+    pub fn create_transfer_fee_mint(&mut self, decimals: u8, fee_bps: u16, maximum_fee: u64) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        let len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[ExtensionType::TransferFeeConfig]).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data).unwrap();
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: fee_bps.into(),
+        };
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.older_transfer_fee = transfer_fee;
+        config.newer_transfer_fee = transfer_fee;
+        state.base = spl_token_2022::state::Mint {
+            mint_authority: Some(self.admin).into(),
+            supply: 0,
+            decimals,
+            is_initialized: true,
+            freeze_authority: None.into(),
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        self.runtime.set_account(mint, TestAccount {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: spl_token_2022::ID,
+            executable: false,
+        });
+        mint
+    }
+
+    /// The native mint, for the SOL instructions
+    pub fn native_mint(&mut self) -> Pubkey {
+        let mint = spl_token::native_mint::ID;
+        if self.runtime.account(&mint).is_none() {
+            self.write_mint(mint, spl_token::native_mint::DECIMALS, &spl_token::ID);
+        }
+        mint
+    }
+
+    fn write_mint(&mut self, mint: Pubkey, decimals: u8, token_program: &Pubkey) {
+        let state = spl_token::state::Mint {
+            mint_authority: Some(self.admin).into(),
+            supply: 0,
+            decimals,
+            is_initialized: true,
+            freeze_authority: None.into(),
+        };
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        state.pack_into_slice(&mut data);
+        self.runtime.set_account(mint, TestAccount {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: *token_program,
+            executable: false,
+        });
+    }
+
+    /// Credits `amount` tokens to the owner's associated token account, creating it if needed
+    /// with the extensions the mint's extensions require. Wrapped SOL is backed by as many lamports.
+    pub fn mint_to(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) {
+        let token_program = self.token_program(mint);
+        let address = pda::associated_token_account(owner, mint, &token_program);
+        let mut data = match self.runtime.account(&address) {
+            Some(account) => account.data.clone(),
+            None => self.new_token_account(owner, mint),
+        };
+        let rent = Rent::default().minimum_balance(data.len());
+        let mut state = spl_token::state::Account::unpack_from_slice(&data[..spl_token::state::Account::LEN]).unwrap();
+        state.amount += amount;
+        if *mint == spl_token::native_mint::ID {
+            state.is_native = Some(rent).into();
+        }
+        state.pack_into_slice(&mut data[..spl_token::state::Account::LEN]);
+        let native_amount = if state.is_native.is_some() { state.amount } else { 0 };
+        self.runtime.set_account(address, TestAccount {
+            lamports: rent + native_amount,
+            data,
+            owner: token_program,
+            executable: false,
+        });
+    }
+
+    fn new_token_account(&self, owner: &Pubkey, mint: &Pubkey) -> Vec<u8> {
+        let state = spl_token_2022::state::Account {
+            mint: *mint,
+            owner: *owner,
+            state: spl_token_2022::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        let mint_data = &self.runtime.account(mint).expect("mint not found").data;
+        if mint_data.len() == spl_token::state::Mint::LEN {
+            let mut data = vec![0; spl_token::state::Account::LEN];
+            state.pack_into_slice(&mut data);
+            return data;
+        }
+        let mint_extensions = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data).unwrap().get_extension_types().unwrap();
+        let extensions = ExtensionType::get_required_init_account_extensions(&mint_extensions);
+        let mut data = vec![0; ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&extensions).unwrap()];
+        let mut account = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack_uninitialized(&mut data).unwrap();
+        for extension in extensions {
+            assert_eq!(extension, ExtensionType::TransferFeeAmount, "unsupported account extension");
+            account.init_extension::<TransferFeeAmount>(true).unwrap();
+        }
+        account.base = state;
+        account.pack_base();
+        account.init_account_type().unwrap();
+        data
+    }
+
+    pub fn token_program(&self, mint: &Pubkey) -> Pubkey {
+        self.runtime.account(mint).expect("mint not found").owner
+    }
+
+    /// Balance of a token account, 0 if it doesn't exist
+    pub fn token_balance(&self, address: &Pubkey) -> u64 {
+        self.runtime.account(address).map_or(0, |account| {
+            spl_token::state::Account::unpack_from_slice(&account.data[..spl_token::state::Account::LEN]).unwrap().amount
+        })
+    }
+
+    /// Balance of the owner's associated token account for `mint`
+    pub fn balance(&self, owner: &Pubkey, mint: &Pubkey) -> u64 {
+        self.token_balance(&pda::associated_token_account(owner, mint, &self.token_program(mint)))
+    }
+
+    /// Balance of the owner's receipt tokens of the bank of `mint`
+    pub fn receipt_balance(&self, owner: &Pubkey, mint: &Pubkey) -> u64 {
+        let receipt_mint = pda::receipt_mint(mint);
+        self.token_balance(&pda::associated_token_account(owner, &receipt_mint, &self.token_program(mint)))
+    }
+
+    /// Receipt tokens of `mint` in circulation, deposit shares not held as collateral
+    pub fn receipt_supply(&self, mint: &Pubkey) -> u64 {
+        let account = self.runtime.account(&pda::receipt_mint(mint)).expect("receipt mint not found");
+        spl_token::state::Mint::unpack_from_slice(&account.data[..spl_token::state::Mint::LEN]).unwrap().supply
+    }
+
+    pub fn treasury_balance(&self, mint: &Pubkey) -> u64 {
+        self.token_balance(&pda::treasury(mint))
+    }
+
+    // Prices
+
+    /// Writes a fully verified price update for the bank of `mint`, published now
+    pub fn set_price(&mut self, mint: &Pubkey, price: i64) {
+        let now = self.now();
+        let update = MockPriceUpdate::new(feed_id(mint), price, PRICE_EXPONENT)
+            .publish_time(now)
+            .posted_slot(self.runtime.clock().slot);
+        self.set_price_update(mint, update);
+    }
+
+    /// Writes the price update account of the bank of `mint`
+    pub fn set_price_update(&mut self, mint: &Pubkey, update: MockPriceUpdate) {
+        let address = self.prices.get(mint).map_or_else(Pubkey::new_unique, |(address, _)| *address);
+        self.runtime.set_account(address, TestAccount {
+            lamports: update.lamports(),
+            data: update.data(),
+            owner: PRICE_UPDATE_OWNER,
+            executable: false,
+        });
+        self.prices.insert(*mint, (address, update));
+    }
+
+    /// The current price update of the bank of `mint`, to derive variations from
+    pub fn mock_price(&self, mint: &Pubkey) -> MockPriceUpdate {
+        self.prices[mint].1.clone()
+    }
+
+    pub fn price_update(&self, mint: &Pubkey) -> Pubkey {
+        self.prices[mint].0
+    }
+
+    // Program accounts
+
+    pub fn account<T: AccountDeserialize>(&self, address: &Pubkey) -> T {
+        let account = self.runtime.account(address).expect("account not found");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub fn bank(&self, mint: &Pubkey) -> Bank {
+        self.account(&pda::bank(mint))
+    }
+
+    pub fn user(&self, owner: &Pubkey, sub_account: u8) -> User {
+        self.account(&pda::user(owner, sub_account))
+    }
+
+    /// Remaining accounts pricing the user's current positions
+    pub fn health_accounts(&self, owner: &Pubkey, sub_account: u8) -> Vec<AccountMeta> {
+        let user = self.user(owner, sub_account);
+        instructions::health_accounts(&user, |bank| {
+            let mint = self.account::<Bank>(bank).mint_address;
+            self.price_update(&mint)
+        })
+    }
+
+    /// Health accounts for a borrow from `mint`, including its bank if the user has no position in it yet
+    pub fn borrow_health_accounts(&self, owner: &Pubkey, sub_account: u8, mint: &Pubkey) -> Vec<AccountMeta> {
+        let mut metas = self.health_accounts(owner, sub_account);
+        if self.user(owner, sub_account).position(&pda::bank(mint)).is_none() {
+            metas.push(AccountMeta::new_readonly(pda::bank(mint), false));
+            metas.push(AccountMeta::new_readonly(self.price_update(mint), false));
+        }
+        metas
+    }
+
+    // Setup
+
+    /// A listed bank for `mint` with the given thresholds, priced at `price`
+    pub fn init_bank(&mut self, mint: &Pubkey, liquidation_threshold: u64, max_ltv: u64, price: i64) {
+        let admin = self.admin;
+        let token_program = self.token_program(mint);
+        self.send(
+            &[instructions::init_bank(&admin, mint, &token_program, liquidation_threshold, max_ltv, feed_id(mint))],
+            &[admin],
+        );
+        self.set_price(mint, price);
+    }
+
+    /// Applies risk parameters through the timelock, leaving the clock `CONFIG_CHANGE_DELAY` later
+    pub fn set_risk_params(&mut self, mint: &Pubkey, params: RiskParams) {
+        let admin = self.admin;
+        self.send(&[instructions::queue_config_change(&admin, mint, params)], &[admin]);
+        self.warp(CONFIG_CHANGE_DELAY);
+        self.send(&[instructions::execute_config_change(mint, &admin)], &[admin]);
+    }
+
+    /// Moves the bank of `mint` into e-mode category `id` through the timelock
+    pub fn set_bank_emode_category(&mut self, mint: &Pubkey, id: u8) {
+        let params = RiskParams { emode_category: id, ..self.risk_params(mint) };
+        self.set_risk_params(mint, params);
+    }
+
+    /// Current risk parameters of the bank of `mint`
+    pub fn risk_params(&self, mint: &Pubkey) -> RiskParams {
+        let bank = self.bank(mint);
+        RiskParams {
+            liquidation_threshold: bank.liquidation_threshold,
+            liquidation_bonus: bank.liquidation_bonus,
+            liquidation_close_factor: bank.liquidation_close_factor,
+            max_ltv: bank.max_ltv,
+            interest_rate: bank.interest_rate,
+            base_rate: bank.base_rate,
+            oracle_feed_id: bank.oracle_feed_id,
+            max_price_age: bank.max_price_age,
+            emode_category: bank.emode_category,
+            collateral_enabled: bank.collateral_enabled,
+            isolated: bank.isolated,
+            isolation_debt_ceiling: bank.isolation_debt_ceiling,
+            borrowable_in_isolation: bank.borrowable_in_isolation,
+        }
+    }
+
+    /// A user account for `owner` holding `amount` tokens of `mint` deposited as collateral
+    pub fn deposit_collateral(&mut self, owner: &Pubkey, sub_account: u8, mint: &Pubkey, amount: u64) {
+        let token_program = self.token_program(mint);
+        if self.runtime.account(&pda::user(owner, sub_account)).is_none() {
+            self.send(&[instructions::init_user(owner, sub_account)], &[*owner]);
+        }
+        self.mint_to(owner, mint, amount);
+        self.send(&[instructions::deposit(owner, mint, &self.price_update(mint), &token_program, amount)], &[*owner]);
+        let shares = self.receipt_balance(owner, mint);
+        self.send(&[instructions::deposit_collateral(owner, mint, &token_program, sub_account, shares)], &[*owner]);
+    }
+
+    /// Liquidity in the bank of `mint` from a fresh depositor
+    pub fn supply(&mut self, mint: &Pubkey, amount: u64) -> Pubkey {
+        let supplier = self.wallet();
+        let token_program = self.token_program(mint);
+        self.mint_to(&supplier, mint, amount);
+        self.send(&[instructions::deposit(&supplier, mint, &self.price_update(mint), &token_program, amount)], &[supplier]);
+        supplier
+    }
+
+    pub fn borrow(&mut self, owner: &Pubkey, sub_account: u8, mint: &Pubkey, amount: u64) -> Result<TransactionMetadata, TransactionError> {
+        let (price_update, token_program) = (self.price_update(mint), self.token_program(mint));
+        let remaining_accounts = self.borrow_health_accounts(owner, sub_account, mint);
+        let isolated_bank = Some(self.user(owner, sub_account).isolated_bank).filter(|bank| *bank != Pubkey::default());
+        self.process(
+            &[instructions::borrow(owner, mint, &price_update, &token_program, sub_account, amount, isolated_bank, &remaining_accounts)],
+            &[*owner],
+        )
+    }
+
+    pub fn repay(&mut self, owner: &Pubkey, sub_account: u8, mint: &Pubkey, amount: u64) -> Result<TransactionMetadata, TransactionError> {
+        let (price_update, token_program) = (self.price_update(mint), self.token_program(mint));
+        let remaining_accounts = self.health_accounts(owner, sub_account);
+        let isolated_bank = Some(self.user(owner, sub_account).isolated_bank).filter(|bank| *bank != Pubkey::default());
+        self.process(
+            &[instructions::repay(owner, mint, &price_update, &token_program, sub_account, amount, isolated_bank, &remaining_accounts)],
+            &[*owner],
+        )
+    }
+}
+
+/// Feed id of the synthetic price feed of `mint`
+pub fn feed_id(mint: &Pubkey) -> [u8; 32] {
+    mint.to_bytes()
+}
+
+/// The instruction error an Anchor error code, of the program or of a library like the Pyth SDK, fails the transaction with
+pub fn program_error(code: impl Into<anchor_lang::error::Error>) -> InstructionError {
+    InstructionError::from(u64::from(ProgramError::from(code.into())))
+}
+
+pub fn assert_error<T: std::fmt::Debug>(result: Result<T, TransactionError>, code: impl Into<anchor_lang::error::Error>) {
+    let err = result.expect_err("transaction should fail");
+    assert_eq!(err.error, program_error(code), "{}", err.logs.join("\n"));
+}
+
+/// Events of type `T` emitted by the transaction
+pub fn events<T: AnchorDeserialize + Discriminator>(metadata: &TransactionMetadata) -> Vec<T> {
+    metadata
+        .data
+        .iter()
+        .filter_map(|data| data.strip_prefix(T::DISCRIMINATOR.as_slice()))
+        .map(|mut data| T::deserialize(&mut data).unwrap())
+        .collect()
+}
+
+/// Return data of a view instruction
+pub fn return_data<T: AnchorDeserialize>(metadata: &TransactionMetadata) -> T {
+    let (_, data) = metadata.return_data.as_ref().expect("no return data");
+    T::deserialize(&mut data.as_slice()).unwrap()
+}
+
+pub fn system_account(lamports: u64) -> TestAccount {
+    TestAccount { lamports, owner: system_program::ID, ..TestAccount::default() }
+}
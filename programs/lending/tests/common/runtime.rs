@@ -0,0 +1,558 @@
+//! Test harness for the integration tests, standing in for `solana-program-test`.
+//!
+//! `solana-program-test` 1.18.0, the only version available offline, pins `solana-sdk` to `=1.18.0` and a yanked
+//! `solana_rbpf`, which the `^1.18.11` SPL programs of `anchor-spl` 0.30.1 rule out, and LiteSVM isn't available.
+//! The harness therefore drives the validator's `solana-program-runtime` itself:
+//!
+//! - Transactions go through the runtime's `MessageProcessor`, which checks CPI privileges, that only an
+//!   account's owner changes its data or debits it, that instructions keep lamports balanced, the call depth
+//!   and the compute budget.
+//! - The lending program and the SPL programs run natively, registered as builtins of their loaders like
+//!   `solana-program-test`'s processor mode. Their accounts are serialized here into the BPF loader's input
+//!   layout, and their changes are committed through the runtime's `BorrowedAccount`, which checks them.
+//! - Their syscalls (CPI, sysvars, logs, return data) are served from the runtime's `InvokeContext`, reached
+//!   through a thread-local pointer like `solana-program-test` does.
+//! - The system instructions the programs use are implemented here on `BorrowedAccount`, the system program's
+//!   crate is only available pinned like `solana-program-test`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::{Arc, Once};
+
+use anchor_spl::associated_token::spl_associated_token_account;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use solana_program_runtime::compute_budget::ComputeBudget;
+use solana_program_runtime::declare_process_instruction;
+use solana_program_runtime::invoke_context::InvokeContext;
+use solana_program_runtime::loaded_programs::{LoadedProgram, LoadedProgramsForTxBatch};
+use solana_program_runtime::log_collector::LogCollector;
+use solana_program_runtime::message_processor::MessageProcessor;
+use solana_program_runtime::stable_log;
+use solana_program_runtime::sysvar_cache::SysvarCache;
+use solana_program_runtime::timings::ExecuteTimings;
+use solana_sdk::account::{AccountSharedData, ReadableAccount, WritableAccount};
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::clock::Clock;
+use solana_sdk::entrypoint::{self, ProgramResult, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER};
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::feature_set::FeatureSet;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::message::{Message, SanitizedMessage};
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::program_stubs::{self, SyscallStubs};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::stable_layout::stable_instruction::StableInstruction;
+use solana_sdk::system_instruction::{SystemError, SystemInstruction, MAX_PERMITTED_DATA_LENGTH};
+use solana_sdk::sysvar::instructions::construct_instructions_data;
+use solana_sdk::sysvar::Sysvar;
+use solana_sdk::transaction;
+use solana_sdk::transaction_context::{BorrowedAccount, IndexOfAccount, InstructionContext, TransactionContext};
+use solana_sdk::{bpf_loader, native_loader, system_program, sysvar};
+
+const SUCCESS: u64 = 0;
+const UNSUPPORTED_SYSVAR: u64 = 2 << 32;
+const BPF_ALIGN_OF_U128: usize = 8;
+/// Compute units the system program charges per instruction
+const SYSTEM_PROGRAM_COMPUTE_UNITS: u64 = 150;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TestAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+impl From<&TestAccount> for AccountSharedData {
+    fn from(account: &TestAccount) -> Self {
+        let mut shared = AccountSharedData::new(account.lamports, 0, &account.owner);
+        shared.set_data_from_slice(&account.data);
+        shared.set_executable(account.executable);
+        shared
+    }
+}
+
+impl From<AccountSharedData> for TestAccount {
+    fn from(account: AccountSharedData) -> Self {
+        Self { lamports: account.lamports(), data: account.data().to_vec(), owner: *account.owner(), executable: account.executable() }
+    }
+}
+
+/// A failed transaction, with the index of the failing instruction and the logs up to the failure
+#[derive(Debug)]
+pub struct TransactionError {
+    pub index: usize,
+    pub error: InstructionError,
+    pub logs: Vec<String>,
+}
+
+/// Logs and return data of a successful transaction
+#[derive(Debug, Default)]
+pub struct TransactionMetadata {
+    pub logs: Vec<String>,
+    /// Data logged with `sol_log_data`, such as Anchor events
+    pub data: Vec<Vec<u8>>,
+    pub return_data: Option<(Pubkey, Vec<u8>)>,
+}
+
+thread_local! {
+    /// Invoke context of the instruction a native program is executing, for its syscalls
+    static INVOKE_CONTEXT: RefCell<Option<usize>> = const { RefCell::new(None) };
+    /// Error of a failed cross-program invocation. On-chain it aborts the transaction, natively
+    /// it is returned to the caller as a `ProgramError`, so it is kept to fail the transaction with.
+    static CPI_ERROR: RefCell<Option<InstructionError>> = const { RefCell::new(None) };
+}
+
+fn set_invoke_context(invoke_context: &mut InvokeContext) {
+    INVOKE_CONTEXT.with(|cell| cell.replace(Some(invoke_context as *mut InvokeContext as usize)));
+}
+
+fn get_invoke_context<'a, 'b>() -> &'a mut InvokeContext<'b> {
+    let pointer = INVOKE_CONTEXT.with(|cell| cell.borrow().expect("no instruction is executing"));
+    // Set by the builtin running the native program, which outlives the program's syscalls
+    unsafe { &mut *(pointer as *mut InvokeContext) }
+}
+
+fn get_sysvar<T: Sysvar + Clone>(sysvar: Result<Arc<T>, InstructionError>, var_addr: *mut u8) -> u64 {
+    let invoke_context = get_invoke_context();
+    if invoke_context.consume_checked(invoke_context.get_compute_budget().sysvar_base_cost + T::size_of() as u64).is_err() {
+        panic!("exceeded the compute budget");
+    }
+    match sysvar {
+        Ok(sysvar) => {
+            unsafe { std::ptr::write(var_addr as *mut T, T::clone(&sysvar)) };
+            SUCCESS
+        }
+        Err(_) => UNSUPPORTED_SYSVAR,
+    }
+}
+
+/// Applies the lamports, data and owner a program left in an account, the runtime rejects changes the program may not make
+fn update_account(account: &mut BorrowedAccount, lamports: u64, data: &[u8], owner: &Pubkey) -> Result<(), InstructionError> {
+    if account.get_lamports() != lamports {
+        account.set_lamports(lamports)?;
+    }
+    if account.get_data() != data {
+        account.set_data_from_slice(data)?;
+    }
+    if account.get_owner() != owner {
+        account.set_owner(owner.as_ref())?;
+    }
+    Ok(())
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, message: &str) {
+        stable_log::program_log(&get_invoke_context().get_log_collector(), message);
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        stable_log::program_data(&get_invoke_context().get_log_collector(), fields);
+    }
+
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        invoke(instruction, account_infos, signers_seeds).map_err(|err| {
+            let program_error = ProgramError::try_from(err.clone()).unwrap_or(ProgramError::Custom(u32::MAX));
+            CPI_ERROR.with(|cell| cell.replace(Some(err)));
+            program_error
+        })
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(get_invoke_context().get_sysvar_cache().get_clock(), var_addr)
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(get_invoke_context().get_sysvar_cache().get_epoch_schedule(), var_addr)
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(get_invoke_context().get_sysvar_cache().get_rent(), var_addr)
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        let (program_id, data) = get_invoke_context().transaction_context.get_return_data();
+        (!data.is_empty()).then(|| (*program_id, data.to_vec()))
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let transaction_context = &mut get_invoke_context().transaction_context;
+        let instruction_context = transaction_context.get_current_instruction_context().unwrap();
+        let program_id = *instruction_context.get_last_program_key(transaction_context).unwrap();
+        transaction_context.set_return_data(program_id, data.to_vec()).unwrap();
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        get_invoke_context().get_stack_height() as u64
+    }
+}
+
+/// Cross-program invocation: the caller's changes to the accounts are handed to the runtime, which checks the
+/// privileges and runs the callee, then the callee's changes are copied back into the caller's accounts
+fn invoke(instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> Result<(), InstructionError> {
+    let invoke_context = get_invoke_context();
+    let instruction = StableInstruction::from(instruction.clone());
+    let caller = {
+        let transaction_context = &invoke_context.transaction_context;
+        *transaction_context.get_current_instruction_context()?.get_last_program_key(transaction_context)?
+    };
+    let signers = signers_seeds
+        .iter()
+        .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| InstructionError::InvalidSeeds)?;
+    let (instruction_accounts, program_indices) = invoke_context.prepare_instruction(&instruction, &signers)?;
+
+    let mut accounts = Vec::new();
+    for instruction_account in &instruction_accounts {
+        if accounts.iter().any(|(index, _)| *index == instruction_account.index_in_caller) {
+            continue;
+        }
+        let transaction_context = &invoke_context.transaction_context;
+        let mut account = transaction_context
+            .get_current_instruction_context()?
+            .try_borrow_instruction_account(transaction_context, instruction_account.index_in_caller)?;
+        let account_info = account_infos.iter().find(|info| info.key == account.get_key()).ok_or(InstructionError::MissingAccount)?;
+        update_account(&mut account, account_info.lamports(), &account_info.try_borrow_data().unwrap(), account_info.owner)?;
+        accounts.push((instruction_account.index_in_caller, account_info));
+    }
+
+    invoke_context.process_instruction(&instruction.data, &instruction_accounts, &program_indices, &mut 0, &mut ExecuteTimings::default())?;
+
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    for (index_in_caller, account_info) in accounts {
+        let account = instruction_context.try_borrow_instruction_account(transaction_context, index_in_caller)?;
+        **account_info.try_borrow_mut_lamports().unwrap() = account.get_lamports();
+        if account_info.owner != account.get_owner() {
+            account_info.assign(account.get_owner());
+        }
+        if account_info.data_len() != account.get_data().len() {
+            account_info.realloc(account.get_data().len(), false).map_err(|_| InstructionError::InvalidRealloc)?;
+        }
+        account_info.try_borrow_mut_data().unwrap().copy_from_slice(account.get_data());
+    }
+    Ok(())
+}
+
+/// Offsets of an account's fields in the serialized input
+struct SerializedAccount {
+    index: IndexOfAccount,
+    owner: usize,
+    lamports: usize,
+    data_len: usize,
+    data: usize,
+}
+
+/// Serializes the instruction's accounts in the BPF loader's input layout
+fn serialize(
+    transaction_context: &TransactionContext,
+    instruction_context: &InstructionContext,
+) -> Result<(Vec<u64>, Vec<SerializedAccount>), InstructionError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut accounts = Vec::new();
+    let number_of_accounts = instruction_context.get_number_of_instruction_accounts();
+    bytes.extend_from_slice(&(number_of_accounts as u64).to_le_bytes());
+    for index in 0..number_of_accounts {
+        if let Some(first) = instruction_context.is_instruction_account_duplicate(index)? {
+            bytes.push(first as u8);
+            bytes.extend_from_slice(&[0; 7]);
+            continue;
+        }
+        let account = instruction_context.try_borrow_instruction_account(transaction_context, index)?;
+        bytes.extend_from_slice(&[NON_DUP_MARKER, account.is_signer() as u8, account.is_writable() as u8, account.is_executable() as u8]);
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(account.get_key().as_ref());
+        let owner = bytes.len();
+        bytes.extend_from_slice(account.get_owner().as_ref());
+        let lamports = bytes.len();
+        bytes.extend_from_slice(&account.get_lamports().to_le_bytes());
+        let data_len = bytes.len();
+        bytes.extend_from_slice(&(account.get_data().len() as u64).to_le_bytes());
+        let data = bytes.len();
+        bytes.extend_from_slice(account.get_data());
+        bytes.resize(bytes.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+        bytes.resize(bytes.len().next_multiple_of(BPF_ALIGN_OF_U128), 0);
+        bytes.extend_from_slice(&account.get_rent_epoch().to_le_bytes());
+        accounts.push(SerializedAccount { index, owner, lamports, data_len, data });
+    }
+    let instruction_data = instruction_context.get_instruction_data();
+    bytes.extend_from_slice(&(instruction_data.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(instruction_data);
+    bytes.extend_from_slice(instruction_context.get_last_program_key(transaction_context)?.as_ref());
+
+    // Back the input with u64s so that it is aligned like the loader's input region
+    let mut buffer = vec![0u64; bytes.len().div_ceil(8)];
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), buffer.as_mut_ptr() as *mut u8, bytes.len()) };
+    Ok((buffer, accounts))
+}
+
+/// Runs the lending program or an SPL program natively on the instruction's accounts
+fn process_native_instruction(invoke_context: &mut InvokeContext) -> Result<(), InstructionError> {
+    set_invoke_context(invoke_context);
+    let transaction_context = &invoke_context.transaction_context;
+    let (mut buffer, accounts) = serialize(transaction_context, transaction_context.get_current_instruction_context()?)?;
+
+    CPI_ERROR.with(|cell| cell.take());
+    let result = {
+        let (program_id, account_infos, data) = unsafe { entrypoint::deserialize(buffer.as_mut_ptr() as *mut u8) };
+        if *program_id == lending::ID {
+            lending::entry(program_id, &account_infos, data)
+        } else if *program_id == spl_token::ID {
+            spl_token::processor::Processor::process(program_id, &account_infos, data)
+        } else if *program_id == spl_token_2022::ID {
+            spl_token_2022::processor::Processor::process(program_id, &account_infos, data)
+        } else if *program_id == spl_associated_token_account::ID {
+            spl_associated_token_account::processor::process_instruction(program_id, &account_infos, data)
+        } else {
+            Err(ProgramError::IncorrectProgramId)
+        }
+    };
+    if let Err(err) = result {
+        return Err(CPI_ERROR.with(|cell| cell.take()).unwrap_or_else(|| InstructionError::from(u64::from(err))));
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, buffer.len() * size_of::<u64>()) };
+    let read_u64 = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    for serialized in accounts {
+        let mut account = instruction_context.try_borrow_instruction_account(transaction_context, serialized.index)?;
+        let data_len = read_u64(serialized.data_len) as usize;
+        let owner = Pubkey::try_from(&bytes[serialized.owner..serialized.owner + 32]).unwrap();
+        update_account(&mut account, read_u64(serialized.lamports), &bytes[serialized.data..serialized.data + data_len], &owner)?;
+    }
+    Ok(())
+}
+
+/// The system instructions the programs use, with the checks of the system program
+fn process_system_instruction(invoke_context: &mut InvokeContext) -> Result<(), InstructionError> {
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    let instruction: SystemInstruction =
+        bincode::deserialize(instruction_context.get_instruction_data()).map_err(|_| InstructionError::InvalidInstructionData)?;
+    let account = |index: IndexOfAccount| instruction_context.try_borrow_instruction_account(transaction_context, index);
+
+    let transfer = |from: IndexOfAccount, to: IndexOfAccount, lamports: u64| -> Result<(), InstructionError> {
+        let mut from = account(from)?;
+        if !from.is_signer() {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+        if !from.get_data().is_empty() {
+            return Err(InstructionError::InvalidArgument);
+        }
+        if lamports > from.get_lamports() {
+            return Err(InstructionError::Custom(SystemError::ResultWithNegativeLamports as u32));
+        }
+        from.checked_sub_lamports(lamports)?;
+        drop(from);
+        account(to)?.checked_add_lamports(lamports)
+    };
+    let allocate = |account: &mut BorrowedAccount, space: u64| -> Result<(), InstructionError> {
+        if !account.is_signer() {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+        if !account.get_data().is_empty() || !system_program::check_id(account.get_owner()) {
+            return Err(InstructionError::Custom(SystemError::AccountAlreadyInUse as u32));
+        }
+        if space > MAX_PERMITTED_DATA_LENGTH {
+            return Err(InstructionError::Custom(SystemError::InvalidAccountDataLength as u32));
+        }
+        account.set_data_length(space as usize)
+    };
+    let assign = |account: &mut BorrowedAccount, owner: &Pubkey| -> Result<(), InstructionError> {
+        if account.get_owner() == owner {
+            return Ok(());
+        }
+        if !account.is_signer() {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+        account.set_owner(owner.as_ref())
+    };
+
+    match instruction {
+        SystemInstruction::CreateAccount { lamports, space, owner } => {
+            {
+                let mut to = account(1)?;
+                if to.get_lamports() > 0 {
+                    return Err(InstructionError::Custom(SystemError::AccountAlreadyInUse as u32));
+                }
+                allocate(&mut to, space)?;
+                assign(&mut to, &owner)?;
+            }
+            transfer(0, 1, lamports)
+        }
+        SystemInstruction::Transfer { lamports } => transfer(0, 1, lamports),
+        SystemInstruction::Allocate { space } => allocate(&mut account(0)?, space),
+        SystemInstruction::Assign { owner } => assign(&mut account(0)?, &owner),
+        _ => Err(InstructionError::InvalidInstructionData),
+    }
+}
+
+declare_process_instruction!(NativeProgram, 1, |invoke_context| { process_native_instruction(invoke_context) });
+declare_process_instruction!(SystemProgram, SYSTEM_PROGRAM_COMPUTE_UNITS, |invoke_context| { process_system_instruction(invoke_context) });
+
+pub struct Runtime {
+    accounts: HashMap<Pubkey, TestAccount>,
+    clock: Clock,
+    /// Builtins by loader: the runtime runs a program with the builtin of its owner
+    programs: LoadedProgramsForTxBatch,
+    /// Pays for the transactions, fees aren't charged
+    fee_payer: Pubkey,
+}
+
+impl Runtime {
+    /// A runtime with the lending program deployed with `upgrade_authority`, and the SPL programs
+    pub fn new(upgrade_authority: Pubkey) -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(Stubs));
+        });
+
+        let mut programs = LoadedProgramsForTxBatch::default();
+        for loader in [bpf_loader::ID, bpf_loader_upgradeable::ID] {
+            programs.replenish(loader, Arc::new(LoadedProgram::new_builtin(0, 0, NativeProgram::vm)));
+        }
+        programs.replenish(system_program::ID, Arc::new(LoadedProgram::new_builtin(0, 0, SystemProgram::vm)));
+
+        let mut runtime = Self {
+            accounts: HashMap::new(),
+            clock: Clock { slot: 1, unix_timestamp: 1_700_000_000, ..Clock::default() },
+            programs,
+            fee_payer: Pubkey::new_unique(),
+        };
+        let program_data = Pubkey::find_program_address(&[lending::ID.as_ref()], &bpf_loader_upgradeable::ID).0;
+        runtime.set_account(lending::ID, TestAccount {
+            lamports: 1,
+            data: bincode::serialize(&UpgradeableLoaderState::Program { programdata_address: program_data }).unwrap(),
+            owner: bpf_loader_upgradeable::ID,
+            executable: true,
+        });
+        runtime.set_account(program_data, TestAccount {
+            lamports: 1,
+            data: bincode::serialize(&UpgradeableLoaderState::ProgramData {
+                slot: 0,
+                upgrade_authority_address: Some(upgrade_authority),
+            })
+            .unwrap(),
+            owner: bpf_loader_upgradeable::ID,
+            executable: false,
+        });
+        for program in [spl_token::ID, spl_token_2022::ID, spl_associated_token_account::ID] {
+            runtime.set_account(program, TestAccount { lamports: 1, data: vec![], owner: bpf_loader::ID, executable: true });
+        }
+        runtime.set_account(system_program::ID, TestAccount { lamports: 1, data: vec![], owner: native_loader::ID, executable: true });
+        runtime
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&TestAccount> {
+        self.accounts.get(key)
+    }
+
+    pub fn set_account(&mut self, key: Pubkey, account: TestAccount) {
+        self.accounts.insert(key, account);
+    }
+
+    pub fn airdrop(&mut self, key: &Pubkey, lamports: u64) {
+        self.accounts.entry(*key).or_insert_with(|| TestAccount { owner: system_program::ID, ..TestAccount::default() }).lamports += lamports;
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.accounts.get(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Moves the clock forward by `seconds`
+    pub fn warp(&mut self, seconds: i64) {
+        self.clock.unix_timestamp += seconds;
+        self.clock.slot += (seconds as u64 * 5 / 2).max(1);
+    }
+
+    /// Runs the instructions atomically. Every account marked as signer must be in `signers`.
+    pub fn process_transaction(&mut self, instructions: &[Instruction], signers: &[Pubkey]) -> Result<TransactionMetadata, TransactionError> {
+        for (index, instruction) in instructions.iter().enumerate() {
+            if instruction.accounts.iter().any(|meta| meta.is_signer && !signers.contains(&meta.pubkey)) {
+                return Err(TransactionError { index, error: InstructionError::MissingRequiredSignature, logs: vec![] });
+            }
+        }
+        let message = SanitizedMessage::try_from(Message::new(instructions, Some(&self.fee_payer))).expect("invalid transaction");
+        let keys: Vec<Pubkey> = message.account_keys().iter().copied().collect();
+        let accounts = keys
+            .iter()
+            .map(|key| {
+                let account = if *key == sysvar::instructions::ID {
+                    let mut account = AccountSharedData::new(1, 0, &sysvar::ID);
+                    account.set_data_from_slice(&construct_instructions_data(&message.decompile_instructions()));
+                    account
+                } else {
+                    self.accounts.get(key).map_or_else(|| AccountSharedData::new(0, 0, &system_program::ID), AccountSharedData::from)
+                };
+                (*key, account)
+            })
+            .collect();
+        let program_indices: Vec<Vec<IndexOfAccount>> =
+            message.instructions().iter().map(|instruction| vec![instruction.program_id_index as IndexOfAccount]).collect();
+
+        let compute_budget = ComputeBudget::default();
+        let mut transaction_context =
+            TransactionContext::new(accounts, Rent::default(), compute_budget.max_invoke_stack_height, compute_budget.max_instruction_trace_length);
+        let mut sysvars = SysvarCache::default();
+        sysvars.set_clock(self.clock.clone());
+        sysvars.set_rent(Rent::default());
+        sysvars.set_epoch_schedule(EpochSchedule::default());
+        let log_collector = LogCollector::new_ref_with_limit(None);
+        let result = MessageProcessor::process_message(
+            &message,
+            &program_indices,
+            &mut transaction_context,
+            Some(log_collector.clone()),
+            &self.programs,
+            &mut LoadedProgramsForTxBatch::default(),
+            Arc::new(FeatureSet::all_enabled()),
+            compute_budget,
+            &mut ExecuteTimings::default(),
+            &sysvars,
+            Hash::default(),
+            0,
+            &mut 0,
+        );
+        let logs = log_collector.borrow().get_recorded_content().to_vec();
+        match result {
+            Ok(()) => {}
+            Err(transaction::TransactionError::InstructionError(index, error)) => {
+                return Err(TransactionError { index: index as usize, error, logs });
+            }
+            Err(err) => panic!("transaction failed: {err}"),
+        }
+
+        let (program_id, data) = transaction_context.get_return_data();
+        let return_data = (!data.is_empty()).then(|| (*program_id, data.to_vec()));
+        for (index, account) in transaction_context.deconstruct_without_keys().unwrap().into_iter().enumerate() {
+            if !message.is_writable(index) {
+                continue;
+            }
+            if account.lamports() == 0 {
+                self.accounts.remove(&keys[index]);
+            } else {
+                self.accounts.insert(keys[index], account.into());
+            }
+        }
+        let data = logs
+            .iter()
+            .filter_map(|log| log.strip_prefix("Program data: "))
+            .map(|fields| fields.split(' ').flat_map(|field| BASE64_STANDARD.decode(field).unwrap()).collect())
+            .collect();
+        Ok(TransactionMetadata { logs, data, return_data })
+    }
+}
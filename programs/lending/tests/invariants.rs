@@ -0,0 +1,149 @@
+//! Property tests driving random sequences of deposits, withdrawals, collateral moves, borrows, repays,
+//! liquidations, price moves and interest accrual across several users, checking the bank accounting
+//! after every step.
+
+mod common;
+
+use anchor_spl::token::spl_token;
+use common::market::{Market, USERS};
+use common::*;
+use lending::constants::SECONDS_PER_YEAR;
+use lending_client::{instructions, pda};
+use proptest::prelude::*;
+
+/// Percentages are of what the user could withdraw, borrow or repay at that point, going past 100 to hit the limits
+#[derive(Clone, Debug)]
+enum Op {
+    Deposit { user: usize, bank: usize, amount: u64 },
+    Withdraw { user: usize, bank: usize, percent: u64 },
+    DepositCollateral { user: usize, bank: usize, amount: u64 },
+    WithdrawCollateral { user: usize, bank: usize, percent: u64 },
+    Borrow { user: usize, bank: usize, percent: u64 },
+    Repay { user: usize, bank: usize, percent: u64 },
+    /// Liquidates every user it can, of their collateral in either bank for their borrow from the other
+    Liquidate,
+    /// USDC moves around its peg, SOL by a percentage of its current price
+    SetUsdcPrice { cents: i64 },
+    MoveSolPrice { percent: i64 },
+    Accrue { seconds: i64 },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let user = 0..USERS;
+    let bank = 0..2usize;
+    let amount = 1..5_000_000_000u64;
+    let percent = 1..=110u64;
+    prop_oneof![
+        3 => (user.clone(), bank.clone(), amount.clone()).prop_map(|(user, bank, amount)| Op::Deposit { user, bank, amount }),
+        2 => (user.clone(), bank.clone(), percent.clone()).prop_map(|(user, bank, percent)| Op::Withdraw { user, bank, percent }),
+        3 => (user.clone(), bank.clone(), amount).prop_map(|(user, bank, amount)| Op::DepositCollateral { user, bank, amount }),
+        1 => (user.clone(), bank.clone(), 1..=100u64).prop_map(|(user, bank, percent)| Op::WithdrawCollateral { user, bank, percent }),
+        3 => (user.clone(), bank.clone(), 50..=110u64).prop_map(|(user, bank, percent)| Op::Borrow { user, bank, percent }),
+        2 => (user, bank, percent).prop_map(|(user, bank, percent)| Op::Repay { user, bank, percent }),
+        2 => Just(Op::Liquidate),
+        1 => (90..=110i64).prop_map(|cents| Op::SetUsdcPrice { cents }),
+        3 => (60..=140i64).prop_map(|percent| Op::MoveSolPrice { percent }),
+        1 => (1..SECONDS_PER_YEAR as i64 / 4).prop_map(|seconds| Op::Accrue { seconds }),
+    ]
+}
+
+fn apply(market: &mut Market, op: &Op) {
+    let env = &mut market.env;
+    match *op {
+        Op::Deposit { user, bank, amount } => {
+            let (owner, mint) = (market.users[user], market.mints[bank]);
+            let _ = env.process(&[instructions::deposit(&owner, &mint, &env.price_update(&mint), &spl_token::ID, amount)], &[owner]);
+        }
+        Op::Withdraw { user, bank, percent } => {
+            let (owner, mint) = (market.users[user], market.mints[bank]);
+            let shares = env.receipt_balance(&owner, &mint);
+            let mut accrued_bank = env.bank(&mint);
+            accrued_bank.accrue_interest(env.now()).unwrap();
+            let value = accrued_bank.deposit_amount_for(shares);
+            let amount = (value * percent / 100).max(1);
+
+            let result = env.process(&[instructions::withdraw(&owner, &mint, &env.price_update(&mint), &spl_token::ID, amount)], &[owner]);
+            if result.is_ok() {
+                assert!(amount <= value, "withdrew {amount} with shares worth {value}");
+                let burned = shares - env.receipt_balance(&owner, &mint);
+                assert!(accrued_bank.deposit_amount_for(burned) >= amount, "withdrew {amount} for {burned} shares");
+            }
+        }
+        Op::DepositCollateral { user, bank, amount } => {
+            let (owner, mint) = (market.users[user], market.mints[bank]);
+            let mut accrued_bank = env.bank(&mint);
+            accrued_bank.accrue_interest(env.now()).unwrap();
+            let shares = accrued_bank.deposit_shares_for(amount);
+            let ixs = [
+                instructions::deposit(&owner, &mint, &env.price_update(&mint), &spl_token::ID, amount),
+                instructions::deposit_collateral(&owner, &mint, &spl_token::ID, 0, shares),
+            ];
+            let _ = env.process(&ixs, &[owner]);
+        }
+        Op::WithdrawCollateral { user, bank, percent } => {
+            let (owner, mint) = (market.users[user], market.mints[bank]);
+            let Some(position) = env.user(&owner, 0).position(&pda::bank(&mint)).copied() else { return };
+            let shares = position.deposit_shares * percent / 100;
+            let health_accounts = env.health_accounts(&owner, 0);
+            let ix = instructions::withdraw_collateral(
+                &owner,
+                &mint,
+                &env.price_update(&mint),
+                &spl_token::ID,
+                0,
+                shares,
+                &health_accounts,
+            );
+            if env.process(&[ix], &[owner]).is_ok() {
+                market.assert_healthy(user);
+            }
+        }
+        Op::Borrow { user, bank, percent } => {
+            let (owner, mint) = (market.users[user], market.mints[bank]);
+            let health_accounts = env.health_accounts(&owner, 0);
+            let ix = instructions::get_max_borrowable(&pda::user(&owner, 0), &mint, &env.price_update(&mint), &health_accounts);
+            let max_borrowable: u64 = return_data(&env.send(&[ix], &[]));
+            let amount = (max_borrowable as u128 * percent as u128 / 100).max(1) as u64;
+            if env.borrow(&owner, 0, &mint, amount).is_ok() {
+                market.assert_healthy(user);
+            }
+        }
+        Op::Repay { user, bank, percent } => {
+            let (owner, mint) = (market.users[user], market.mints[bank]);
+            let Some(position) = env.user(&owner, 0).position(&pda::bank(&mint)).copied() else { return };
+            let mut accrued_bank = env.bank(&mint);
+            accrued_bank.accrue_interest(env.now()).unwrap();
+            let amount = (accrued_bank.borrow_amount_for(position.borrow_shares) * percent / 100).max(1);
+            let _ = env.repay(&owner, 0, &mint, amount);
+        }
+        Op::Liquidate => {
+            for user in 0..USERS {
+                let _ = market.liquidate(user, 0, 1);
+                let _ = market.liquidate(user, 1, 0);
+            }
+        }
+        Op::SetUsdcPrice { cents } => env.set_price(&market.mints[0], usd(cents) / 100),
+        Op::MoveSolPrice { percent } => {
+            let price = env.mock_price(&market.mints[1]).price * percent / 100;
+            env.set_price(&market.mints[1], price.max(usd(1)));
+        }
+        Op::Accrue { seconds } => {
+            env.warp(seconds);
+            let ixs: Vec<_> = market.mints.iter().map(instructions::accrue_interest).collect();
+            env.send(&ixs, &[]);
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig { cases: 64, ..ProptestConfig::default() })]
+
+    #[test]
+    fn bank_accounting_invariants(ops in prop::collection::vec(op(), 1..60)) {
+        let mut market = Market::new();
+        for op in &ops {
+            apply(&mut market, op);
+            market.assert_invariants();
+        }
+    }
+}
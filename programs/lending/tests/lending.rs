@@ -0,0 +1,612 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use common::*;
+use lending::constants::{BASIS_POINTS, SECONDS_PER_YEAR};
+use lending::error::ErrorCode;
+use lending::events::{
+    BorrowEvent, DepositEvent, FlashBorrowEvent, FlashRepayEvent, InterestAccrued, RepayEvent, SetCollateralEnabledEvent,
+    SetUserEModeEvent, TransferCollateralEvent, WithdrawCollateralEvent,
+};
+use lending::instructions::{LiquidationPriceView, UserPositionView};
+use lending::state::RiskParams;
+use lending_client::{instructions, pda};
+
+const USDC_DECIMALS: u8 = 6;
+const SOL_DECIMALS: u8 = 9;
+
+/// A USDC bank priced at $1 and a SOL bank priced at $100, both with 80% liquidation threshold and 70% max LTV
+fn setup() -> (TestEnv, Pubkey, Pubkey) {
+    let mut env = TestEnv::new();
+    let usdc = env.create_mint(USDC_DECIMALS, &spl_token::ID);
+    let sol = env.create_mint(SOL_DECIMALS, &spl_token::ID);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.init_bank(&sol, 80, 70, usd(100));
+    (env, usdc, sol)
+}
+
+#[test]
+fn deposit_and_withdraw() {
+    let (mut env, usdc, _) = setup();
+    let user = env.wallet();
+    env.mint_to(&user, &usdc, 1_000);
+
+    let metadata = env.send(&[instructions::deposit(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 1_000)], &[user]);
+    let event = &events::<DepositEvent>(&metadata)[0];
+    assert_eq!((event.owner, event.shares), (user, 1_000));
+    assert_eq!((event.price, event.price_exponent), (usd(1), PRICE_EXPONENT));
+    assert_eq!(env.treasury_balance(&usdc), 1_000);
+    assert_eq!(env.receipt_balance(&user, &usdc), 1_000);
+    let bank = env.bank(&usdc);
+    assert_eq!((bank.total_deposits, bank.total_deposit_shares), (1_000, 1_000));
+
+    assert_error(
+        env.process(&[instructions::withdraw(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 1_001)], &[user]),
+        ErrorCode::InsufficientFunds,
+    );
+    env.send(&[instructions::withdraw(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 400)], &[user]);
+    assert_eq!(env.balance(&user, &usdc), 400);
+    assert_eq!(env.receipt_balance(&user, &usdc), 600);
+    assert_eq!(env.bank(&usdc).total_deposits, 600);
+
+    // Receipt tokens of another depositor cannot be redeemed twice
+    let other = env.wallet();
+    env.mint_to(&other, &usdc, 1_000);
+    env.send(&[instructions::deposit(&other, &usdc, &env.price_update(&usdc), &spl_token::ID, 1_000)], &[other]);
+    assert_error(
+        env.process(&[instructions::withdraw(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 601)], &[user]),
+        ErrorCode::InsufficientFunds,
+    );
+}
+
+#[test]
+fn native_sol_deposit_and_withdraw() {
+    let mut env = TestEnv::new();
+    let native_mint = env.native_mint();
+    env.init_bank(&native_mint, 80, 70, usd(100));
+    let user = env.wallet();
+    // Wrapped SOL the user already holds is not touched
+    env.mint_to(&user, &native_mint, 5_000);
+    let lamports = env.runtime.lamports(&user);
+
+    env.send(&[instructions::deposit_sol(&user, &native_mint, &env.price_update(&native_mint), &spl_token::ID, LAMPORTS_PER_SOL)], &[user]);
+    assert_eq!(env.treasury_balance(&native_mint), LAMPORTS_PER_SOL);
+    assert_eq!(env.receipt_balance(&user, &native_mint), LAMPORTS_PER_SOL);
+    // The temporary wrapped SOL account is closed again
+    assert!(env.runtime.account(&pda::wsol_account(&user)).is_none());
+    assert!(env.runtime.lamports(&user) < lamports - LAMPORTS_PER_SOL);
+    assert_eq!(env.balance(&user, &native_mint), 5_000);
+
+    let lamports = env.runtime.lamports(&user);
+    env.send(
+        &[instructions::withdraw_sol(&user, &native_mint, &env.price_update(&native_mint), &spl_token::ID, LAMPORTS_PER_SOL / 2)],
+        &[user],
+    );
+    assert_eq!(env.runtime.lamports(&user), lamports + LAMPORTS_PER_SOL / 2);
+    assert_eq!(env.treasury_balance(&native_mint), LAMPORTS_PER_SOL / 2);
+    assert!(env.runtime.account(&pda::wsol_account(&user)).is_none());
+    assert_eq!(env.balance(&user, &native_mint), 5_000);
+
+    let usdc = env.create_mint(USDC_DECIMALS, &spl_token::ID);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    assert_error(
+        env.process(&[instructions::deposit_sol(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 1_000)], &[user]),
+        ErrorCode::NotNativeMint,
+    );
+}
+
+#[test]
+fn collateral_deposit_and_withdraw() {
+    let (mut env, usdc, sol) = setup();
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, 10 * LAMPORTS_PER_SOL);
+    assert_eq!(env.receipt_balance(&user, &sol), 0);
+    let position = *env.user(&user, 0).position(&pda::bank(&sol)).unwrap();
+    assert_eq!(position.deposit_shares, 10 * LAMPORTS_PER_SOL);
+
+    // Without borrows the collateral can be withdrawn without prices
+    let metadata = env.send(
+        &[instructions::withdraw_collateral(&user, &sol, &env.price_update(&sol), &spl_token::ID, 0, LAMPORTS_PER_SOL, &[])],
+        &[user],
+    );
+    let event = &events::<WithdrawCollateralEvent>(&metadata)[0];
+    assert_eq!((event.user, event.owner, event.shares), (pda::user(&user, 0), user, LAMPORTS_PER_SOL));
+    assert_eq!(env.receipt_balance(&user, &sol), LAMPORTS_PER_SOL);
+    assert_error(
+        env.process(
+            &[instructions::withdraw_collateral(&user, &sol, &env.price_update(&sol), &spl_token::ID, 0, 10 * LAMPORTS_PER_SOL, &[])],
+            &[user],
+        ),
+        ErrorCode::InsufficientFunds,
+    );
+
+    // With a borrow, releasing collateral must keep the user healthy
+    env.supply(&usdc, 1_000_000_000);
+    env.borrow(&user, 0, &usdc, 600_000_000).unwrap();
+    let health_accounts = env.health_accounts(&user, 0);
+    assert_error(
+        env.process(
+            &[instructions::withdraw_collateral(
+                &user,
+                &sol,
+                &env.price_update(&sol),
+                &spl_token::ID,
+                0,
+                2 * LAMPORTS_PER_SOL,
+                &health_accounts,
+            )],
+            &[user],
+        ),
+        ErrorCode::UnderCollateralized,
+    );
+    env.send(
+        &[instructions::withdraw_collateral(&user, &sol, &env.price_update(&sol), &spl_token::ID, 0, LAMPORTS_PER_SOL, &health_accounts)],
+        &[user],
+    );
+    assert_error(
+        env.process(&[instructions::set_collateral_enabled(&user, &sol, 0, false, &health_accounts)], &[user]),
+        ErrorCode::UnderCollateralized,
+    );
+}
+
+#[test]
+fn collateral_can_be_disabled_without_borrows() {
+    let (mut env, _, sol) = setup();
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+
+    let metadata = env.send(&[instructions::set_collateral_enabled(&user, &sol, 0, false, &[])], &[user]);
+    let event = &events::<SetCollateralEnabledEvent>(&metadata)[0];
+    assert_eq!((event.user, event.owner, event.enabled), (pda::user(&user, 0), user, false));
+    let position = *env.user(&user, 0).position(&pda::bank(&sol)).unwrap();
+    assert!(position.collateral_disabled);
+    env.send(&[instructions::set_collateral_enabled(&user, &sol, 0, true, &[])], &[user]);
+    assert!(!env.user(&user, 0).position(&pda::bank(&sol)).unwrap().collateral_disabled);
+}
+
+#[test]
+fn zero_amounts_on_an_empty_bank() {
+    let (mut env, usdc, _) = setup();
+    let user = env.wallet();
+    env.mint_to(&user, &usdc, 1_000);
+    env.send(&[instructions::deposit(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 1_000)], &[user]);
+    env.send(&[instructions::withdraw(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 1_000)], &[user]);
+    assert_eq!(env.bank(&usdc).total_deposits, 0);
+
+    env.send(&[instructions::withdraw(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 0)], &[user]);
+    env.send(&[instructions::init_user(&user, 0), instructions::init_user(&user, 1)], &[user]);
+    assert_error(
+        env.process(&[instructions::transfer_collateral(&user, &usdc, &env.price_update(&usdc), 0, 1, 0, &[])], &[user]),
+        ErrorCode::PositionNotFound,
+    );
+}
+
+#[test]
+fn transfer_collateral_between_sub_accounts() {
+    let (mut env, _, sol) = setup();
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, 4 * LAMPORTS_PER_SOL);
+    env.send(&[instructions::init_user(&user, 1)], &[user]);
+
+    assert_error(
+        env.process(&[instructions::transfer_collateral(&user, &sol, &env.price_update(&sol), 0, 0, LAMPORTS_PER_SOL, &[])], &[user]),
+        ErrorCode::SameSubAccount,
+    );
+    let metadata = env.send(
+        &[instructions::transfer_collateral(&user, &sol, &env.price_update(&sol), 0, 1, LAMPORTS_PER_SOL, &[])],
+        &[user],
+    );
+    let event = &events::<TransferCollateralEvent>(&metadata)[0];
+    assert_eq!((event.from_user, event.to_user, event.owner), (pda::user(&user, 0), pda::user(&user, 1), user));
+    assert_eq!((event.amount, event.shares), (LAMPORTS_PER_SOL, LAMPORTS_PER_SOL));
+    let bank = pda::bank(&sol);
+    assert_eq!(env.user(&user, 0).position(&bank).unwrap().deposit_shares, 3 * LAMPORTS_PER_SOL);
+    assert_eq!(env.user(&user, 1).position(&bank).unwrap().deposit_shares, LAMPORTS_PER_SOL);
+
+    // Once the bank stops taking collateral, only existing positions can receive more
+    let params = RiskParams { collateral_enabled: false, ..env.risk_params(&sol) };
+    env.set_risk_params(&sol, params);
+    env.send(&[instructions::init_user(&user, 2)], &[user]);
+    assert_error(
+        env.process(&[instructions::transfer_collateral(&user, &sol, &env.price_update(&sol), 0, 2, LAMPORTS_PER_SOL, &[])], &[user]),
+        ErrorCode::CollateralDisabled,
+    );
+    env.send(&[instructions::transfer_collateral(&user, &sol, &env.price_update(&sol), 0, 1, 3 * LAMPORTS_PER_SOL, &[])], &[user]);
+    assert!(env.user(&user, 0).position(&bank).is_none());
+}
+
+#[test]
+fn borrow_and_repay() {
+    let (mut env, usdc, sol) = setup();
+    env.supply(&usdc, 10_000_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+
+    // $100 of collateral at 70% max LTV
+    assert_error(env.borrow(&user, 0, &usdc, 70_000_001), ErrorCode::OverLTV);
+    let metadata = env.borrow(&user, 0, &usdc, 50_000_000).unwrap();
+    let event = &events::<BorrowEvent>(&metadata)[0];
+    assert_eq!((event.owner, event.amount, event.shares), (user, 50_000_000, 50_000_000));
+    // $80 liquidation value against $50 of debt
+    assert_eq!(event.health_factor, 160);
+    assert_eq!((event.price, event.price_exponent), (usd(1), PRICE_EXPONENT));
+    assert_eq!(env.balance(&user, &usdc), 50_000_000);
+    assert_eq!(env.user(&user, 0).health_factor, 160);
+    let bank = env.bank(&usdc);
+    assert_eq!((bank.total_borrowed, bank.total_borrowed_shares), (50_000_000, 50_000_000));
+
+    // The price accounts must match the user's positions
+    let mut health_accounts = env.borrow_health_accounts(&user, 0, &usdc);
+    health_accounts.swap(0, 2);
+    assert_error(
+        env.process(
+            &[instructions::borrow(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 0, 1, None, &health_accounts)],
+            &[user],
+        ),
+        ErrorCode::InvalidRemainingAccounts,
+    );
+
+    env.mint_to(&user, &usdc, 1);
+    assert_error(env.repay(&user, 0, &usdc, 50_000_001), ErrorCode::OverRepay);
+    let metadata = env.repay(&user, 0, &usdc, 20_000_000).unwrap();
+    let event = &events::<RepayEvent>(&metadata)[0];
+    assert_eq!(event.shares, 20_000_000);
+    // $80 liquidation value against the remaining $30 of debt
+    assert_eq!(event.health_factor, 266);
+    env.repay(&user, 0, &usdc, 30_000_000).unwrap();
+    let bank = env.bank(&usdc);
+    assert_eq!((bank.total_borrowed, bank.total_borrowed_shares), (0, 0));
+    assert!(env.user(&user, 0).position(&pda::bank(&usdc)).is_none());
+}
+
+#[test]
+fn borrow_requires_fresh_price() {
+    let (mut env, usdc, sol) = setup();
+    env.supply(&usdc, 1_000_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+
+    // Prices published more than `max_price_age` ago are rejected
+    let stale = env.price_update(&sol);
+    env.runtime.warp(101);
+    assert!(env.borrow(&user, 0, &usdc, 1_000_000).is_err());
+    env.set_price(&sol, usd(100));
+    assert_eq!(env.price_update(&sol), stale);
+    env.set_price(&usdc, usd(1));
+    env.borrow(&user, 0, &usdc, 1_000_000).unwrap();
+
+    env.set_price(&sol, 0);
+    assert_error(env.borrow(&user, 0, &usdc, 1_000_000), ErrorCode::InvalidPrice);
+}
+
+#[test]
+fn borrow_limits_and_flags() {
+    let (mut env, usdc, sol) = setup();
+    let admin = env.admin;
+    env.supply(&usdc, 1_000_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+
+    env.send(&[instructions::set_bank_limits(&admin, &usdc, 0, 10_000_000)], &[admin]);
+    assert_error(env.borrow(&user, 0, &usdc, 10_000_001), ErrorCode::BorrowLimitExceeded);
+    env.send(&[instructions::set_borrow_enabled(&admin, &usdc, false)], &[admin]);
+    assert_error(env.borrow(&user, 0, &usdc, 1_000_000), ErrorCode::BorrowDisabled);
+    env.send(&[instructions::set_borrow_enabled(&admin, &usdc, true)], &[admin]);
+    env.borrow(&user, 0, &usdc, 10_000_000).unwrap();
+}
+
+#[test]
+fn native_sol_borrow_and_repay() {
+    let mut env = TestEnv::new();
+    let usdc = env.create_mint(USDC_DECIMALS, &spl_token::ID);
+    let native_mint = env.native_mint();
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.init_bank(&native_mint, 80, 70, usd(100));
+    let supplier = env.wallet();
+    env.send(
+        &[instructions::deposit_sol(&supplier, &native_mint, &env.price_update(&native_mint), &spl_token::ID, 10 * LAMPORTS_PER_SOL)],
+        &[supplier],
+    );
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &usdc, 1_000_000_000);
+
+    let lamports = env.runtime.lamports(&user);
+    let remaining_accounts = env.borrow_health_accounts(&user, 0, &native_mint);
+    env.send(
+        &[instructions::borrow_sol(
+            &user,
+            &native_mint,
+            &env.price_update(&native_mint),
+            &spl_token::ID,
+            0,
+            LAMPORTS_PER_SOL,
+            None,
+            &remaining_accounts,
+        )],
+        &[user],
+    );
+    assert_eq!(env.runtime.lamports(&user), lamports + LAMPORTS_PER_SOL);
+
+    env.send(
+        &[instructions::repay_sol(&user, &native_mint, &env.price_update(&native_mint), &spl_token::ID, 0, LAMPORTS_PER_SOL, None, &[])],
+        &[user],
+    );
+    assert_eq!(env.runtime.lamports(&user), lamports);
+    assert_eq!(env.bank(&native_mint).total_borrowed, 0);
+    assert_eq!(env.treasury_balance(&native_mint), 10 * LAMPORTS_PER_SOL);
+}
+
+#[test]
+fn token_2022_bank() {
+    let mut env = TestEnv::new();
+    let usdc = env.create_mint(USDC_DECIMALS, &spl_token_2022::ID);
+    let sol = env.create_mint(SOL_DECIMALS, &spl_token::ID);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.init_bank(&sol, 80, 70, usd(100));
+    env.supply(&usdc, 1_000_000_000);
+    assert_eq!(env.treasury_balance(&usdc), 1_000_000_000);
+
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+    env.borrow(&user, 0, &usdc, 10_000_000).unwrap();
+    assert_eq!(env.balance(&user, &usdc), 10_000_000);
+    env.send(&[instructions::repay(&user, &usdc, &env.price_update(&usdc), &spl_token_2022::ID, 0, 10_000_000, None, &[])], &[user]);
+    assert_eq!(env.treasury_balance(&usdc), 1_000_000_000);
+}
+
+/// Sets a 10% borrow rate at full utilization, and no base rate
+fn set_interest_rate(env: &mut TestEnv, mint: &Pubkey) {
+    let params = RiskParams { interest_rate: 1_000, base_rate: 0, ..env.risk_params(mint) };
+    env.set_risk_params(mint, params);
+}
+
+#[test]
+fn interest_accrual() {
+    let (mut env, usdc, sol) = setup();
+    set_interest_rate(&mut env, &usdc);
+    let supplier = env.supply(&usdc, 100_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+    env.borrow(&user, 0, &usdc, 50_000_000).unwrap();
+    assert_eq!(env.bank(&usdc).utilization_rate(), 5_000);
+
+    // 50% utilization borrows at 5% a year
+    env.warp(SECONDS_PER_YEAR as i64);
+    let metadata = env.send(&[instructions::accrue_interest(&usdc)], &[]);
+    let event = &events::<InterestAccrued>(&metadata)[0];
+    let interest = 50_000_000 * 500 / BASIS_POINTS;
+    assert_eq!(event.interest, interest);
+    let bank = env.bank(&usdc);
+    assert_eq!(bank.total_borrowed, 50_000_000 + interest);
+    assert_eq!(bank.total_deposits, 100_000_000 + interest);
+    assert_eq!(bank.last_updated, env.now());
+
+    // The borrower owes the interest and the supplier earns it
+    let owed = bank.borrow_amount_for(env.user(&user, 0).position(&pda::bank(&usdc)).unwrap().borrow_shares);
+    assert_eq!(owed, 50_000_000 + interest);
+    env.mint_to(&user, &usdc, interest + 1);
+    assert_error(
+        env.process(&[instructions::repay(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 0, owed + 1, None, &[])], &[user]),
+        ErrorCode::OverRepay,
+    );
+    env.send(&[instructions::repay(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 0, owed, None, &[])], &[user]);
+    assert!(env.user(&user, 0).position(&pda::bank(&usdc)).is_none());
+
+    env.send(&[instructions::withdraw(&supplier, &usdc, &env.price_update(&usdc), &spl_token::ID, 100_000_000 + interest)], &[supplier]);
+    assert_eq!(env.balance(&supplier, &usdc), 100_000_000 + interest);
+    assert_eq!(env.receipt_balance(&supplier, &usdc), 0);
+}
+
+#[test]
+fn refresh_user_stores_health_and_accrued_banks() {
+    let (mut env, usdc, sol) = setup();
+    set_interest_rate(&mut env, &usdc);
+    env.supply(&usdc, 100_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+    env.borrow(&user, 0, &usdc, 50_000_000).unwrap();
+    assert_eq!(env.user(&user, 0).health_factor, 160);
+
+    env.warp(1_000);
+    env.set_price(&sol, usd(75));
+    let mut remaining_accounts = env.health_accounts(&user, 0);
+    // Only the writable USDC bank gets its interest stored
+    let usdc_bank_index = remaining_accounts.iter().position(|meta| meta.pubkey == pda::bank(&usdc)).unwrap();
+    remaining_accounts[usdc_bank_index].is_writable = true;
+    let metadata = env.send(&[instructions::refresh_user(&pda::user(&user, 0), &remaining_accounts)], &[]);
+    let accrued = events::<InterestAccrued>(&metadata);
+    assert_eq!(accrued.len(), 1);
+    assert_eq!(accrued[0].bank, pda::bank(&usdc));
+
+    // $60 liquidation value against $50 of debt and a little interest
+    assert_eq!(env.user(&user, 0).health_factor, 119);
+    assert_eq!(env.bank(&usdc).last_updated, env.now());
+    assert!(env.bank(&sol).last_updated < env.now());
+}
+
+#[test]
+fn flash_loan() {
+    let (mut env, usdc, _) = setup();
+    let admin = env.admin;
+    env.send(&[instructions::set_flash_loan_fee(&admin, &usdc, 30)], &[admin]);
+    env.supply(&usdc, 100_000_000);
+    let user = env.wallet();
+    env.mint_to(&user, &usdc, 30_000);
+
+    assert_error(
+        env.process(&[instructions::flash_borrow(&user, &usdc, &spl_token::ID, 10_000_000)], &[user]),
+        ErrorCode::MissingFlashRepay,
+    );
+    assert_error(env.process(&[instructions::flash_repay(&user, &usdc, &spl_token::ID)], &[user]), ErrorCode::NoFlashLoanInProgress);
+
+    let metadata = env.send(
+        &[
+            instructions::flash_borrow(&user, &usdc, &spl_token::ID, 10_000_000),
+            instructions::flash_repay(&user, &usdc, &spl_token::ID),
+        ],
+        &[user],
+    );
+    let borrow_event = &events::<FlashBorrowEvent>(&metadata)[0];
+    assert_eq!((borrow_event.owner, borrow_event.amount), (user, 10_000_000));
+    let repay_event = &events::<FlashRepayEvent>(&metadata)[0];
+    assert_eq!((repay_event.amount, repay_event.fee), (10_000_000, 30_000));
+    // The 0.3% fee goes to the depositors
+    assert_eq!(env.balance(&user, &usdc), 0);
+    assert_eq!(env.treasury_balance(&usdc), 100_030_000);
+    let bank = env.bank(&usdc);
+    assert_eq!((bank.total_deposits, bank.flash_loan_amount), (100_030_000, 0));
+
+    // A loan that cannot be repaid reverts the whole transaction
+    assert!(env
+        .process(
+            &[
+                instructions::flash_borrow(&user, &usdc, &spl_token::ID, 10_000_000),
+                instructions::flash_repay(&user, &usdc, &spl_token::ID),
+            ],
+            &[user],
+        )
+        .is_err());
+    assert_eq!(env.treasury_balance(&usdc), 100_030_000);
+}
+
+#[test]
+fn flash_loan_with_transfer_fee() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    // 1% of every transfer is withheld by the mint
+    let usdc = env.create_transfer_fee_mint(USDC_DECIMALS, 100, u64::MAX);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.send(&[instructions::set_flash_loan_fee(&admin, &usdc, 30)], &[admin]);
+    env.supply(&usdc, 100_000_000);
+    assert_eq!(env.bank(&usdc).total_deposits, 99_000_000);
+    let user = env.wallet();
+    // The loan arrives less the fee, the repayment is grossed up so that the loan and its fee arrive in full
+    env.mint_to(&user, &usdc, 100_000 + 131_314);
+
+    env.send(
+        &[
+            instructions::flash_borrow(&user, &usdc, &spl_token_2022::ID, 10_000_000),
+            instructions::flash_repay(&user, &usdc, &spl_token_2022::ID),
+        ],
+        &[user],
+    );
+    assert_eq!(env.balance(&user, &usdc), 0);
+    assert_eq!(env.treasury_balance(&usdc), 99_030_000);
+    let bank = env.bank(&usdc);
+    assert_eq!((bank.total_deposits, bank.flash_loan_amount), (99_030_000, 0));
+}
+
+#[test]
+fn isolation_mode() {
+    let (mut env, usdc, sol) = setup();
+    let meme = env.create_mint(SOL_DECIMALS, &spl_token::ID);
+    env.init_bank(&meme, 60, 50, usd(1));
+    // Isolated users can borrow up to $100 of USDC against the meme token
+    let params = RiskParams { isolated: true, isolation_debt_ceiling: 100_000_000, ..env.risk_params(&meme) };
+    env.set_risk_params(&meme, params);
+    let params = RiskParams { borrowable_in_isolation: true, ..env.risk_params(&usdc) };
+    env.set_risk_params(&usdc, params);
+    env.supply(&usdc, 1_000_000_000);
+    env.supply(&sol, 10 * LAMPORTS_PER_SOL);
+
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &meme, 1_000 * LAMPORTS_PER_SOL);
+    assert_eq!(env.user(&user, 0).isolated_bank, pda::bank(&meme));
+
+    // Other collateral cannot be added
+    env.mint_to(&user, &sol, LAMPORTS_PER_SOL);
+    env.send(&[instructions::deposit(&user, &sol, &env.price_update(&sol), &spl_token::ID, LAMPORTS_PER_SOL)], &[user]);
+    assert_error(
+        env.process(&[instructions::deposit_collateral(&user, &sol, &spl_token::ID, 0, LAMPORTS_PER_SOL)], &[user]),
+        ErrorCode::IsolatedCollateral,
+    );
+
+    assert_error(env.borrow(&user, 0, &sol, 1_000), ErrorCode::NotBorrowableInIsolation);
+    assert_error(env.borrow(&user, 0, &usdc, 100_000_001), ErrorCode::IsolationDebtCeilingExceeded);
+    let remaining_accounts = env.borrow_health_accounts(&user, 0, &usdc);
+    assert_error(
+        env.process(
+            &[instructions::borrow(&user, &usdc, &env.price_update(&usdc), &spl_token::ID, 0, 1_000_000, None, &remaining_accounts)],
+            &[user],
+        ),
+        ErrorCode::MissingIsolatedBank,
+    );
+    env.borrow(&user, 0, &usdc, 100_000_000).unwrap();
+    assert_eq!(env.bank(&meme).isolation_debt, 100_000_000);
+
+    env.repay(&user, 0, &usdc, 40_000_000).unwrap();
+    assert_eq!(env.bank(&meme).isolation_debt, 60_000_000);
+}
+
+#[test]
+fn emode_raises_borrowing_power() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let usdc = env.create_mint(USDC_DECIMALS, &spl_token::ID);
+    let usdt = env.create_mint(USDC_DECIMALS, &spl_token::ID);
+    let sol = env.create_mint(SOL_DECIMALS, &spl_token::ID);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.init_bank(&usdt, 80, 70, usd(1));
+    env.init_bank(&sol, 80, 70, usd(100));
+    env.send(&[instructions::init_emode_category(&admin, 1, 90, 95, 2)], &[admin]);
+    env.set_bank_emode_category(&usdc, 1);
+    env.set_bank_emode_category(&usdt, 1);
+    env.supply(&usdt, 1_000_000_000);
+    env.supply(&sol, 10 * LAMPORTS_PER_SOL);
+
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &usdc, 100_000_000);
+    assert_error(env.borrow(&user, 0, &usdt, 80_000_000), ErrorCode::OverLTV);
+
+    let emode_accounts = [anchor_lang::prelude::AccountMeta::new_readonly(pda::emode_category(1), false)];
+    let metadata = env.send(&[instructions::set_user_emode(&user, 0, 1, &emode_accounts)], &[user]);
+    let event = &events::<SetUserEModeEvent>(&metadata)[0];
+    assert_eq!((event.user, event.owner, event.emode_category), (pda::user(&user, 0), user, 1));
+    env.borrow(&user, 0, &usdt, 90_000_000).unwrap();
+    // $95 liquidation value against $90 of debt
+    assert_eq!(env.user(&user, 0).health_factor, 105);
+
+    // Only banks of the category can be borrowed
+    assert_error(env.borrow(&user, 0, &sol, 1_000), ErrorCode::EModeMismatch);
+    // Leaving e-mode would make the user liquidatable
+    let mut remaining_accounts = env.health_accounts(&user, 0);
+    remaining_accounts.remove(0);
+    assert_error(
+        env.process(&[instructions::set_user_emode(&user, 0, 0, &remaining_accounts)], &[user]),
+        ErrorCode::UnderCollateralized,
+    );
+}
+
+#[test]
+fn views() {
+    let (mut env, usdc, sol) = setup();
+    env.supply(&usdc, 1_000_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+    env.borrow(&user, 0, &usdc, 50_000_000).unwrap();
+    let user_account = pda::user(&user, 0);
+    let health_accounts = env.health_accounts(&user, 0);
+
+    let metadata = env.send(&[instructions::get_user_position(&user_account, &health_accounts)], &[]);
+    let view: UserPositionView = return_data(&metadata);
+    assert_eq!(view.health_factor, 160);
+    assert_eq!(view.collateral_value, 100_000_000);
+    assert_eq!(view.borrowable_value, 70_000_000);
+    assert_eq!(view.liquidation_value, 80_000_000);
+    assert_eq!(view.borrowed_value, 50_000_000);
+    assert_eq!(view.positions.len(), 2);
+
+    let metadata = env.send(&[instructions::get_max_borrowable(&user_account, &usdc, &env.price_update(&usdc), &health_accounts)], &[]);
+    assert_eq!(return_data::<u64>(&metadata), 20_000_000);
+
+    // Withdrawing more than 3/8 SOL would bring the liquidation value below the debt
+    let metadata = env.send(&[instructions::get_max_withdrawable(&user_account, &sol, &env.price_update(&sol), &health_accounts)], &[]);
+    assert_eq!(return_data::<u64>(&metadata), 375_000_000);
+
+    // The SOL price at which $80 of liquidation value shrinks to the $50 debt
+    let metadata = env.send(&[instructions::get_liquidation_price(&user_account, &sol, &env.price_update(&sol), &health_accounts)], &[]);
+    let view: LiquidationPriceView = return_data::<Option<_>>(&metadata).unwrap();
+    assert_eq!(view.price as f64 * 10f64.powi(view.exponent), 62.5);
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use common::*;
+use lending::constants::{PAUSE_LIQUIDATE, SECONDS_PER_YEAR};
+use lending::error::ErrorCode;
+use lending::events::LiquidationEvent;
+use lending::state::RiskParams;
+use lending_client::{instructions, pda};
+
+/// A user with 1 SOL of collateral at $100 borrowing 70 USDC, and a liquidator holding 1000 USDC
+struct Fixture {
+    env: TestEnv,
+    usdc: Pubkey,
+    sol: Pubkey,
+    user: Pubkey,
+    liquidator: Pubkey,
+}
+
+fn setup() -> Fixture {
+    let mut env = TestEnv::new();
+    let usdc = env.create_mint(6, &spl_token::ID);
+    let sol = env.create_mint(9, &spl_token::ID);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.init_bank(&sol, 80, 70, usd(100));
+    env.supply(&usdc, 100_000_000);
+
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+    env.borrow(&user, 0, &usdc, 70_000_000).unwrap();
+    let liquidator = env.wallet();
+    env.mint_to(&liquidator, &usdc, 1_000_000_000);
+    Fixture { env, usdc, sol, user, liquidator }
+}
+
+impl Fixture {
+    fn liquidate(&mut self) -> Result<TransactionMetadata, TransactionError> {
+        let remaining_accounts = self.env.health_accounts(&self.user, 0);
+        let ix = instructions::liquidate(
+            &self.liquidator,
+            &pda::user(&self.user, 0),
+            &self.sol,
+            &self.usdc,
+            &spl_token::ID,
+            &remaining_accounts,
+        );
+        self.env.process(&[ix], &[self.liquidator])
+    }
+
+    fn position(&self, mint: &Pubkey) -> (u64, u64) {
+        self.env
+            .user(&self.user, 0)
+            .position(&pda::bank(mint))
+            .map_or((0, 0), |position| (position.deposit_shares, position.borrow_shares))
+    }
+}
+
+#[test]
+fn healthy_user_cannot_be_liquidated() {
+    let mut fixture = setup();
+    assert_error(fixture.liquidate(), ErrorCode::NotUndercollateralized);
+}
+
+#[test]
+fn liquidation_after_price_drop() {
+    let mut fixture = setup();
+    // $64 of liquidation value against $70 of debt
+    fixture.env.set_price(&fixture.sol, usd(80));
+
+    let metadata = fixture.liquidate().unwrap();
+    let event = &events::<LiquidationEvent>(&metadata)[0];
+    assert_eq!((event.user, event.owner), (pda::user(&fixture.user, 0), fixture.user));
+    // Half the debt is repaid, for collateral worth it plus the 5% bonus
+    assert_eq!(event.repaid_amount, 35_000_000);
+    assert_eq!(event.seized_amount, 459_375_000);
+    // $34.6 of liquidation value left against $35 of debt
+    assert_eq!(event.health_factor, 98);
+    assert_eq!((event.collateral_price, event.borrowed_price), (usd(80), usd(1)));
+
+    let (usdc, sol, liquidator) = (fixture.usdc, fixture.sol, fixture.liquidator);
+    assert_eq!(fixture.env.balance(&liquidator, &usdc), 965_000_000);
+    assert_eq!(fixture.env.balance(&liquidator, &sol), 459_375_000);
+    assert_eq!(fixture.position(&sol), (LAMPORTS_PER_SOL - 459_375_000, 0));
+    assert_eq!(fixture.position(&usdc), (0, 35_000_000));
+    let usdc_bank = fixture.env.bank(&usdc);
+    assert_eq!((usdc_bank.total_borrowed, usdc_bank.total_borrowed_shares), (35_000_000, 35_000_000));
+    let sol_bank = fixture.env.bank(&sol);
+    assert_eq!(sol_bank.total_deposits, LAMPORTS_PER_SOL - 459_375_000);
+    assert_eq!(fixture.env.treasury_balance(&sol), LAMPORTS_PER_SOL - 459_375_000);
+    assert_eq!(fixture.env.user(&fixture.user, 0).health_factor, 98);
+}
+
+#[test]
+fn liquidation_requires_distinct_banks() {
+    let mut fixture = setup();
+    let (sol, liquidator) = (fixture.sol, fixture.liquidator);
+    fixture.env.set_price(&sol, usd(110));
+    fixture.env.borrow(&fixture.user, 0, &sol, LAMPORTS_PER_SOL / 100).unwrap();
+    fixture.env.set_price(&sol, usd(80));
+    fixture.env.mint_to(&liquidator, &sol, LAMPORTS_PER_SOL);
+
+    // Repaying the SOL borrow with the SOL collateral would lose one side's update of the bank
+    let remaining_accounts = fixture.env.health_accounts(&fixture.user, 0);
+    let ix = instructions::liquidate(&liquidator, &pda::user(&fixture.user, 0), &sol, &sol, &spl_token::ID, &remaining_accounts);
+    assert_error(fixture.env.process(&[ix], &[liquidator]), ErrorCode::SameBank);
+    fixture.liquidate().unwrap();
+}
+
+#[test]
+fn liquidation_of_collateral_disabled_by_the_bank() {
+    let mut fixture = setup();
+    let sol = fixture.sol;
+    let params = RiskParams { collateral_enabled: false, ..fixture.env.risk_params(&sol) };
+    fixture.env.set_risk_params(&sol, params);
+
+    // The SOL no longer backs the debt but can still be seized to repay it
+    let event = events::<LiquidationEvent>(&fixture.liquidate().unwrap()).remove(0);
+    assert_eq!(event.health_factor, 0);
+    assert_eq!(event.repaid_amount, 35_000_000);
+    assert_eq!(event.seized_amount, 367_500_000);
+    assert_eq!(fixture.position(&sol), (LAMPORTS_PER_SOL - 367_500_000, 0));
+}
+
+#[test]
+fn liquidation_with_transfer_fee() {
+    let mut env = TestEnv::new();
+    // 1% of every USDC transfer is withheld by the mint
+    let usdc = env.create_transfer_fee_mint(6, 100, u64::MAX);
+    let sol = env.create_mint(9, &spl_token_2022::ID);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.init_bank(&sol, 80, 70, usd(100));
+    env.supply(&usdc, 100_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+    env.borrow(&user, 0, &usdc, 70_000_000).unwrap();
+    let liquidator = env.wallet();
+    env.mint_to(&liquidator, &usdc, 1_000_000_000);
+    env.set_price(&sol, usd(80));
+
+    let remaining_accounts = env.health_accounts(&user, 0);
+    let ix = instructions::liquidate(&liquidator, &pda::user(&user, 0), &sol, &usdc, &spl_token_2022::ID, &remaining_accounts);
+    let event = events::<LiquidationEvent>(&env.send(&[ix], &[liquidator])).remove(0);
+
+    // Only the 34.65 USDC that arrived repays the debt, and the seized SOL shrinks in proportion
+    assert_eq!(event.repaid_amount, 34_650_000);
+    assert_eq!(event.seized_amount, 454_781_250);
+    assert_eq!(env.balance(&liquidator, &usdc), 965_000_000);
+    assert_eq!(env.balance(&liquidator, &sol), 454_781_250);
+    assert_eq!(env.treasury_balance(&usdc), 99_000_000 - 70_000_000 + 34_650_000);
+    let position = env.user(&user, 0).position(&pda::bank(&usdc)).unwrap().borrow_shares;
+    assert_eq!(position, 70_000_000 - 34_650_000);
+    let bank = env.bank(&usdc);
+    assert_eq!((bank.total_borrowed, bank.total_borrowed_shares), (35_350_000, 35_350_000));
+}
+
+#[test]
+fn liquidation_capped_by_collateral() {
+    let mut fixture = setup();
+    fixture.env.set_price(&fixture.sol, usd(30));
+
+    // All of the $30 collateral is seized for the debt it covers including the bonus
+    let event = events::<LiquidationEvent>(&fixture.liquidate().unwrap()).remove(0);
+    assert_eq!(event.seized_amount, LAMPORTS_PER_SOL);
+    assert_eq!(event.repaid_amount, 28_571_428);
+    assert!(fixture.env.user(&fixture.user, 0).position(&pda::bank(&fixture.sol)).is_none());
+    assert_eq!(fixture.position(&fixture.usdc), (0, 70_000_000 - 28_571_428));
+}
+
+#[test]
+fn liquidation_after_interest_accrual() {
+    let mut fixture = setup();
+    let usdc = fixture.usdc;
+    let params = RiskParams { interest_rate: 10_000, base_rate: 0, ..fixture.env.risk_params(&usdc) };
+    fixture.env.set_risk_params(&usdc, params);
+    assert_error(fixture.liquidate(), ErrorCode::NotUndercollateralized);
+
+    // 70% utilization borrows at 70% a year, the debt grows past the $80 liquidation value
+    fixture.env.warp(SECONDS_PER_YEAR as i64);
+    let event = events::<LiquidationEvent>(&fixture.liquidate().unwrap()).remove(0);
+    assert!(event.health_factor < 100);
+    let bank = fixture.env.bank(&usdc);
+    assert_eq!(bank.last_updated, fixture.env.now());
+    assert!(bank.total_borrowed > 70_000_000 - event.repaid_amount);
+}
+
+#[test]
+fn liquidation_can_be_paused() {
+    let mut fixture = setup();
+    let admin = fixture.env.admin;
+    fixture.env.set_price(&fixture.sol, usd(80));
+    fixture.env.send(&[instructions::set_protocol_pause(&admin, PAUSE_LIQUIDATE)], &[admin]);
+    assert_error(fixture.liquidate(), ErrorCode::ActionPaused);
+    fixture.env.send(&[instructions::set_protocol_pause(&admin, 0)], &[admin]);
+    fixture.liquidate().unwrap();
+}
+
+#[test]
+fn emode_liquidation_bonus() {
+    let mut env = TestEnv::new();
+    let admin = env.admin;
+    let usdc = env.create_mint(6, &spl_token::ID);
+    let usdt = env.create_mint(6, &spl_token::ID);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.init_bank(&usdt, 80, 70, usd(1));
+    env.send(&[instructions::init_emode_category(&admin, 1, 90, 95, 2)], &[admin]);
+    env.set_bank_emode_category(&usdc, 1);
+    env.set_bank_emode_category(&usdt, 1);
+    env.supply(&usdt, 1_000_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &usdc, 100_000_000);
+    let emode_accounts = [anchor_lang::prelude::AccountMeta::new_readonly(pda::emode_category(1), false)];
+    env.send(&[instructions::set_user_emode(&user, 0, 1, &emode_accounts)], &[user]);
+    env.borrow(&user, 0, &usdt, 90_000_000).unwrap();
+
+    // USDC depegs to $0.90, $85.5 of liquidation value against $90 of debt
+    env.set_price(&usdc, usd(9) / 10);
+    let liquidator = env.wallet();
+    env.mint_to(&liquidator, &usdt, 100_000_000);
+    let remaining_accounts = env.health_accounts(&user, 0);
+    let metadata = env.send(
+        &[instructions::liquidate(&liquidator, &pda::user(&user, 0), &usdc, &usdt, &spl_token::ID, &remaining_accounts)],
+        &[liquidator],
+    );
+    let event = &events::<LiquidationEvent>(&metadata)[0];
+    // The category's 2% bonus applies instead of the bank's 5%
+    assert_eq!(event.repaid_amount, 45_000_000);
+    assert_eq!(event.seized_amount, 51_000_000);
+    // $41.9 of liquidation value left against $45 of debt
+    assert_eq!(event.health_factor, 93);
+}
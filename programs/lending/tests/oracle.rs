@@ -0,0 +1,129 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::runtime::TestAccount;
+use common::*;
+use lending::error::ErrorCode;
+use lending::events::LiquidationEvent;
+use lending_client::mock_oracle::MockPriceUpdate;
+use lending_client::{instructions, pda};
+use pyth_solana_receiver_sdk::error::GetPriceError;
+use pyth_solana_receiver_sdk::price_update::VerificationLevel;
+
+/// A user with 1 SOL of collateral at $100 and a USDC bank to borrow from
+fn setup() -> (TestEnv, Pubkey, Pubkey, Pubkey) {
+    let mut env = TestEnv::new();
+    let usdc = env.create_mint(6, &spl_token::ID);
+    let sol = env.create_mint(9, &spl_token::ID);
+    env.init_bank(&usdc, 80, 70, usd(1));
+    env.init_bank(&sol, 80, 70, usd(100));
+    env.supply(&usdc, 100_000_000);
+    let user = env.wallet();
+    env.deposit_collateral(&user, 0, &sol, LAMPORTS_PER_SOL);
+    (env, usdc, sol, user)
+}
+
+fn max_borrowable(env: &mut TestEnv, user: &Pubkey, mint: &Pubkey) -> u64 {
+    let health_accounts = env.health_accounts(user, 0);
+    let ix = instructions::get_max_borrowable(&pda::user(user, 0), mint, &env.price_update(mint), &health_accounts);
+    return_data(&env.send(&[ix], &[]))
+}
+
+fn liquidate(env: &mut TestEnv, user: &Pubkey, collateral_mint: &Pubkey, borrowed_mint: &Pubkey) -> Result<TransactionMetadata, TransactionError> {
+    let liquidator = env.wallet();
+    env.mint_to(&liquidator, borrowed_mint, 1_000_000_000);
+    let remaining_accounts = env.health_accounts(user, 0);
+    let ix = instructions::liquidate(&liquidator, &pda::user(user, 0), collateral_mint, borrowed_mint, &spl_token::ID, &remaining_accounts);
+    env.process(&[ix], &[liquidator])
+}
+
+#[test]
+fn price_exponent_is_normalized() {
+    let (mut env, usdc, sol, user) = setup();
+    assert_eq!(max_borrowable(&mut env, &user, &usdc), 70_000_000);
+
+    // The same prices expressed with other exponents give the same limit
+    env.set_price_update(&sol, env.mock_price(&sol).price(100_000_000, -6));
+    env.set_price_update(&usdc, env.mock_price(&usdc).price(1, 0));
+    assert_eq!(max_borrowable(&mut env, &user, &usdc), 70_000_000);
+    env.set_price_update(&sol, env.mock_price(&sol).price(1, 2));
+    env.set_price_update(&usdc, env.mock_price(&usdc).price(100, -2));
+    assert_eq!(max_borrowable(&mut env, &user, &usdc), 70_000_000);
+}
+
+#[test]
+fn confidence_and_ema_are_ignored() {
+    let (mut env, usdc, sol, user) = setup();
+    env.set_price_update(&sol, env.mock_price(&sol).conf(usd(50) as u64).ema(usd(10), usd(1) as u64));
+    assert_eq!(max_borrowable(&mut env, &user, &usdc), 70_000_000);
+    env.borrow(&user, 0, &usdc, 70_000_000).unwrap();
+}
+
+#[test]
+fn borrow_rejects_unverified_price() {
+    let (mut env, usdc, sol, user) = setup();
+    let full = env.mock_price(&sol);
+    env.set_price_update(&sol, full.clone().verification_level(VerificationLevel::Partial { num_signatures: 5 }));
+    assert_error(env.borrow(&user, 0, &usdc, 1_000_000), GetPriceError::InsufficientVerificationLevel);
+
+    env.set_price_update(&sol, full);
+    env.borrow(&user, 0, &usdc, 1_000_000).unwrap();
+}
+
+#[test]
+fn borrow_rejects_mismatched_feed() {
+    let (mut env, usdc, sol, user) = setup();
+    // The USDC price update posted for the SOL bank
+    env.set_price_update(&sol, MockPriceUpdate { feed_id: feed_id(&usdc), ..env.mock_price(&sol) });
+    assert_error(env.borrow(&user, 0, &usdc, 1_000_000), GetPriceError::MismatchedFeedId);
+}
+
+#[test]
+fn borrow_rejects_price_update_not_owned_by_receiver() {
+    let (mut env, usdc, sol, user) = setup();
+    let update = env.mock_price(&sol);
+    let address = env.price_update(&sol);
+    env.runtime.set_account(address, TestAccount { lamports: update.lamports(), data: update.data(), owner: Pubkey::new_unique(), executable: false });
+    assert_error(env.borrow(&user, 0, &usdc, 1_000_000), ErrorCode::InvalidRemainingAccounts);
+}
+
+#[test]
+fn borrow_rejects_non_positive_price() {
+    let (mut env, usdc, sol, user) = setup();
+    env.set_price(&sol, 0);
+    assert_error(env.borrow(&user, 0, &usdc, 1_000_000), ErrorCode::InvalidPrice);
+    env.set_price(&sol, -usd(100));
+    assert_error(env.borrow(&user, 0, &usdc, 1_000_000), ErrorCode::InvalidPrice);
+}
+
+#[test]
+fn liquidate_rejects_stale_price() {
+    let (mut env, usdc, sol, user) = setup();
+    env.borrow(&user, 0, &usdc, 70_000_000).unwrap();
+    let now = env.now();
+    let max_price_age = env.bank(&sol).max_price_age as i64;
+
+    // Old enough to be rejected even though it would make the user liquidatable
+    env.set_price_update(&sol, env.mock_price(&sol).price(usd(80), PRICE_EXPONENT).publish_time(now - max_price_age - 1));
+    assert_error(liquidate(&mut env, &user, &sol, &usdc), GetPriceError::PriceTooOld);
+
+    env.set_price_update(&sol, env.mock_price(&sol).publish_time(now - max_price_age));
+    liquidate(&mut env, &user, &sol, &usdc).unwrap();
+}
+
+#[test]
+fn liquidation_threshold_boundary() {
+    let (mut env, usdc, sol, user) = setup();
+    env.borrow(&user, 0, &usdc, 70_000_000).unwrap();
+
+    // At $87.5 the $70 liquidation value exactly covers the debt
+    env.set_price(&sol, usd(175) / 2);
+    assert_error(liquidate(&mut env, &user, &sol, &usdc), ErrorCode::NotUndercollateralized);
+
+    env.set_price(&sol, usd(175) / 2 - 1);
+    let event = events::<LiquidationEvent>(&liquidate(&mut env, &user, &sol, &usdc).unwrap()).remove(0);
+    assert_eq!(event.repaid_amount, 35_000_000);
+    // Repaying half the debt brings the user back above the threshold
+    assert_eq!(event.health_factor, 115);
+}
@@ -55,6 +55,9 @@ describe('Lending Smart Contract Tests', async () => {
   const SOL_PRICE_FEED_ID =
     '0xeaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a';
 
+  // Both banks are priced with the feed account loaded above
+  const feedId = Array.from(Buffer.from(SOL_PRICE_FEED_ID.slice(2), 'hex'));
+
   const solUsdPriceFeedAccount = pythSolanaReceiver
     .getPriceFeedAccountAddress(0, SOL_PRICE_FEED_ID)
     .toBase58();
@@ -113,6 +116,27 @@ describe('Lending Smart Contract Tests', async () => {
 
   console.log('SOL Bank Account', solBankAccount.toBase58());
   // 1. Initialization
+  // This test creates the global protocol config that holds the admin, the guardian and the global pause flags.
+  it('Test Init Protocol Config', async () => {
+    console.log('\n--- Test: Initialize Protocol Config ---');
+
+    // Only the program's upgrade authority can create the config
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey('BPFLoaderUpgradeab1e11111111111111111111111')
+    );
+
+    const initConfigTx = await program.methods
+      .initProtocolConfig(signer.publicKey)
+      .accounts({
+        signer: signer.publicKey,
+        programData,
+      })
+      .rpc({ commitment: 'confirmed' });
+
+    console.log('Transaction Signature for Protocol Config Initialization:', initConfigTx);
+  });
+
   // This test initializes a user account and links it to the mint for USDC, ensuring the user can interact with the protocol.
   it('Test Init User', async () => {
   console.log('\n--- Test: Initialize User ---');
@@ -120,7 +144,7 @@ describe('Lending Smart Contract Tests', async () => {
   console.log(`Signer Address: ${signer.publicKey.toBase58()}`);
     
     const initUserTx = await program.methods
-      .initUser(mintUSDC)
+      .initUser(0)
       .accounts({
         signer: signer.publicKey,
       })
@@ -137,7 +161,7 @@ describe('Lending Smart Contract Tests', async () => {
     console.log(`Treasury Account (USDC): ${usdcBankAccount.toBase58()}`);
 
     const initUSDCBankTx = await program.methods
-      .initBank(new BN(1), new BN(1))
+      .initBank(new BN(1), new BN(1), feedId)
       .accounts({
         signer: signer.publicKey,
         mint: mintUSDC,
@@ -174,7 +198,7 @@ describe('Lending Smart Contract Tests', async () => {
     console.log(`Treasury Account (SOL): ${solBankAccount.toBase58()}`);
   
     const initSOLBankTx = await program.methods
-      .initBank(new BN(1), new BN(1))
+      .initBank(new BN(1), new BN(1), feedId)
       .accounts({
         signer: signer.publicKey,
         mint: mintSOL,
@@ -262,6 +286,24 @@ describe('Lending Smart Contract Tests', async () => {
     console.log('Deposit Transaction Signature:', depositUSDC);
   });
 
+  // The user locks half of the received USDC receipt tokens as collateral in their User account.
+  it('Test Deposit Collateral', async () => {
+    console.log('\n--- Test: Deposit USDC Collateral ---');
+    const collateralShares = 50_000_000_000; // 50 USDC worth of receipt tokens
+    console.log(`Locking ${collateralShares / 10 ** 9} USDC receipt tokens as collateral...`);
+
+    const depositCollateralUSDC = await program.methods
+      .depositCollateral(0, new BN(collateralShares))
+      .accounts({
+        signer: signer.publicKey,
+        mint: mintUSDC,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc({ commitment: 'confirmed' });
+
+    console.log('Deposit Collateral Transaction Signature:', depositCollateralUSDC);
+  });
+
   // The user borrows SOL using their USDC deposit as collateral. The test ensures the borrow logic interacts with the Pyth price feed correctly.
   it('Test Borrow', async () => {
  console.log('\n--- Test: Borrow SOL ---');
@@ -270,14 +312,25 @@ describe('Lending Smart Contract Tests', async () => {
   console.log(`Borrower: ${signer.publicKey.toBase58()}`);
   console.log(`Price Feed Account: ${solUsdPriceFeedAccount}`);
 
+  // A (bank, price update) pair for each of the user's positions: the USDC collateral, then the new SOL borrow
+  const [usdcBank] = PublicKey.findProgramAddressSync([mintUSDC.toBuffer()], program.programId);
+  const [solBank] = PublicKey.findProgramAddressSync([mintSOL.toBuffer()], program.programId);
+  const priceUpdate = new PublicKey(solUsdPriceFeedAccount);
+
   const borrowSOL = await program.methods
-    .borrow(new BN(borrowAmount))
+    .borrow(0, new BN(borrowAmount))
     .accounts({
       signer: signer.publicKey,
       mint: mintSOL,
       tokenProgram: TOKEN_PROGRAM_ID,
-      priceUpdate: solUsdPriceFeedAccount,
+      isolatedBank: null,
     })
+    .remainingAccounts([
+      { pubkey: usdcBank, isSigner: false, isWritable: false },
+      { pubkey: priceUpdate, isSigner: false, isWritable: false },
+      { pubkey: solBank, isSigner: false, isWritable: false },
+      { pubkey: priceUpdate, isSigner: false, isWritable: false },
+    ])
     .rpc({ commitment: 'confirmed' });
 
   console.log('Borrow Transaction Signature:', borrowSOL);
@@ -292,11 +345,12 @@ describe('Lending Smart Contract Tests', async () => {
     console.log(`Treasury Account (SOL): ${solBankAccount.toBase58()}`);
   
     const repaySOL = await program.methods
-      .repay(new BN(repayAmount))
+      .repay(0, new BN(repayAmount))
       .accounts({
         signer: signer.publicKey,
         mint: mintSOL,
         tokenProgram: TOKEN_PROGRAM_ID,
+        isolatedBank: null,
       })
       .rpc({ commitment: 'confirmed' });
   